@@ -0,0 +1,254 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: nesmachine-cli <ROM> [OPTIONS]
+
+Runs a ROM without a display.
+
+Options:
+  --frames <N>             Frames to run. With --until-* or --test-rom, the time limit.
+                           [default: 60, 600 with --until-*, 3600 with --test-rom]
+  --until-pc <ADDR>        Stop when the next instruction is at ADDR
+  --until-mem <ADDR=VALUE> Stop when CPU address ADDR holds VALUE
+  --test-rom               Run until the ROM reports a result at $6000, like blargg's tests
+  --png <PATH>             Save the last frame as PNG
+  --palette <PATH>         Use a .pal file instead of the default palette
+  --cpu                    Print CPU state when done
+  -h, --help               Print help
+
+Numbers are decimal, or hex with a $ or 0x prefix.
+
+Exit status:
+  0  Ran all frames, a stop condition was met, or the test ROM passed
+  1  No stop condition was met within the frame limit, or the test ROM didn't pass
+  2  Error";
+
+/// Frames to run if not specified
+const DEFAULT_FRAMES: usize = 60;
+/// Frame limit for stop conditions if not specified
+const DEFAULT_FRAMES_UNTIL: usize = 600;
+/// Frame limit for test ROMs if not specified. Some run for tens of seconds.
+const DEFAULT_FRAMES_TEST_ROM: usize = 3600;
+
+/// Stop condition, checked between instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Pc(u16),
+    Mem { addr: u16, value: u8 },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args {
+    pub rom: PathBuf,
+    pub frames: usize,
+    pub until: Vec<Condition>,
+    pub test_rom: bool,
+    pub png: Option<PathBuf>,
+    pub palette: Option<PathBuf>,
+    pub print_cpu: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArgsError {
+    Help,
+    MissingRom,
+    MissingValue(String),
+    InvalidValue(String, String),
+    Unexpected(String),
+    Conflict(String, String),
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgsError::Help => write!(f, "Help requested"),
+            ArgsError::MissingRom => write!(f, "No ROM given"),
+            ArgsError::MissingValue(arg) => write!(f, "Missing value for {arg}"),
+            ArgsError::InvalidValue(arg, value) => write!(f, "Invalid value for {arg}: {value}"),
+            ArgsError::Unexpected(arg) => write!(f, "Unexpected argument: {arg}"),
+            ArgsError::Conflict(a, b) => write!(f, "{a} can't be used with {b}"),
+        }
+    }
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgsError> {
+        let mut rom = None;
+        let mut frames = None;
+        let mut until = vec![];
+        let mut test_rom = false;
+        let mut png = None;
+        let mut palette = None;
+        let mut print_cpu = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(ArgsError::MissingValue(arg.clone()));
+            match arg.as_str() {
+                "-h" | "--help" => return Err(ArgsError::Help),
+                "--frames" => {
+                    let value = value()?;
+                    frames = Some(parse_num(&value).ok_or(invalid(&arg, &value))?);
+                }
+                "--until-pc" => {
+                    let value = value()?;
+                    until.push(Condition::Pc(
+                        parse_num(&value).ok_or(invalid(&arg, &value))?,
+                    ));
+                }
+                "--until-mem" => {
+                    let value = value()?;
+                    let (addr, byte) = value
+                        .split_once('=')
+                        .and_then(|(addr, byte)| Some((parse_num(addr)?, parse_num(byte)?)))
+                        .ok_or(invalid(&arg, &value))?;
+                    until.push(Condition::Mem { addr, value: byte });
+                }
+                "--test-rom" => test_rom = true,
+                "--png" => png = Some(PathBuf::from(value()?)),
+                "--palette" => palette = Some(PathBuf::from(value()?)),
+                "--cpu" => print_cpu = true,
+                _ if arg.starts_with('-') || rom.is_some() => {
+                    return Err(ArgsError::Unexpected(arg));
+                }
+                _ => rom = Some(PathBuf::from(arg)),
+            }
+        }
+
+        if test_rom && !until.is_empty() {
+            return Err(ArgsError::Conflict("--test-rom".into(), "--until-*".into()));
+        }
+
+        let default_frames = if test_rom {
+            DEFAULT_FRAMES_TEST_ROM
+        } else if until.is_empty() {
+            DEFAULT_FRAMES
+        } else {
+            DEFAULT_FRAMES_UNTIL
+        };
+
+        Ok(Self {
+            rom: rom.ok_or(ArgsError::MissingRom)?,
+            frames: frames.unwrap_or(default_frames),
+            until,
+            test_rom,
+            png,
+            palette,
+            print_cpu,
+        })
+    }
+}
+
+fn invalid(arg: &str, value: &str) -> ArgsError {
+    ArgsError::InvalidValue(arg.into(), value.into())
+}
+
+/// Decimal, or hex with a `$` or `0x` prefix
+fn parse_num<T: TryFrom<u64>>(value: &str) -> Option<T> {
+    let num = if let Some(hex) = value.strip_prefix('$').or(value.strip_prefix("0x")) {
+        u64::from_str_radix(hex, 16).ok()?
+    } else {
+        value.parse().ok()?
+    };
+    T::try_from(num).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = parse(&["game.nes"]).unwrap();
+        assert_eq!(args.rom, PathBuf::from("game.nes"));
+        assert_eq!(args.frames, DEFAULT_FRAMES);
+        assert!(args.until.is_empty());
+        assert!(!args.test_rom);
+        assert!(args.png.is_none());
+        assert!(!args.print_cpu);
+
+        let args = parse(&["game.nes", "--until-pc", "$c66e"]).unwrap();
+        assert_eq!(args.frames, DEFAULT_FRAMES_UNTIL);
+
+        let args = parse(&["test.nes", "--test-rom"]).unwrap();
+        assert!(args.test_rom);
+        assert_eq!(args.frames, DEFAULT_FRAMES_TEST_ROM);
+    }
+
+    #[test]
+    fn test_all_options() {
+        let args = parse(&[
+            "--frames",
+            "10",
+            "--until-pc",
+            "0xc66e",
+            "--until-mem",
+            "$6000=128",
+            "--png",
+            "out.png",
+            "--palette",
+            "a.pal",
+            "--cpu",
+            "game.nes",
+        ])
+        .unwrap();
+        assert_eq!(
+            args,
+            Args {
+                rom: PathBuf::from("game.nes"),
+                frames: 10,
+                until: vec![
+                    Condition::Pc(0xc66e),
+                    Condition::Mem {
+                        addr: 0x6000,
+                        value: 0x80
+                    }
+                ],
+                test_rom: false,
+                png: Some(PathBuf::from("out.png")),
+                palette: Some(PathBuf::from("a.pal")),
+                print_cpu: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(&[]), Err(ArgsError::MissingRom));
+        assert_eq!(parse(&["a.nes", "--help"]), Err(ArgsError::Help));
+        assert_eq!(
+            parse(&["a.nes", "--frames"]),
+            Err(ArgsError::MissingValue("--frames".into()))
+        );
+        assert_eq!(
+            parse(&["a.nes", "--until-pc", "$10000"]),
+            Err(ArgsError::InvalidValue(
+                "--until-pc".into(),
+                "$10000".into()
+            ))
+        );
+        assert_eq!(
+            parse(&["a.nes", "--until-mem", "$6000"]),
+            Err(ArgsError::InvalidValue(
+                "--until-mem".into(),
+                "$6000".into()
+            ))
+        );
+        assert_eq!(
+            parse(&["a.nes", "b.nes"]),
+            Err(ArgsError::Unexpected("b.nes".into()))
+        );
+        assert_eq!(
+            parse(&["a.nes", "--fast"]),
+            Err(ArgsError::Unexpected("--fast".into()))
+        );
+        assert_eq!(
+            parse(&["a.nes", "--test-rom", "--until-pc", "$8000"]),
+            Err(ArgsError::Conflict("--test-rom".into(), "--until-*".into()))
+        );
+    }
+}
@@ -0,0 +1,177 @@
+mod args;
+
+use std::{fs::File, io::BufWriter, path::Path, process::ExitCode};
+
+use args::{Args, ArgsError, Condition, USAGE};
+use nesmc_emu::{
+    NesMachine, Palette,
+    test_rom::{self, TestResult},
+};
+
+const EXIT_NOT_MET: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+/// How a run ended
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    /// All frames were run
+    Done,
+    /// A stop condition was met
+    Met(Condition),
+    /// Ran out of frames while waiting for a stop condition
+    NotMet,
+    /// Test ROM finished or ran out of frames
+    Tested(TestResult),
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(ArgsError::Help) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    match run(&args) {
+        Ok(Outcome::NotMet) => ExitCode::from(EXIT_NOT_MET),
+        Ok(Outcome::Tested(result)) if !result.passed() => ExitCode::from(EXIT_NOT_MET),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<Outcome, String> {
+    let mut machine = NesMachine::default();
+    if let Some(path) = &args.palette {
+        machine.ppu.palette = Palette::open(path).map_err(|e| format!("{path:?}: {e}"))?;
+    }
+    machine
+        .open_path(&args.rom)
+        .map_err(|e| format!("{:?}: {e}", args.rom))?;
+
+    let (outcome, frames) = if args.test_rom {
+        let result = test_rom::run(&mut machine, args.frames);
+        let frames = result.frames;
+        (Outcome::Tested(result), frames)
+    } else {
+        run_frames(&mut machine, args.frames, &args.until)
+    };
+
+    match &outcome {
+        Outcome::Done => println!("Ran {frames} frames"),
+        Outcome::Met(condition) => println!("Stopped on frame {frames}: {condition:x?}"),
+        Outcome::NotMet => println!("No stop condition met in {frames} frames"),
+        Outcome::Tested(result) => {
+            println!("{} on frame {frames}", result.outcome);
+            if !result.message.is_empty() {
+                println!("{}", result.message.trim_end());
+            }
+        }
+    }
+    if machine.cpu_halted() {
+        println!("CPU halted by JAM at ${:04X}", machine.cpu.pc);
+    }
+    if args.print_cpu {
+        println!("{} CYC:{}", machine.cpu, machine.cycle_count);
+    }
+    if let Some(path) = &args.png {
+        write_png(&machine, path).map_err(|e| format!("{path:?}: {e}"))?;
+    }
+
+    Ok(outcome)
+}
+
+/// Run up to `frames` frames, stopping early if any of `until` is met.
+/// Returns the outcome and the no. of frames completed.
+fn run_frames(machine: &mut NesMachine, frames: usize, until: &[Condition]) -> (Outcome, usize) {
+    let mut frame = 0;
+    let mut scanline = machine.ppu.scanline();
+
+    while frame < frames {
+        machine.step_instruction();
+
+        // Scanline wraps around at the start of a frame.
+        if machine.ppu.scanline() < scanline {
+            frame += 1;
+        }
+        scanline = machine.ppu.scanline();
+
+        if let Some(condition) = until.iter().find(|c| is_met(machine, c)) {
+            return (Outcome::Met(*condition), frame);
+        }
+    }
+
+    if until.is_empty() {
+        (Outcome::Done, frame)
+    } else {
+        (Outcome::NotMet, frame)
+    }
+}
+
+fn is_met(machine: &NesMachine, condition: &Condition) -> bool {
+    match *condition {
+        Condition::Pc(pc) => machine.cpu.pc == pc,
+        Condition::Mem { addr, value } => machine.bus.read_immutable(addr) == value,
+    }
+}
+
+fn write_png(machine: &NesMachine, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), 256, 240);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(machine.ppu.framebuffer()))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_frames() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/nestest.nes").unwrap();
+        let (outcome, frames) = run_frames(&mut machine, 3, &[]);
+        assert_eq!(outcome, Outcome::Done);
+        assert_eq!(frames, 3);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/nestest.nes").unwrap();
+        machine.cpu.pc = 0xc000;
+
+        // Last line of nestest_c000.log
+        let until = [Condition::Pc(0xc66e)];
+        let (outcome, frames) = run_frames(&mut machine, 60, &until);
+        assert_eq!(outcome, Outcome::Met(Condition::Pc(0xc66e)));
+        assert_eq!(machine.cycle_count, 26554);
+        assert!(frames < 60);
+    }
+
+    #[test]
+    fn test_run_until_not_met() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/nestest.nes").unwrap();
+
+        let until = [Condition::Mem {
+            addr: 0x0800,
+            value: 0x42,
+        }];
+        let (outcome, frames) = run_frames(&mut machine, 2, &until);
+        assert_eq!(outcome, Outcome::NotMet);
+        assert_eq!(frames, 2);
+    }
+}
@@ -58,14 +58,14 @@ impl Widget for ScrollSlider<'_> {
             .response;
 
         // scroll wheel
-        if ui.is_enabled() {
-            if let (true, Some(_)) = (
+        if ui.is_enabled()
+            && let (true, Some(_)) = (
                 response.contains_pointer(),
                 ui.input(|i| i.pointer.hover_pos()),
-            ) {
-                let scroll_delta = ui.input(|i| i.raw_scroll_delta).y as isize;
-                *self.value = self.value.saturating_add_signed(scroll_delta);
-            }
+            )
+        {
+            let scroll_delta = ui.input(|i| i.raw_scroll_delta).y as isize;
+            *self.value = self.value.saturating_add_signed(scroll_delta);
         }
 
         response
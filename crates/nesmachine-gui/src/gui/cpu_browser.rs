@@ -175,14 +175,14 @@ impl CpuBrowser {
             .response;
 
         // scroll wheel
-        if !self.follow_pc {
-            if let (true, Some(_)) = (
+        if !self.follow_pc
+            && let (true, Some(_)) = (
                 table_area_response.contains_pointer(),
                 ui.input(|i| i.pointer.hover_pos()),
-            ) {
-                let scroll_delta = ui.input(|i| i.raw_scroll_delta).y as isize / 2;
-                self.slider_pos = self.slider_pos.saturating_add_signed(scroll_delta);
-            }
+            )
+        {
+            let scroll_delta = ui.input(|i| i.raw_scroll_delta).y as isize / 2;
+            self.slider_pos = self.slider_pos.saturating_add_signed(scroll_delta);
         }
     }
 
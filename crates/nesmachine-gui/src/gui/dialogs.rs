@@ -1,51 +1,90 @@
+use std::path::PathBuf;
+
 use egui_toast::{Toast, ToastKind};
+use nesmc_emu::Palette;
 use poll_promise::Promise;
 use rfd::AsyncFileDialog;
 
 use crate::NesMachineApp;
 
+pub struct PickedFile {
+    /// Just the file name on wasm
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+pub type FilePromise = Promise<Option<PickedFile>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pick_file(filter_name: &'static str, extensions: &'static [&'static str]) -> FilePromise {
+    Promise::spawn_async(async move {
+        let f = AsyncFileDialog::new()
+            .add_filter(filter_name, extensions)
+            .pick_file()
+            .await?;
+
+        Some(PickedFile {
+            path: f.path().to_path_buf(),
+            data: f.read().await,
+        })
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn pick_file(filter_name: &'static str, extensions: &'static [&'static str]) -> FilePromise {
+    Promise::spawn_local(async move {
+        let f = AsyncFileDialog::new()
+            .add_filter(filter_name, extensions)
+            .pick_file()
+            .await;
+
+        let Some(f) = f else {
+            return None;
+        };
+        Some(PickedFile {
+            path: PathBuf::from(f.file_name()),
+            data: f.read().await,
+        })
+    })
+}
+
 impl NesMachineApp {
-    #[cfg(not(target_arch = "wasm32"))]
     pub fn open_rom_dialog(&mut self) {
         if self.open_file_fialog.is_some() {
             return;
         }
 
-        let promise = Promise::spawn_async(async {
-            let f = AsyncFileDialog::new()
-                .add_filter("NES file", &["nes"])
-                .pick_file()
-                .await;
+        self.open_file_fialog = Some(pick_file("NES file", &["nes"]));
+    }
 
-            Some(f?.read().await)
-        });
+    pub fn open_palette_dialog(&mut self) {
+        if self.open_palette_dialog.is_some() {
+            return;
+        }
 
-        self.open_file_fialog = Some(promise);
+        self.open_palette_dialog = Some(pick_file("Palette file", &["pal"]));
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn open_rom_dialog(&mut self) {
-        if self.open_file_fialog.is_some() {
+    pub fn check_open_rom_dialog(&mut self) {
+        let Some(promise) = &mut self.open_file_fialog else {
             return;
-        }
+        };
 
-        let promise = Promise::spawn_local(async {
-            let f = AsyncFileDialog::new()
-                .add_filter("NES file", &["nes"])
-                .pick_file()
-                .await;
+        let Some(result) = promise.ready() else {
+            return;
+        };
 
-            let Some(f) = f else {
-                return None;
-            };
-            Some(f.read().await)
-        });
+        if let Some(file) = result
+            && let Err(e) = self.behavior.machine.open_data_at(&file.data, &file.path)
+        {
+            self.error_toast(e);
+        };
 
-        self.open_file_fialog = Some(promise);
+        self.open_file_fialog = None;
     }
 
-    pub fn check_open_rom_dialog(&mut self) {
-        let Some(promise) = &mut self.open_file_fialog else {
+    pub fn check_open_palette_dialog(&mut self) {
+        let Some(promise) = &mut self.open_palette_dialog else {
             return;
         };
 
@@ -53,17 +92,22 @@ impl NesMachineApp {
             return;
         };
 
-        if let Some(data) = result {
-            if let Err(e) = self.behavior.machine.open_data(data) {
-                println!("{e}");
-                self.toasts.add(Toast {
-                    text: format!("{e}").into(),
-                    kind: ToastKind::Error,
-                    ..Default::default()
-                });
+        if let Some(file) = result {
+            match Palette::from_bytes(&file.data) {
+                Ok(palette) => self.behavior.machine.ppu.palette = palette,
+                Err(e) => self.error_toast(e),
             }
         };
 
-        self.open_file_fialog = None;
+        self.open_palette_dialog = None;
+    }
+
+    pub fn error_toast(&mut self, e: impl std::fmt::Display) {
+        println!("{e}");
+        self.toasts.add(Toast {
+            text: format!("{e}").into(),
+            kind: ToastKind::Error,
+            ..Default::default()
+        });
     }
 }
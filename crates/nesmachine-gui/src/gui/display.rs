@@ -1,5 +1,8 @@
-use egui::{ColorImage, Image, Rect, TextureFilter, TextureOptions, Ui, load::SizedTexture, vec2};
-use nesmc_emu::NesMachine;
+use egui::{
+    ColorImage, EventFilter, Image, InputState, Key, Rect, Sense, TextureFilter, TextureOptions,
+    Ui, load::SizedTexture, vec2,
+};
+use nesmc_emu::{NesMachine, bus::Buttons};
 
 #[derive(Debug)]
 pub struct Display;
@@ -26,5 +29,43 @@ impl Display {
         let center = ui.available_rect_before_wrap().center();
         let rect = Rect::from_center_size(center, size);
         image.paint_at(ui, rect);
+
+        // Click the display to play. Keyboard goes to controller 1 while it has focus.
+        let response = ui.interact(rect, ui.id().with("display"), Sense::click());
+        if response.clicked() {
+            response.request_focus();
+        }
+
+        let buttons = if response.has_focus() {
+            ui.memory_mut(|mem| {
+                mem.set_focus_lock_filter(
+                    response.id,
+                    EventFilter {
+                        tab: false,
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        escape: false,
+                    },
+                )
+            });
+            ui.input(keyboard_buttons)
+        } else {
+            Buttons::default()
+        };
+        machine.set_buttons(0, buttons);
+    }
+}
+
+/// Arrows, X: A, Z: B, Shift: Select, Enter: Start
+fn keyboard_buttons(input: &InputState) -> Buttons {
+    Buttons {
+        a: input.key_down(Key::X),
+        b: input.key_down(Key::Z),
+        select: input.modifiers.shift,
+        start: input.key_down(Key::Enter),
+        up: input.key_down(Key::ArrowUp),
+        down: input.key_down(Key::ArrowDown),
+        left: input.key_down(Key::ArrowLeft),
+        right: input.key_down(Key::ArrowRight),
     }
 }
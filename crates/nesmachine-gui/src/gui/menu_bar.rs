@@ -26,6 +26,11 @@ impl NesMachineApp {
                 ui.close_menu();
             }
 
+            if ui.button("Open Palette").clicked() {
+                self.open_palette_dialog();
+                ui.close_menu();
+            }
+
             ui.separator();
 
             if ui.add(quit_button).clicked() {
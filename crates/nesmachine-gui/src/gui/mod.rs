@@ -13,6 +13,7 @@ mod ppu_pattern_inspector;
 
 pub use cpu_browser::CpuBrowser;
 pub use cpu_inspector::CpuInspector;
+pub use dialogs::FilePromise;
 pub use display::Display;
 pub use playback_control::PlaybackControl;
 pub use ppu_browser::PpuBrowser;
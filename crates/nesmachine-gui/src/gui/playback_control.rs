@@ -30,13 +30,13 @@ impl PlaybackControl {
             }
 
             if ui.button("Frame").clicked() {
-                loop {
-                    machine.step();
-                    if machine.ppu.cycle() == 0 && machine.ppu.scanline() == 0 {
-                        break;
-                    }
-                }
+                machine.step_frame();
             }
         });
+
+        if machine.cpu_halted() {
+            let text = format!("Stopped: CPU halted by JAM at ${:04X}", machine.cpu.pc);
+            ui.colored_label(ui.visuals().warn_fg_color, text);
+        }
     }
 }
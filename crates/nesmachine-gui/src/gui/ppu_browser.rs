@@ -165,10 +165,10 @@ impl PpuBrowser {
 
                 ui.label("PPU pointers");
                 ui.horizontal(|ui| {
-                    let text = format!("0x{:04x}", machine.bus.ppu_regs.ppu_addr);
+                    let text = format!("0x{:04x}", machine.bus.ppu_regs.v);
                     ui.monospace(text);
                     if ui.link("VRAM Access Addr").clicked() {
-                        self.jump_to(machine.bus.ppu_regs.ppu_addr as usize);
+                        self.jump_to(machine.bus.ppu_regs.v as usize);
                     }
                 });
                 ui.horizontal(|ui| {
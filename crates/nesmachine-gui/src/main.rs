@@ -4,14 +4,17 @@ mod playback_state;
 use eframe::egui;
 use egui::{CentralPanel, Frame, Ui, vec2};
 use egui_tiles::{Behavior, LinearDir, SimplificationOptions, TileId, Tiles};
-use egui_toast::Toasts;
+use egui_toast::{Toast, ToastKind, Toasts};
 use gui::*;
 use nesmc_emu::NesMachine;
 use playback_state::{PlaybackCommand, PlaybackState};
-use poll_promise::Promise;
 use std::time::Duration;
 use web_time::Instant;
 
+/// Battery-backed RAM is written out this often, so little is lost if the app is killed or the
+/// browser tab closed.
+const SAVE_RAM_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
 //#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Debug)]
 pub enum Pane {
@@ -106,7 +109,9 @@ struct NesMachineApp {
     behavior: TreeBehavior,
     toasts: Toasts,
 
-    open_file_fialog: Option<Promise<Option<Vec<u8>>>>,
+    open_file_fialog: Option<FilePromise>,
+    open_palette_dialog: Option<FilePromise>,
+    t_next_save_ram_flush: Instant,
 }
 
 impl Default for NesMachineApp {
@@ -159,6 +164,8 @@ impl Default for NesMachineApp {
             behavior: TreeBehavior::new(),
             toasts: Toasts::new(),
             open_file_fialog: None,
+            open_palette_dialog: None,
+            t_next_save_ram_flush: Instant::now(),
         }
     }
 }
@@ -168,13 +175,23 @@ impl NesMachineApp {
         Self::default()
     }
 
+    fn flush_save_ram(&mut self) {
+        if Instant::now() < self.t_next_save_ram_flush {
+            return;
+        }
+        self.t_next_save_ram_flush = Instant::now() + SAVE_RAM_FLUSH_INTERVAL;
+        if let Err(e) = self.behavior.machine.flush_save_ram() {
+            self.error_toast(e);
+        }
+    }
+
     fn update_emu(&mut self) {
         let playback = &mut self.behavior.playback;
         let machine = &mut self.behavior.machine;
 
         if let Some(command) = &playback.command {
             match command {
-                PlaybackCommand::Step => machine.step(),
+                PlaybackCommand::Step => machine.step_instruction(),
                 PlaybackCommand::Reset => machine.reset(),
                 PlaybackCommand::Pause => playback.paused = true,
                 PlaybackCommand::Unpause => {
@@ -189,15 +206,25 @@ impl NesMachineApp {
             loop {
                 // Run until machine reaches next frame
                 loop {
-                    machine.step();
+                    let scanline = machine.ppu.scanline();
+                    machine.step_instruction();
 
-                    if machine.ppu.cycle() == 0 && machine.ppu.scanline() == 0 {
+                    if machine.ppu.scanline() < scanline {
                         break;
                     }
                     if playback.breakpoints.contains(&machine.cpu.pc) {
                         playback.paused = true;
                         break;
                     }
+                    if machine.cpu_halted() {
+                        playback.paused = true;
+                        self.toasts.add(Toast {
+                            text: format!("CPU halted by JAM at ${:04X}", machine.cpu.pc).into(),
+                            kind: ToastKind::Warning,
+                            ..Default::default()
+                        });
+                        break;
+                    }
                 }
 
                 playback.t_next_frame += Duration::from_secs_f64(1. / 60.);
@@ -217,6 +244,7 @@ impl NesMachineApp {
 impl eframe::App for NesMachineApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.check_open_rom_dialog();
+        self.check_open_palette_dialog();
         self.consume_common_shortcuts(ctx);
 
         // GUI
@@ -231,6 +259,13 @@ impl eframe::App for NesMachineApp {
 
         // Emu logic
         self.update_emu();
+        self.flush_save_ram();
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.behavior.machine.flush_save_ram() {
+            println!("{e}");
+        }
     }
 }
 
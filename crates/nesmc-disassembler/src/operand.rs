@@ -4,8 +4,6 @@ use nesmc_emu::NesMachine;
 use nesmc_types::instruction::OpCode;
 
 pub enum Operand {
-    Todo,
-
     A,
     Abs(u16),
     AbsX(u16),
@@ -24,7 +22,6 @@ pub enum Operand {
 impl Debug for Operand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Operand::Todo => write!(f, "Unknown, not yet implemented"),
             Operand::A => write!(f, "A"),
             Operand::Abs(val) => write!(f, "ZpgX {val:04X} "),
             Operand::AbsX(val) => write!(f, "AbsZ {val:02X} + X"),
@@ -46,8 +43,6 @@ impl Operand {
     /// addr is the addr of opcode
     pub fn from_read_machine(op_code: OpCode, machine: &NesMachine, addr: u16) -> Self {
         match op_code {
-            OpCode::Illegal(_) => Self::Todo,
-
             OpCode::AslA | OpCode::LsrA | OpCode::RolA | OpCode::RorA => Self::A,
 
             OpCode::AdcAbs
@@ -108,7 +103,8 @@ impl Operand {
             | OpCode::SloAbsX
             | OpCode::RlaAbsX
             | OpCode::SreAbsX
-            | OpCode::RraAbsX => {
+            | OpCode::RraAbsX
+            | OpCode::ShyAbsX => {
                 let l = machine.bus.read_immutable(addr.wrapping_add(1)) as u16;
                 let h = machine.bus.read_immutable(addr.wrapping_add(2)) as u16;
                 Self::AbsX((h << 8) + l)
@@ -129,7 +125,11 @@ impl Operand {
             | OpCode::SloAbsY
             | OpCode::RlaAbsY
             | OpCode::SreAbsY
-            | OpCode::RraAbsY => {
+            | OpCode::RraAbsY
+            | OpCode::LasAbsY
+            | OpCode::ShaAbsY
+            | OpCode::ShxAbsY
+            | OpCode::TasAbsY => {
                 let l = machine.bus.read_immutable(addr.wrapping_add(1)) as u16;
                 let h = machine.bus.read_immutable(addr.wrapping_add(2)) as u16;
                 Self::AbsY((h << 8) + l)
@@ -146,7 +146,13 @@ impl Operand {
             | OpCode::LdyImm
             | OpCode::OraImm
             | OpCode::SbcImm
-            | OpCode::NopImm => Self::Imm(machine.bus.read_immutable(addr.wrapping_add(1))),
+            | OpCode::NopImm
+            | OpCode::AncImm
+            | OpCode::AlrImm
+            | OpCode::ArrImm
+            | OpCode::AneImm
+            | OpCode::LxaImm
+            | OpCode::SbxImm => Self::Imm(machine.bus.read_immutable(addr.wrapping_add(1))),
 
             OpCode::Jam
             | OpCode::BrkImpl
@@ -212,7 +218,8 @@ impl Operand {
             | OpCode::SloIndY
             | OpCode::RlaIndY
             | OpCode::SreIndY
-            | OpCode::RraIndY => Self::IndY(machine.bus.read_immutable(addr.wrapping_add(1))),
+            | OpCode::RraIndY
+            | OpCode::ShaIndY => Self::IndY(machine.bus.read_immutable(addr.wrapping_add(1))),
 
             OpCode::BccRel
             | OpCode::BcsRel
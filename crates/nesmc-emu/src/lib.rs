@@ -1,3 +1,6 @@
 mod nes_machine;
 
-pub use nes_machine::{NesMachine, NesMachineError, bus};
+pub use nes_machine::{
+    AudioOutput, CPU_CLOCK_HZ, Cpu, CpuBus, CpuStatus, NesMachine, NesMachineError, Palette, bus,
+    test_rom,
+};
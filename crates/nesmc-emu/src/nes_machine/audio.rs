@@ -0,0 +1,277 @@
+use std::f32::consts::PI;
+
+/// NTSC CPU clock: master clock 21.477272 MHz / 12
+pub const CPU_CLOCK_HZ: f64 = 21_477_272. / 12.;
+
+/// First-order high-pass filter
+#[derive(Debug)]
+struct HighPass {
+    alpha: f32,
+    prev_in: f32,
+    prev_out: f32,
+}
+
+impl HighPass {
+    fn new(cutoff: f32, input_rate: f32) -> Self {
+        let rc = 1. / (2. * PI * cutoff);
+        let dt = 1. / input_rate;
+        Self {
+            alpha: rc / (rc + dt),
+            prev_in: 0.,
+            prev_out: 0.,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.prev_out = self.alpha * (self.prev_out + input - self.prev_in);
+        self.prev_in = input;
+        self.prev_out
+    }
+}
+
+/// First-order low-pass filter
+#[derive(Debug)]
+struct LowPass {
+    alpha: f32,
+    prev_out: f32,
+}
+
+impl LowPass {
+    fn new(cutoff: f32, input_rate: f32) -> Self {
+        let rc = 1. / (2. * PI * cutoff);
+        let dt = 1. / input_rate;
+        Self {
+            alpha: dt / (rc + dt),
+            prev_out: 0.,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.prev_out += self.alpha * (input - self.prev_out);
+        self.prev_out
+    }
+}
+
+/// Output samples on each side of the center of a band-limited step
+const STEP_HALF_WIDTH: usize = 16;
+const STEP_WIDTH: usize = 2 * STEP_HALF_WIDTH;
+/// Kernel resolution between two output samples. Phases in between are interpolated.
+const STEP_PHASES: usize = 64;
+/// Cutoff of the band-limited steps, as a fraction of the output sample rate
+const STEP_CUTOFF: f64 = 0.45;
+
+/// Impulse response of the anti-aliasing low-pass, a Blackman-windowed sinc, at every phase
+/// from 0 to 1 output samples. Each row sums to 1, so steps settle at exactly their height.
+fn step_kernel() -> Vec<[f64; STEP_WIDTH]> {
+    use std::f64::consts::PI;
+
+    let half_width = STEP_HALF_WIDTH as f64;
+    (0..=STEP_PHASES)
+        .map(|phase| {
+            let frac = phase as f64 / STEP_PHASES as f64;
+            let mut row = [0.; STEP_WIDTH];
+            for (i, tap) in row.iter_mut().enumerate() {
+                let t = i as f64 - half_width + 1. - frac;
+                let x = 2. * STEP_CUTOFF * t;
+                let sinc = if x == 0. {
+                    1.
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let window = 0.42
+                    + 0.5 * (PI * t / half_width).cos()
+                    + 0.08 * (2. * PI * t / half_width).cos();
+                *tap = sinc * window.max(0.);
+            }
+            let sum: f64 = row.iter().sum();
+            row.map(|tap| tap / sum)
+        })
+        .collect()
+}
+
+/// Turns the mixer output of every CPU cycle into PCM samples.
+///
+/// The mixer level only changes in steps. Each step is drawn into the output as a band-limited
+/// step, low-passed at 0.45x the sample rate, so the edges' harmonics above Nyquist don't fold
+/// back into the audible band. The samples then go through the same filter chain as the NES's
+/// own audio path: high-pass at 90 Hz and 440 Hz, low-pass at 14 kHz.
+#[derive(Debug)]
+pub struct AudioOutput {
+    sample_rate: u32,
+    /// Output samples per CPU cycle
+    samples_per_cycle: f64,
+    /// Time of the current CPU cycle, in output samples past the last one produced
+    time: f64,
+    /// Mixer level of the last CPU cycle
+    level: f32,
+    /// Step kernel rows, see [step_kernel]
+    kernel: Vec<[f64; STEP_WIDTH]>,
+    /// Band-limited level changes waiting to be summed into the next output samples
+    deltas: [f64; STEP_WIDTH],
+    /// Sum of all deltas already output
+    integrator: f64,
+
+    high_pass_90: HighPass,
+    high_pass_440: HighPass,
+    low_pass_14k: LowPass,
+
+    samples: Vec<f32>,
+}
+
+impl AudioOutput {
+    pub fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f32;
+        Self {
+            sample_rate,
+            samples_per_cycle: sample_rate as f64 / CPU_CLOCK_HZ,
+            time: 0.,
+            level: 0.,
+            kernel: step_kernel(),
+            deltas: [0.; STEP_WIDTH],
+            integrator: 0.,
+
+            high_pass_90: HighPass::new(90., rate),
+            high_pass_440: HighPass::new(440., rate),
+            low_pass_14k: LowPass::new(14_000., rate),
+
+            samples: vec![],
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Feed the mixer output of one CPU cycle.
+    pub fn push(&mut self, level: f32) {
+        if level != self.level {
+            self.add_step((level - self.level) as f64);
+            self.level = level;
+        }
+
+        self.time += self.samples_per_cycle;
+        if self.time >= 1. {
+            self.time -= 1.;
+            self.integrator += self.deltas[0];
+            self.deltas.copy_within(1.., 0);
+            self.deltas[STEP_WIDTH - 1] = 0.;
+
+            let sample = self.integrator as f32;
+            let sample = self.high_pass_90.process(sample);
+            let sample = self.high_pass_440.process(sample);
+            let sample = self.low_pass_14k.process(sample);
+            self.samples.push(sample);
+        }
+    }
+
+    /// Spread a level change at [Self::time] over the next output samples.
+    fn add_step(&mut self, delta: f64) {
+        let pos = self.time * STEP_PHASES as f64;
+        let phase = (pos as usize).min(STEP_PHASES - 1);
+        let weight = pos - phase as f64;
+        let (row, next_row) = (&self.kernel[phase], &self.kernel[phase + 1]);
+        for (i, out) in self.deltas.iter_mut().enumerate() {
+            *out += delta * (row[i] + weight * (next_row[i] - row[i]));
+        }
+    }
+
+    /// Take the samples produced so far, in range -1.0..=1.0
+    pub fn take_f32(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Take the samples produced so far as signed 16 bit PCM
+    pub fn take_i16(&mut self) -> Vec<i16> {
+        self.take_f32()
+            .into_iter()
+            .map(|sample| (sample.clamp(-1., 1.) * i16::MAX as f32) as i16)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1 second of a square wave. Returns the exact frequency, as rounded to whole CPU cycles.
+    fn square(output: &mut AudioOutput, freq: f64, amplitude: f32) -> f64 {
+        let half_period = (CPU_CLOCK_HZ / freq / 2.) as usize;
+        for i in 0..CPU_CLOCK_HZ as usize {
+            let high = (i / half_period).is_multiple_of(2);
+            output.push(if high { amplitude } else { 0. });
+        }
+        CPU_CLOCK_HZ / (2 * half_period) as f64
+    }
+
+    #[test]
+    fn test_sample_count() {
+        for rate in [44100, 48000] {
+            let mut output = AudioOutput::new(rate);
+            square(&mut output, 440., 0.);
+            let len = output.take_f32().len() as i64;
+            assert!((len - rate as i64).abs() <= 1);
+            assert!(output.take_f32().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_dc_is_removed() {
+        let mut output = AudioOutput::new(44100);
+        for _ in 0..CPU_CLOCK_HZ as usize {
+            output.push(0.5);
+        }
+        let samples = output.take_f32();
+        assert!(samples.last().unwrap().abs() < 0.001);
+    }
+
+    #[test]
+    fn test_square_wave() {
+        let mut output = AudioOutput::new(48000);
+        square(&mut output, 440., 0.25);
+        let samples = output.take_i16();
+
+        // Settled, centered on zero
+        let settled = &samples[samples.len() / 2..];
+        let peak = settled.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        let mean = settled.iter().map(|s| *s as i64).sum::<i64>() / settled.len() as i64;
+        assert!(peak > i16::MAX as u16 / 16 && peak < i16::MAX as u16 / 2);
+        assert!(mean.abs() < 100);
+    }
+
+    #[test]
+    fn test_square_wave_aliasing() {
+        for rate in [44100, 48000] {
+            let mut output = AudioOutput::new(rate);
+            // Odd harmonics at 30 kHz, 50 kHz, ... all fold back below Nyquist.
+            let freq = square(&mut output, 10_000., 0.25);
+            let samples = output.take_f32();
+            let settled: Vec<f64> = samples[samples.len() / 2..]
+                .iter()
+                .map(|&s| s as f64)
+                .collect();
+
+            // Everything but the fundamental is aliasing.
+            let omega = 2. * std::f64::consts::PI * freq / rate as f64;
+            let n = settled.len() as f64;
+            let (mut re, mut im) = (0., 0.);
+            for (i, s) in settled.iter().enumerate() {
+                re += s * (omega * i as f64).cos();
+                im += s * (omega * i as f64).sin();
+            }
+            let (re, im) = (2. * re / n, 2. * im / n);
+            let fundamental = (re * re + im * im) / 2.;
+            let residual = settled
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    let fit = re * (omega * i as f64).cos() + im * (omega * i as f64).sin();
+                    (s - fit).powi(2)
+                })
+                .sum::<f64>()
+                / n;
+
+            let alias_db = 10. * (residual / fundamental).log10();
+            assert!(alias_db < -60., "{rate} Hz: aliasing at {alias_db:.1} dB");
+        }
+    }
+}
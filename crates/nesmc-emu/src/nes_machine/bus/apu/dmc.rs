@@ -0,0 +1,252 @@
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+/// NTSC output rates in CPU cycles
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// Delta modulation channel. Plays 1-bit delta samples fetched from CPU memory.
+#[derive(Debug, Clone)]
+pub struct Dmc {
+    irq_enabled: bool,
+    loop_flag: bool,
+    timer_period: u16,
+    timer: u16,
+
+    sample_addr: u16,
+    sample_len: u16,
+    current_addr: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+
+    pub irq_flag: bool,
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Self {
+            irq_enabled: false,
+            loop_flag: false,
+            timer_period: RATE_TABLE[0],
+            timer: 0,
+
+            sample_addr: 0xc000,
+            sample_len: 1,
+            current_addr: 0xc000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+
+            irq_flag: false,
+        }
+    }
+}
+
+impl Dmc {
+    /// Write one of the four channel registers.
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+                self.loop_flag = value & 0x40 != 0;
+                self.timer_period = RATE_TABLE[value as usize & 0x0f];
+            }
+            1 => self.output_level = value & 0x7f,
+            2 => self.sample_addr = 0xc000 | ((value as u16) << 6),
+            3 => self.sample_len = ((value as u16) << 4) | 0x01,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Channel enable bit of $4015
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq_flag = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_len;
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_output();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_output(&mut self) {
+        if !self.silence {
+            if self.shift_register & 0x01 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(value) => {
+                    self.shift_register = value;
+                    self.silence = false;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    /// Address of the next sample byte, if the sample buffer needs a refill.
+    pub fn fetch_addr(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_addr)
+        } else {
+            None
+        }
+    }
+
+    /// Fill the sample buffer with the byte read from `fetch_addr`.
+    pub fn fill(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        self.current_addr = match self.current_addr {
+            0xffff => 0x8000,
+            addr => addr + 1,
+        };
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+impl Snapshot for Dmc {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bool(self.irq_enabled);
+        w.bool(self.loop_flag);
+        w.u16(self.timer_period);
+        w.u16(self.timer);
+
+        w.u16(self.sample_addr);
+        w.u16(self.sample_len);
+        w.u16(self.current_addr);
+        w.u16(self.bytes_remaining);
+        w.option_u8(self.sample_buffer);
+
+        w.u8(self.shift_register);
+        w.u8(self.bits_remaining);
+        w.bool(self.silence);
+        w.u8(self.output_level);
+
+        w.bool(self.irq_flag);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.irq_enabled = r.bool()?;
+        self.loop_flag = r.bool()?;
+        self.timer_period = r.u16()?;
+        self.timer = r.u16()?;
+
+        self.sample_addr = r.u16()?;
+        self.sample_len = r.u16()?;
+        self.current_addr = r.u16()?;
+        self.bytes_remaining = r.u16()?;
+        self.sample_buffer = r.option_u8()?;
+
+        self.shift_register = r.u8()?;
+        self.bits_remaining = r.u8()?;
+        self.silence = r.bool()?;
+        self.output_level = r.u8()? & 0x7f;
+
+        self.irq_flag = r.bool()?;
+        if self.timer_period == 0 || self.bits_remaining == 0 {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_playback() {
+        let mut dmc = Dmc::default();
+        dmc.write(0, 0x8f);
+        dmc.write(1, 0x40);
+        dmc.write(2, 0xff);
+        dmc.write(3, 0x00);
+        dmc.set_enabled(true);
+
+        assert_eq!(dmc.fetch_addr(), Some(0xffc0));
+        dmc.fill(0xff);
+        assert_eq!(dmc.fetch_addr(), None);
+        assert!(!dmc.active());
+        assert!(dmc.irq_flag);
+
+        // The current 8 silent bits play out before the sample.
+        for _ in 0..8 * 54 {
+            dmc.clock_timer();
+        }
+        assert_eq!(dmc.output(), 0x40);
+        for _ in 0..8 * 54 {
+            dmc.clock_timer();
+        }
+        assert_eq!(dmc.output(), 0x40 + 16);
+    }
+
+    #[test]
+    fn test_loop_restarts_sample() {
+        let mut dmc = Dmc::default();
+        dmc.write(0, 0x40);
+        dmc.write(2, 0x00);
+        dmc.write(3, 0x01);
+        dmc.set_enabled(true);
+
+        for i in 0..17 {
+            assert_eq!(dmc.fetch_addr(), Some(0xc000 + i));
+            dmc.fill(0);
+            dmc.sample_buffer = None;
+        }
+        assert_eq!(dmc.fetch_addr(), Some(0xc000));
+        assert!(!dmc.irq_flag);
+    }
+}
@@ -0,0 +1,396 @@
+mod dmc;
+mod envelope;
+mod length_counter;
+mod noise;
+mod pulse;
+mod triangle;
+
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+use dmc::Dmc;
+use noise::Noise;
+use pulse::Pulse;
+use triangle::Triangle;
+
+use super::{CpuDevice, Device};
+
+/// NTSC frame counter step timings in CPU cycles
+const FRAME_STEP_1: usize = 7457;
+const FRAME_STEP_2: usize = 14913;
+const FRAME_STEP_3: usize = 22371;
+const FRAME_STEP_4: usize = 29829;
+const FRAME_STEP_5: usize = 37281;
+
+/// 2A03 Audio Processing Unit
+#[derive(Debug, Clone)]
+pub struct Apu {
+    pulse_1: Pulse,
+    pulse_2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    /// CPU cycles since power-up. Pulse channels are clocked on odd cycles.
+    cycle: usize,
+
+    /// CPU cycles since the start of the frame counter sequence
+    frame_cycle: usize,
+    frame_five_step: bool,
+    frame_irq_inhibit: bool,
+    frame_irq: bool,
+    /// CPU cycles until a $4017 write resets the sequence
+    frame_reset_delay: Option<u8>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            pulse_1: Pulse::new(true),
+            pulse_2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            dmc: Dmc::default(),
+
+            cycle: 0,
+
+            frame_cycle: 0,
+            frame_five_step: false,
+            frame_irq_inhibit: false,
+            frame_irq: false,
+            frame_reset_delay: None,
+        }
+    }
+}
+
+impl Apu {
+    /// Step one CPU cycle
+    pub fn step(&mut self) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if !self.cycle.is_multiple_of(2) {
+            self.pulse_1.clock_timer();
+            self.pulse_2.clock_timer();
+        }
+
+        self.step_frame_counter();
+        self.cycle += 1;
+    }
+
+    fn step_frame_counter(&mut self) {
+        if let Some(delay) = self.frame_reset_delay {
+            if delay == 0 {
+                self.frame_reset_delay = None;
+                self.frame_cycle = 0;
+                if self.frame_five_step {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            } else {
+                self.frame_reset_delay = Some(delay - 1);
+            }
+        }
+
+        self.frame_cycle += 1;
+
+        match (self.frame_cycle, self.frame_five_step) {
+            (FRAME_STEP_1 | FRAME_STEP_3, _) => self.clock_quarter_frame(),
+            (FRAME_STEP_2, _) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            // The IRQ flag is raised on three consecutive cycles.
+            (cycle, false) if cycle == FRAME_STEP_4 - 1 => self.raise_frame_irq(),
+            (FRAME_STEP_4, false) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                self.raise_frame_irq();
+            }
+            (cycle, false) if cycle == FRAME_STEP_4 + 1 => {
+                self.raise_frame_irq();
+                self.frame_cycle = 0;
+            }
+            (FRAME_STEP_5, true) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            (cycle, true) if cycle == FRAME_STEP_5 + 1 => self.frame_cycle = 0,
+            _ => (),
+        }
+    }
+
+    /// Envelopes and the triangle's linear counter
+    fn clock_quarter_frame(&mut self) {
+        self.pulse_1.envelope.clock();
+        self.pulse_2.envelope.clock();
+        self.triangle.clock_linear();
+        self.noise.envelope.clock();
+    }
+
+    /// Length counters and sweep units
+    fn clock_half_frame(&mut self) {
+        self.pulse_1.length.clock();
+        self.pulse_2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse_1.clock_sweep();
+        self.pulse_2.clock_sweep();
+    }
+
+    fn raise_frame_irq(&mut self) {
+        if !self.frame_irq_inhibit {
+            self.frame_irq = true;
+        }
+    }
+
+    /// Frame counter or DMC interrupt pending
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq_flag
+    }
+
+    /// Address the DMC wants to read a sample byte from
+    pub fn dmc_fetch_addr(&self) -> Option<u16> {
+        self.dmc.fetch_addr()
+    }
+
+    /// Hand the DMC the sample byte it asked for.
+    pub fn dmc_fill(&mut self, value: u8) {
+        self.dmc.fill(value);
+    }
+
+    /// Current output levels: pulse 1, pulse 2, triangle, noise (0-15) and DMC (0-127)
+    pub fn channel_outputs(&self) -> [u8; 5] {
+        [
+            self.pulse_1.output(),
+            self.pulse_2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        ]
+    }
+
+    /// Non-linear 2A03 mixer. Output is in range 0.0..=1.0
+    pub fn mix(&self) -> f32 {
+        let [pulse_1, pulse_2, triangle, noise, dmc] = self.channel_outputs().map(|out| out as f32);
+
+        let pulse = pulse_1 + pulse_2;
+        let pulse_out = if pulse == 0. {
+            0.
+        } else {
+            95.88 / (8128. / pulse + 100.)
+        };
+
+        let tnd = triangle / 8227. + noise / 12241. + dmc / 22638.;
+        let tnd_out = if tnd == 0. {
+            0.
+        } else {
+            159.79 / (1. / tnd + 100.)
+        };
+
+        pulse_out + tnd_out
+    }
+
+    fn status(&self) -> u8 {
+        (self.pulse_1.length.active() as u8)
+            | ((self.pulse_2.length.active() as u8) << 1)
+            | ((self.triangle.length.active() as u8) << 2)
+            | ((self.noise.length.active() as u8) << 3)
+            | ((self.dmc.active() as u8) << 4)
+            | ((self.frame_irq as u8) << 6)
+            | ((self.dmc.irq_flag as u8) << 7)
+    }
+
+    fn write_status(&mut self, value: u8) {
+        self.pulse_1.length.set_enabled(value & 0x01 != 0);
+        self.pulse_2.length.set_enabled(value & 0x02 != 0);
+        self.triangle.length.set_enabled(value & 0x04 != 0);
+        self.noise.length.set_enabled(value & 0x08 != 0);
+        self.dmc.set_enabled(value & 0x10 != 0);
+    }
+
+    fn write_frame_counter(&mut self, value: u8) {
+        self.frame_five_step = value & 0x80 != 0;
+        self.frame_irq_inhibit = value & 0x40 != 0;
+        if self.frame_irq_inhibit {
+            self.frame_irq = false;
+        }
+        // The sequence resets 3 or 4 CPU cycles later, depending on the alignment.
+        self.frame_reset_delay = Some(if self.cycle.is_multiple_of(2) { 2 } else { 3 });
+    }
+}
+
+impl Device for Apu {
+    fn reset(&mut self) {
+        self.write_status(0);
+        self.frame_irq = false;
+        self.frame_reset_delay = Some(2);
+    }
+}
+
+impl CpuDevice for Apu {
+    /// Reading $4015 acknowledges the frame IRQ.
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.read_immutable(addr);
+        if addr == 0x4015 {
+            self.frame_irq = false;
+        }
+        value
+    }
+
+    fn read_immutable(&self, addr: u16) -> u8 {
+        match addr {
+            0x4015 => self.status(),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse_1.write(addr - 0x4000, value),
+            0x4004..=0x4007 => self.pulse_2.write(addr - 0x4004, value),
+            0x4008..=0x400b => self.triangle.write(addr - 0x4008, value),
+            0x400c..=0x400f => self.noise.write(addr - 0x400c, value),
+            0x4010..=0x4013 => self.dmc.write(addr - 0x4010, value),
+            0x4015 => self.write_status(value),
+            0x4017 => self.write_frame_counter(value),
+            _ => (),
+        }
+    }
+}
+
+impl Snapshot for Apu {
+    fn snapshot(&self, w: &mut StateWriter) {
+        self.pulse_1.snapshot(w);
+        self.pulse_2.snapshot(w);
+        self.triangle.snapshot(w);
+        self.noise.snapshot(w);
+        self.dmc.snapshot(w);
+
+        w.usize(self.cycle);
+
+        w.usize(self.frame_cycle);
+        w.bool(self.frame_five_step);
+        w.bool(self.frame_irq_inhibit);
+        w.bool(self.frame_irq);
+        w.option_u8(self.frame_reset_delay);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.pulse_1.restore(r)?;
+        self.pulse_2.restore(r)?;
+        self.triangle.restore(r)?;
+        self.noise.restore(r)?;
+        self.dmc.restore(r)?;
+
+        self.cycle = r.usize()?;
+
+        self.frame_cycle = r.usize()?;
+        self.frame_five_step = r.bool()?;
+        self.frame_irq_inhibit = r.bool()?;
+        self.frame_irq = r.bool()?;
+        self.frame_reset_delay = r.option_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(apu: &mut Apu, cycles: usize) {
+        for _ in 0..cycles {
+            apu.step();
+        }
+    }
+
+    #[test]
+    fn test_mix() {
+        // Triangle sits at level 15 at power-up.
+        let mut apu = Apu::default();
+        let triangle = 159.79 / (1. / (15. / 8227.) + 100.);
+        assert!((apu.mix() - triangle).abs() < 0.0001);
+
+        apu.dmc.write(1, 0x7f);
+        let max_tnd = 159.79 / (1. / (15. / 8227. + 127. / 22638.) + 100.);
+        assert!((apu.mix() - max_tnd).abs() < 0.0001);
+        assert!(apu.mix() < 1.);
+    }
+
+    #[test]
+    fn test_status_length_counters() {
+        let mut apu = Apu::default();
+        apu.write(0x4003, 0x08);
+        assert_eq!(apu.read(0x4015), 0x00);
+
+        apu.write(0x4015, 0x0f);
+        apu.write(0x4003, 0x08);
+        apu.write(0x4007, 0x08);
+        apu.write(0x400b, 0x08);
+        apu.write(0x400f, 0x08);
+        assert_eq!(apu.read(0x4015), 0x0f);
+
+        apu.write(0x4015, 0x05);
+        assert_eq!(apu.read(0x4015), 0x05);
+    }
+
+    #[test]
+    fn test_length_counter_expires() {
+        let mut apu = Apu::default();
+        apu.write(0x4015, 0x01);
+        // Length 254, index 1
+        apu.write(0x4003, 0x08);
+
+        // Two half frames per 4-step sequence
+        run(&mut apu, 126 * (FRAME_STEP_4 + 1) + FRAME_STEP_2);
+        assert_eq!(apu.read(0x4015) & 0x01, 0x01);
+        run(&mut apu, FRAME_STEP_4 - FRAME_STEP_2);
+        assert_eq!(apu.read(0x4015) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn test_frame_irq() {
+        let mut apu = Apu::default();
+        run(&mut apu, FRAME_STEP_4 - 2);
+        assert!(!apu.irq());
+        run(&mut apu, 1);
+        assert!(apu.irq());
+
+        // Reading $4015 acknowledges, but the flag is raised for two more cycles.
+        assert_eq!(apu.read(0x4015) & 0x40, 0x40);
+        run(&mut apu, 2);
+        apu.read(0x4015);
+        assert!(!apu.irq());
+        run(&mut apu, FRAME_STEP_4 + 1);
+        assert!(apu.irq());
+
+        // Inhibit clears the flag.
+        apu.write(0x4017, 0x40);
+        assert!(!apu.irq());
+        run(&mut apu, 2 * (FRAME_STEP_4 + 1));
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_five_step_mode_has_no_irq() {
+        let mut apu = Apu::default();
+        apu.write(0x4017, 0x80);
+        run(&mut apu, 3 * (FRAME_STEP_5 + 1));
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_five_step_write_clocks_half_frame() {
+        let mut apu = Apu::default();
+        apu.write(0x4015, 0x01);
+        apu.write(0x4000, 0x00);
+        // Length 2, index 3
+        apu.write(0x4003, 0x18);
+
+        apu.write(0x4017, 0x80);
+        run(&mut apu, 4);
+        assert_eq!(apu.read(0x4015), 0x01);
+        apu.write(0x4017, 0x80);
+        run(&mut apu, 4);
+        assert_eq!(apu.read(0x4015), 0x00);
+    }
+}
@@ -0,0 +1,132 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+/// NTSC timer periods in CPU cycles
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// Pseudo-random noise channel
+#[derive(Debug, Clone)]
+pub struct Noise {
+    /// Short mode taps bit 6 instead of bit 1, giving a 93-step sequence.
+    short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            short_mode: false,
+            timer_period: PERIOD_TABLE[0],
+            timer: 0,
+            shift_register: 1,
+
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+}
+
+impl Noise {
+    /// Write one of the four channel registers.
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => (),
+            2 => {
+                self.short_mode = value & 0x80 != 0;
+                self.timer_period = PERIOD_TABLE[value as usize & 0x0f];
+            }
+            3 => {
+                self.length.load(value >> 3);
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 0x01 != 0 || !self.length.active() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Snapshot for Noise {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bool(self.short_mode);
+        w.u16(self.timer_period);
+        w.u16(self.timer);
+        w.u16(self.shift_register);
+
+        self.envelope.snapshot(w);
+        self.length.snapshot(w);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.short_mode = r.bool()?;
+        self.timer_period = r.u16()?;
+        self.timer = r.u16()?;
+        self.shift_register = r.u16()?;
+        if self.timer_period == 0 {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+
+        self.envelope.restore(r)?;
+        self.length.restore(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// No. of timer periods until the shift register repeats
+    fn sequence_len(noise: &mut Noise) -> usize {
+        let start = noise.shift_register;
+        let mut len = 0;
+        loop {
+            for _ in 0..noise.timer_period {
+                noise.clock_timer();
+            }
+            len += 1;
+            if noise.shift_register == start {
+                return len;
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequence_lengths() {
+        let mut noise = Noise::default();
+        noise.write(2, 0x00);
+        assert_eq!(sequence_len(&mut noise), 32767);
+
+        noise.write(2, 0x80);
+        assert_eq!(sequence_len(&mut noise), 93);
+    }
+}
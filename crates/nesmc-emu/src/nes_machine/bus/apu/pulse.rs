@@ -0,0 +1,217 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// Pulse (square wave) channel
+#[derive(Debug, Clone)]
+pub struct Pulse {
+    /// Pulse 1 negates with one's complement, pulse 2 with two's complement.
+    ones_complement: bool,
+
+    duty: u8,
+    duty_pos: u8,
+    timer_period: u16,
+    timer: u16,
+
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+
+            duty: 0,
+            duty_pos: 0,
+            timer_period: 0,
+            timer: 0,
+
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+        }
+    }
+
+    /// Write one of the four channel registers.
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.duty = value >> 6;
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00ff) | ((value as u16 & 0x07) << 8);
+                self.length.load(value >> 3);
+                self.duty_pos = 0;
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every APU cycle (2 CPU cycles).
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.duty_pos = (self.duty_pos + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocked by the frame counter every half frame.
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    /// The sweep unit computes this continuously, even while disabled.
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = change + self.ones_complement as u16;
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7ff
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.muted()
+            || !self.length.active()
+            || DUTY_TABLE[self.duty as usize][self.duty_pos as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Snapshot for Pulse {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.u8(self.duty);
+        w.u8(self.duty_pos);
+        w.u16(self.timer_period);
+        w.u16(self.timer);
+
+        self.envelope.snapshot(w);
+        self.length.snapshot(w);
+
+        w.bool(self.sweep_enabled);
+        w.u8(self.sweep_period);
+        w.bool(self.sweep_negate);
+        w.u8(self.sweep_shift);
+        w.bool(self.sweep_reload);
+        w.u8(self.sweep_divider);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.duty = r.u8()? & 0x03;
+        self.duty_pos = r.u8()? & 0x07;
+        self.timer_period = r.u16()?;
+        self.timer = r.u16()?;
+
+        self.envelope.restore(r)?;
+        self.length.restore(r)?;
+
+        self.sweep_enabled = r.bool()?;
+        self.sweep_period = r.u8()?;
+        self.sweep_negate = r.bool()?;
+        self.sweep_shift = r.u8()? & 0x07;
+        self.sweep_reload = r.bool()?;
+        self.sweep_divider = r.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_pulse(ones_complement: bool) -> Pulse {
+        let mut pulse = Pulse::new(ones_complement);
+        pulse.length.set_enabled(true);
+        pulse
+    }
+
+    #[test]
+    fn test_sweep() {
+        let mut pulse = enabled_pulse(true);
+        pulse.write(0, 0x3f);
+        pulse.write(2, 0x00);
+        pulse.write(3, 0x01);
+
+        // Sweep up by period >> 1 every other half frame
+        pulse.write(1, 0x91);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x180);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x180);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x240);
+
+        // One's complement negate every half frame, after the divider reload
+        pulse.write(1, 0x89);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x240);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x240 - 0x120 - 1);
+    }
+
+    #[test]
+    fn test_sweep_mutes_on_overflow() {
+        let mut pulse = enabled_pulse(false);
+        pulse.write(0, 0xbf);
+        pulse.write(2, 0x00);
+        pulse.write(3, 0x05);
+        pulse.write(1, 0x01);
+        pulse.clock_timer();
+        assert_eq!(pulse.output(), 15);
+
+        // Target period $500 + $500 overflows 11 bits even while the sweep is disabled.
+        pulse.write(1, 0x00);
+        assert_eq!(pulse.output(), 0);
+    }
+}
@@ -0,0 +1,104 @@
+use super::length_counter::LengthCounter;
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+#[rustfmt::skip]
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Triangle wave channel
+#[derive(Debug, Clone, Default)]
+pub struct Triangle {
+    timer_period: u16,
+    timer: u16,
+    sequence_pos: u8,
+
+    pub length: LengthCounter,
+
+    /// Also the length counter halt flag
+    linear_control: bool,
+    linear_reload_value: u8,
+    linear_reload: bool,
+    linear_counter: u8,
+}
+
+impl Triangle {
+    /// Write one of the four channel registers.
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.linear_control = value & 0x80 != 0;
+                self.length.halt = self.linear_control;
+                self.linear_reload_value = value & 0x7f;
+            }
+            1 => (),
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0x00ff) | ((value as u16 & 0x07) << 8);
+                self.length.load(value >> 3);
+                self.linear_reload = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.sequence_pos = (self.sequence_pos + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocked by the frame counter every quarter frame.
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.linear_control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// A stopped triangle keeps outputting its last value.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.sequence_pos as usize]
+    }
+}
+
+impl Snapshot for Triangle {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.u16(self.timer_period);
+        w.u16(self.timer);
+        w.u8(self.sequence_pos);
+
+        self.length.snapshot(w);
+
+        w.bool(self.linear_control);
+        w.u8(self.linear_reload_value);
+        w.bool(self.linear_reload);
+        w.u8(self.linear_counter);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.timer_period = r.u16()?;
+        self.timer = r.u16()?;
+        self.sequence_pos = r.u8()? & 0x1f;
+
+        self.length.restore(r)?;
+
+        self.linear_control = r.bool()?;
+        self.linear_reload_value = r.u8()?;
+        self.linear_reload = r.bool()?;
+        self.linear_counter = r.u8()?;
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use super::CpuDevice;
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
 /// CPU Internal RAM
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IRam([u8; Self::SIZE]);
 
 impl IRam {
@@ -31,3 +33,13 @@ impl CpuDevice for IRam {
         self.0[addr as usize % Self::SIZE] = value;
     }
 }
+
+impl Snapshot for IRam {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bytes(&self.0);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        r.bytes(&mut self.0)
+    }
+}
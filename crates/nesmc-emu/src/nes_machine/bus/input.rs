@@ -1,16 +1,204 @@
 use super::CpuDevice;
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
-#[derive(Debug, Default)]
-pub struct Input;
+/// Standard controller button state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons {
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool,
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl From<Buttons> for u8 {
+    /// Shift register order: A is read first.
+    fn from(value: Buttons) -> Self {
+        (value.a as u8)
+            | ((value.b as u8) << 1)
+            | ((value.select as u8) << 2)
+            | ((value.start as u8) << 3)
+            | ((value.up as u8) << 4)
+            | ((value.down as u8) << 5)
+            | ((value.left as u8) << 6)
+            | ((value.right as u8) << 7)
+    }
+}
+
+impl From<u8> for Buttons {
+    fn from(value: u8) -> Self {
+        Self {
+            a: value & 0x01 != 0,
+            b: value & 0x02 != 0,
+            select: value & 0x04 != 0,
+            start: value & 0x08 != 0,
+            up: value & 0x10 != 0,
+            down: value & 0x20 != 0,
+            left: value & 0x40 != 0,
+            right: value & 0x80 != 0,
+        }
+    }
+}
+
+/// Standard controllers in ports 1 ($4016) and 2 ($4017)
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    pub buttons: [Buttons; 2],
+    /// While set, the shift registers are continuously reloaded.
+    strobe: bool,
+    shift_registers: [u8; 2],
+}
+
+impl Input {
+    /// Upper bits aren't driven. They keep the high byte of the address from the bus.
+    const OPEN_BUS: u8 = 0x40;
+
+    /// Port 1 is 0, port 2 is 1. Other ports don't exist and are ignored.
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        if let Some(port_buttons) = self.buttons.get_mut(port) {
+            *port_buttons = buttons;
+        }
+    }
+
+    fn latch(&mut self) {
+        self.shift_registers = self.buttons.map(u8::from);
+    }
+}
 
 impl CpuDevice for Input {
-    fn read(&mut self, _addr: u16) -> u8 {
-        0
+    fn read(&mut self, addr: u16) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let value = self.read_immutable(addr);
+
+        // Official controllers return 1 after all 8 buttons are read.
+        let port = (addr - 0x4016) as usize;
+        self.shift_registers[port] = (self.shift_registers[port] >> 1) | 0x80;
+        value
+    }
+
+    fn read_immutable(&self, addr: u16) -> u8 {
+        let port = (addr - 0x4016) as usize;
+        let bit = if self.strobe {
+            self.buttons[port].a as u8
+        } else {
+            self.shift_registers[port] & 0x01
+        };
+        bit | Self::OPEN_BUS
+    }
+
+    /// Only $4016 gets here, $4017 writes go to the APU frame counter.
+    fn write(&mut self, _addr: u16, value: u8) {
+        self.strobe = value & 0x01 != 0;
+        if self.strobe {
+            self.latch();
+        }
+    }
+}
+
+impl Snapshot for Input {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bytes(&self.buttons.map(u8::from));
+        w.bool(self.strobe);
+        w.bytes(&self.shift_registers);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        let mut buttons = [0; 2];
+        r.bytes(&mut buttons)?;
+        self.buttons = buttons.map(Buttons::from);
+        self.strobe = r.bool()?;
+        r.bytes(&mut self.shift_registers)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn read_immutable(&self, _addr: u16) -> u8 {
-        0
+    fn read_8(input: &mut Input, addr: u16) -> Vec<u8> {
+        (0..8).map(|_| input.read(addr) & 0x01).collect()
     }
 
-    fn write(&mut self, _addr: u16, _value: u8) {}
+    #[test]
+    fn test_serial_reads() {
+        let mut input = Input::default();
+        input.set_buttons(
+            0,
+            Buttons {
+                a: true,
+                start: true,
+                left: true,
+                ..Default::default()
+            },
+        );
+        input.set_buttons(
+            1,
+            Buttons {
+                b: true,
+                right: true,
+                ..Default::default()
+            },
+        );
+
+        input.write(0x4016, 1);
+        input.write(0x4016, 0);
+        assert_eq!(read_8(&mut input, 0x4016), [1, 0, 0, 1, 0, 0, 1, 0]);
+        assert_eq!(read_8(&mut input, 0x4017), [0, 1, 0, 0, 0, 0, 0, 1]);
+
+        // Exhausted shift register reads 1, upper bits are open bus.
+        assert_eq!(input.read(0x4016), 0x41);
+        assert_eq!(input.read(0x4017), 0x41);
+    }
+
+    #[test]
+    fn test_strobe_high_reads_a() {
+        let mut input = Input::default();
+        input.write(0x4016, 1);
+        assert_eq!(input.read(0x4016), 0x40);
+
+        input.set_buttons(
+            0,
+            Buttons {
+                a: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(input.read(0x4016), 0x41);
+        assert_eq!(input.read(0x4016), 0x41);
+    }
+
+    #[test]
+    fn test_buttons_latched_on_strobe() {
+        let mut input = Input::default();
+        input.set_buttons(
+            0,
+            Buttons {
+                a: true,
+                ..Default::default()
+            },
+        );
+        input.write(0x4016, 1);
+        input.write(0x4016, 0);
+
+        input.set_buttons(0, Buttons::default());
+        assert_eq!(input.read(0x4016), 0x41);
+    }
+
+    #[test]
+    fn test_missing_port_ignored() {
+        let mut input = Input::default();
+        let buttons = Buttons {
+            a: true,
+            ..Default::default()
+        };
+        input.set_buttons(2, buttons);
+        input.set_buttons(usize::MAX, buttons);
+        assert_eq!(input.buttons, [Buttons::default(); 2]);
+    }
 }
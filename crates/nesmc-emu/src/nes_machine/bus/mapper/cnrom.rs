@@ -0,0 +1,292 @@
+use super::{MapperIo, NametableArrangement};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+const KIB: usize = 1024;
+
+/// CNROM (mapper 3): fixed PRG, switchable 8KB CHR bank.
+#[derive(Debug, Clone)]
+pub struct Cnrom {
+    /// Optional 8 KB program ram at $6000-$7fff. Not on the original boards.
+    prg_ram: Option<Vec<u8>>,
+    /// PRG-RAM is battery-backed.
+    battery: bool,
+    /// CPU 0x8000..=0xffff. 16KB is mirrored.
+    prg_rom: Vec<u8>,
+    /// CHR ROM, or 8 KB of CHR RAM if the cart has no CHR ROM.
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    /// Belongs to console, but routed by mapper. The upper 2KB is on the cartridge, and only
+    /// used with four-screen arrangement.
+    vram: [u8; 0x1000],
+    arrangement: NametableArrangement,
+
+    /// 8KB bank no.
+    chr_bank: usize,
+    /// The ROM drives the data bus during register writes, so the written value is ANDed with
+    /// the ROM byte at the same address. Some boards prevent this.
+    bus_conflicts: bool,
+}
+
+impl Cnrom {
+    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, arrangement: NametableArrangement) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; 8 * KIB]
+        } else {
+            chr_rom
+        };
+
+        Self {
+            prg_ram: None,
+            battery: false,
+            prg_rom,
+            chr,
+            chr_is_ram,
+            vram: [0; 0x1000],
+            arrangement,
+
+            chr_bank: 0,
+            bus_conflicts: true,
+        }
+    }
+
+    /// None by default. Carts with a trainer or battery, or NES 2.0 PRG-RAM get 8 KB.
+    pub fn set_prg_ram(&mut self, prg_ram: Option<Vec<u8>>) {
+        self.prg_ram = prg_ram;
+    }
+
+    /// Off by default.
+    pub fn set_battery(&mut self, battery: bool) {
+        self.battery = battery;
+    }
+
+    pub fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    /// On by default, like the original boards.
+    pub fn set_bus_conflicts(&mut self, enabled: bool) {
+        self.bus_conflicts = enabled;
+    }
+
+    fn map_prg_rom_mirror(&self, addr: u16) -> usize {
+        (addr as usize - 0x8000) % self.prg_rom.len()
+    }
+
+    /// PPU pattern tables $0000-$1fff
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let bank_count = self.chr.len() / (8 * KIB);
+        (self.chr_bank % bank_count) * 8 * KIB + addr as usize
+    }
+}
+
+impl MapperIo for Cnrom {
+    fn read_cpu(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x5fff => 0,
+            0x6000..=0x7fff => match &self.prg_ram {
+                Some(prg_ram) => prg_ram[(addr as usize - 0x6000) % prg_ram.len()],
+                None => 0,
+            },
+            0x8000..=0xffff => self.prg_rom[self.map_prg_rom_mirror(addr)],
+        }
+    }
+
+    fn write_cpu(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x5fff => (),
+            0x6000..=0x7fff => {
+                if let Some(prg_ram) = &mut self.prg_ram {
+                    let len = prg_ram.len();
+                    prg_ram[(addr as usize - 0x6000) % len] = value;
+                }
+            }
+            0x8000..=0xffff => {
+                let value = if self.bus_conflicts {
+                    value & self.read_cpu(addr)
+                } else {
+                    value
+                };
+                self.chr_bank = value as usize;
+            }
+        }
+    }
+
+    fn read_ppu(&self, addr: u16) -> u8 {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff => self.chr[self.map_chr_addr(addr)],
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000],
+            _ => 0,
+        }
+    }
+
+    fn write_ppu(&mut self, addr: u16, value: u8) {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff if self.chr_is_ram => {
+                let chr_addr = self.map_chr_addr(addr);
+                self.chr[chr_addr] = value;
+            }
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000] = value,
+            _ => (),
+        }
+    }
+
+    fn arrangement(&self) -> NametableArrangement {
+        self.arrangement
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.as_deref()
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.as_deref_mut()
+    }
+
+    fn battery(&self) -> bool {
+        self.battery
+    }
+}
+
+impl Snapshot for Cnrom {
+    fn snapshot(&self, w: &mut StateWriter) {
+        if let Some(prg_ram) = &self.prg_ram {
+            w.vec(prg_ram);
+        }
+        if self.chr_is_ram {
+            w.bytes(&self.chr);
+        }
+        w.bytes(&self.vram);
+        w.u8(self.chr_bank as u8);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        if let Some(prg_ram) = &mut self.prg_ram {
+            r.vec_into(prg_ram)?;
+        }
+        if self.chr_is_ram {
+            r.bytes(&mut self.chr)?;
+        }
+        r.bytes(&mut self.vram)?;
+        self.chr_bank = r.u8()? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NesMachine;
+
+    /// CHR ROM where every byte holds its own 8KB bank no.
+    fn numbered_chr(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; 8 * KIB]).collect()
+    }
+
+    #[test]
+    fn test_prg_mirror() {
+        let mut prg = vec![0; 16 * KIB];
+        prg[0x0123] = 0x42;
+        let cnrom = Cnrom::new(
+            prg,
+            numbered_chr(4),
+            NametableArrangement::VerticalArrangement,
+        );
+        assert_eq!(cnrom.read_cpu(0x8123), 0x42);
+        assert_eq!(cnrom.read_cpu(0xc123), 0x42);
+    }
+
+    #[test]
+    fn test_chr_bank_switch() {
+        let mut cnrom = Cnrom::new(
+            vec![0xff; 32 * KIB],
+            numbered_chr(4),
+            NametableArrangement::VerticalArrangement,
+        );
+        assert_eq!(cnrom.read_ppu(0x0000), 0);
+
+        for bank in 0..4 {
+            cnrom.write_cpu(0x8000 + bank as u16 * 0x2000, bank);
+            assert_eq!(cnrom.read_ppu(0x0000), bank);
+            assert_eq!(cnrom.read_ppu(0x1fff), bank);
+        }
+
+        // High bits wrap around the bank count.
+        cnrom.write_cpu(0xffff, 0x05);
+        assert_eq!(cnrom.read_ppu(0x0000), 1);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        let mut prg = vec![0xff; 32 * KIB];
+        prg[0x0000] = 0x01;
+        let mut cnrom = Cnrom::new(
+            prg,
+            numbered_chr(4),
+            NametableArrangement::VerticalArrangement,
+        );
+        assert!(cnrom.bus_conflicts());
+
+        // ROM has $01 at $8000, $ff elsewhere.
+        cnrom.write_cpu(0x8000, 0x03);
+        assert_eq!(cnrom.read_ppu(0x0000), 1);
+        cnrom.write_cpu(0x8001, 0x03);
+        assert_eq!(cnrom.read_ppu(0x0000), 3);
+
+        cnrom.set_bus_conflicts(false);
+        cnrom.write_cpu(0x8000, 0x02);
+        assert_eq!(cnrom.read_ppu(0x0000), 2);
+    }
+
+    #[test]
+    fn test_chr_rom_is_read_only() {
+        let mut cnrom = Cnrom::new(
+            vec![0; 32 * KIB],
+            numbered_chr(2),
+            NametableArrangement::VerticalArrangement,
+        );
+        cnrom.write_ppu(0x0123, 0xab);
+        assert_eq!(cnrom.read_ppu(0x0123), 0);
+
+        cnrom.write_ppu(0x2000, 0x11);
+        assert_eq!(cnrom.read_ppu(0x2400), 0x11);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut cnrom = Cnrom::new(
+            vec![0; 32 * KIB],
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        cnrom.write_ppu(0x0123, 0xab);
+        assert_eq!(cnrom.read_ppu(0x0123), 0xab);
+
+        // Only one bank to switch to.
+        cnrom.write_cpu(0x8000, 0x03);
+        assert_eq!(cnrom.read_ppu(0x0123), 0xab);
+    }
+
+    #[test]
+    fn test_open_data() {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 4, 0x31, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 16 * KIB]);
+        rom.extend(numbered_chr(4));
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        machine.bus.write(0x8000, 0x00);
+        assert_eq!(machine.bus.read_ppu(0x1000), 0);
+
+        let crate::bus::Mapper::Cnrom(cnrom) = &mut machine.bus.cart else {
+            panic!("Expected CNROM");
+        };
+        cnrom.set_bus_conflicts(false);
+        machine.bus.write(0x8000, 0x03);
+        assert_eq!(machine.bus.read_ppu(0x1000), 3);
+    }
+}
@@ -0,0 +1,295 @@
+//! iNES and NES 2.0 file headers
+//!
+//! NES 2.0 is a superset of iNES, identified by bits 2-3 of byte 7 being 0b10. It extends the
+//! mapper no. to 12 bits and adds a submapper, larger ROM sizes, RAM sizes, timing region and
+//! more. See <https://www.nesdev.org/wiki/NES_2.0>.
+
+use std::io::{BufReader, Read};
+
+use crate::nes_machine::NesMachineError;
+
+const KIB: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    INes,
+    /// iNES with garbage in bytes 7-15, like "DiskDude!". Only the low mapper nibble is used.
+    ArchaicINes,
+    Nes2,
+}
+
+/// CPU/PPU timing the ROM was made for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    #[default]
+    Ntsc,
+    Pal,
+    /// Works on both NTSC and PAL
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    #[default]
+    Nes,
+    VsSystem {
+        ppu_type: u8,
+        hardware_type: u8,
+    },
+    Playchoice10,
+    /// NES 2.0 extended console type, like Famiclone with decimal mode or VT01
+    Extended(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct INesHeader {
+    pub format: HeaderFormat,
+    /// 12 bits in NES 2.0, 8 in iNES
+    pub mapper_id: u16,
+    /// NES 2.0 only. 0 otherwise.
+    pub submapper: u8,
+
+    pub len_prg_rom: usize,
+    pub len_chr_rom: usize,
+    /// Volatile PRG-RAM. NES 2.0 only, 0 otherwise: the mapper decides.
+    pub len_prg_ram: usize,
+    /// Battery-backed PRG-RAM. NES 2.0 only.
+    pub len_prg_nvram: usize,
+    /// NES 2.0 only.
+    pub len_chr_ram: usize,
+    /// NES 2.0 only.
+    pub len_chr_nvram: usize,
+
+    /// Hard-wired nametable arrangement, for mappers that don't control it
+    pub v_mirroring: bool,
+    pub battery: bool,
+    /// 512 bytes before PRG ROM
+    pub trainer: bool,
+    /// Four-screen or mapper-specific nametable layout
+    pub alt_nametable_layout: bool,
+
+    pub console: ConsoleType,
+    /// NES 2.0 only.
+    pub timing: Timing,
+    /// No. of miscellaneous ROMs after CHR ROM. NES 2.0 only.
+    pub misc_roms: u8,
+    /// Default expansion device, as listed on the NES 2.0 wiki page. 0 is unspecified.
+    pub expansion_device: u8,
+}
+
+impl INesHeader {
+    const MAGIC: &[u8; 4] = b"NES\x1a";
+
+    pub fn read<R: Read>(reader: &mut BufReader<R>) -> Result<Self, NesMachineError> {
+        let mut header_buf = [0_u8; 16];
+        reader.read_exact(&mut header_buf)?;
+        let header = Self::parse(&header_buf)?;
+
+        if header.console != ConsoleType::Nes {
+            return Err(NesMachineError::MapperUnsupportedFeatures);
+        }
+        Ok(header)
+    }
+
+    pub fn parse(header_buf: &[u8; 16]) -> Result<Self, NesMachineError> {
+        if header_buf[0..=3] != *Self::MAGIC {
+            return Err(NesMachineError::FileInvalidSig);
+        }
+
+        let format = if (header_buf[7] >> 2) & 0x3 == 2 {
+            HeaderFormat::Nes2
+        } else if header_buf[12..=15] == [0; 4] {
+            HeaderFormat::INes
+        } else {
+            HeaderFormat::ArchaicINes
+        };
+
+        let v_mirroring = header_buf[6] & 0x1 != 0;
+        let battery = header_buf[6] & 0x2 != 0;
+        let trainer = header_buf[6] & 0x4 != 0;
+        let alt_nametable_layout = header_buf[6] & 0x8 != 0;
+
+        let mut header = Self {
+            format,
+            mapper_id: (header_buf[6] >> 4) as u16,
+            submapper: 0,
+            len_prg_rom: header_buf[4] as usize * 16 * KIB,
+            len_chr_rom: header_buf[5] as usize * 8 * KIB,
+            len_prg_ram: 0,
+            len_prg_nvram: 0,
+            len_chr_ram: 0,
+            len_chr_nvram: 0,
+            v_mirroring,
+            battery,
+            trainer,
+            alt_nametable_layout,
+            console: ConsoleType::Nes,
+            timing: Timing::Ntsc,
+            misc_roms: 0,
+            expansion_device: 0,
+        };
+        if format == HeaderFormat::ArchaicINes {
+            return Ok(header);
+        }
+
+        header.mapper_id |= (header_buf[7] & 0xf0) as u16;
+        header.console = match header_buf[7] & 0x3 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem {
+                ppu_type: 0,
+                hardware_type: 0,
+            },
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(0),
+        };
+        if format == HeaderFormat::INes {
+            return Ok(header);
+        }
+
+        header.mapper_id |= ((header_buf[8] & 0x0f) as u16) << 8;
+        header.submapper = header_buf[8] >> 4;
+
+        header.len_prg_rom = rom_len(header_buf[4], header_buf[9] & 0x0f, 16 * KIB);
+        header.len_chr_rom = rom_len(header_buf[5], header_buf[9] >> 4, 8 * KIB);
+        header.len_prg_ram = ram_len(header_buf[10] & 0x0f);
+        header.len_prg_nvram = ram_len(header_buf[10] >> 4);
+        header.len_chr_ram = ram_len(header_buf[11] & 0x0f);
+        header.len_chr_nvram = ram_len(header_buf[11] >> 4);
+
+        header.timing = match header_buf[12] & 0x3 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+        header.console = match header.console {
+            ConsoleType::VsSystem { .. } => ConsoleType::VsSystem {
+                ppu_type: header_buf[13] & 0x0f,
+                hardware_type: header_buf[13] >> 4,
+            },
+            ConsoleType::Extended(_) => ConsoleType::Extended(header_buf[13] & 0x0f),
+            console => console,
+        };
+        header.misc_roms = header_buf[14] & 0x3;
+        header.expansion_device = header_buf[15] & 0x3f;
+
+        Ok(header)
+    }
+}
+
+/// NES 2.0 ROM size from the LSB byte and MSB nibble. MSB $f means the LSB is an exponent and
+/// a multiplier instead: 2^E * (MM * 2 + 1).
+fn rom_len(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0x0f {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x3) as usize * 2 + 1;
+        2_usize.saturating_pow(exponent).saturating_mul(multiplier)
+    } else {
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+}
+
+/// NES 2.0 RAM size from a shift count. 0 means none.
+fn ram_len(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(bytes: [u8; 12]) -> [u8; 16] {
+        let mut header = [0; 16];
+        header[..4].copy_from_slice(b"NES\x1a");
+        header[4..].copy_from_slice(&bytes);
+        header
+    }
+
+    #[test]
+    fn test_ines() {
+        let header = INesHeader::parse(&header([2, 1, 0x41, 0x10, 0, 0, 0, 0, 0, 0, 0, 0]));
+        let header = header.unwrap();
+        assert_eq!(header.format, HeaderFormat::INes);
+        assert_eq!(header.mapper_id, 0x14);
+        assert_eq!(header.len_prg_rom, 32 * KIB);
+        assert_eq!(header.len_chr_rom, 8 * KIB);
+        assert!(header.v_mirroring);
+        assert_eq!(header.console, ConsoleType::Nes);
+    }
+
+    #[test]
+    fn test_archaic_ines() {
+        // "DiskDude!" over bytes 7-15
+        let mut bytes = header([2, 1, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes[7..].copy_from_slice(b"DiskDude!");
+        let header = INesHeader::parse(&bytes).unwrap();
+        assert_eq!(header.format, HeaderFormat::ArchaicINes);
+        assert_eq!(header.mapper_id, 4);
+    }
+
+    #[test]
+    fn test_nes2() {
+        #[rustfmt::skip]
+        let bytes = header([
+            0x02, 0x00,       // PRG, CHR LSB
+            0x12, 0x48,       // Battery, mapper $x41
+            0x25,             // Submapper 2, mapper $5xx
+            0x10,             // PRG MSB 0, CHR MSB 1
+            0x97,             // PRG-NVRAM 32KB, PRG-RAM 8KB
+            0x07,             // CHR-RAM 8KB
+            0x01, 0x00, 0x00, // PAL
+            0x03,             // Four Score
+        ]);
+        let header = INesHeader::parse(&bytes).unwrap();
+        assert_eq!(header.format, HeaderFormat::Nes2);
+        assert_eq!(header.mapper_id, 0x541);
+        assert_eq!(header.submapper, 2);
+        assert_eq!(header.len_prg_rom, 32 * KIB);
+        assert_eq!(header.len_chr_rom, 0x100 * 8 * KIB);
+        assert_eq!(header.len_prg_ram, 8 * KIB);
+        assert_eq!(header.len_prg_nvram, 32 * KIB);
+        assert_eq!(header.len_chr_ram, 8 * KIB);
+        assert_eq!(header.len_chr_nvram, 0);
+        assert!(header.battery);
+        assert_eq!(header.timing, Timing::Pal);
+        assert_eq!(header.expansion_device, 3);
+    }
+
+    #[test]
+    fn test_nes2_exponent_size() {
+        // 2^7 * 3 = 384 bytes of PRG
+        let bytes = header([0x1d, 0, 0, 0x08, 0, 0x0f, 0, 0, 0, 0, 0, 0]);
+        let header = INesHeader::parse(&bytes).unwrap();
+        assert_eq!(header.len_prg_rom, 384);
+    }
+
+    #[test]
+    fn test_nes2_console_type() {
+        let bytes = header([1, 1, 0, 0x09, 0, 0, 0, 0, 0, 0x21, 0, 0]);
+        let header = INesHeader::parse(&bytes).unwrap();
+        assert_eq!(
+            header.console,
+            ConsoleType::VsSystem {
+                ppu_type: 1,
+                hardware_type: 2,
+            }
+        );
+
+        let bytes = self::header([1, 1, 0, 0x0b, 0, 0, 0, 0, 0, 0x03, 0, 0]);
+        assert_eq!(
+            INesHeader::parse(&bytes).unwrap().console,
+            ConsoleType::Extended(3)
+        );
+    }
+
+    #[test]
+    fn test_invalid_sig() {
+        let mut bytes = header([0; 12]);
+        bytes[3] = 0;
+        assert!(matches!(
+            INesHeader::parse(&bytes),
+            Err(NesMachineError::FileInvalidSig)
+        ));
+    }
+}
@@ -1,8 +1,10 @@
 use super::{MapperIo, NametableArrangement};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
 const KIB: usize = 1024;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PrgBankMode {
     /// 1x32KB. Switched. Ignores low bit of bank no.
     Big,
@@ -14,7 +16,7 @@ enum PrgBankMode {
 
 impl PrgBankMode {
     pub fn from_sr_byte(value: u8) -> Self {
-        match (value >> 2) | 0x03 {
+        match (value >> 2) & 0x03 {
             0 | 1 => Self::Big,
             2 => Self::SplitFixFirst,
             3 => Self::SplitFixLast,
@@ -23,9 +25,9 @@ impl PrgBankMode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ChrBankMode {
-    /// 1x8KB. Switched.
+    /// 1x8KB. Switched. Ignores low bit of bank no.
     Big,
     /// 2x4KB. Switched.
     Split,
@@ -33,52 +35,85 @@ enum ChrBankMode {
 
 impl ChrBankMode {
     pub fn from_sr_byte(value: u8) -> Self {
-        if value & (1 << 5) != 0 {
-            Self::Big
-        } else {
+        if value & (1 << 4) != 0 {
             Self::Split
+        } else {
+            Self::Big
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mmc1 {
     /// Optional 8 KB program ram
     prg_ram: Option<Vec<u8>>,
+    /// PRG-RAM is battery-backed.
+    battery: bool,
     prg_rom: Vec<u8>,
-    _chr_rom: Vec<u8>,
+    /// CHR ROM, or 8 KB of CHR RAM if the cart has no CHR ROM.
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    /// Belongs to console, but routed by mapper.
+    vram: [u8; 0x800],
 
     /// Shift register
     sr: u8,
     sr_write_counter: u8,
+    /// CPU cycles since the last write to the shift register. Saturates.
+    cycles_since_sr_write: u8,
 
     arrangement: NametableArrangement,
     prg_mode: PrgBankMode,
-    prg_bank_offset: usize,
     chr_mode: ChrBankMode,
+    /// 16KB bank no. (or 32KB bank no. * 2)
+    prg_bank: usize,
+    /// 4KB bank no. (or 8KB bank no. * 2)
+    chr_bank_0: usize,
+    /// 4KB bank no. Unused in 8KB mode.
+    chr_bank_1: usize,
+    prg_ram_enabled: bool,
 }
 
 impl Mmc1 {
     const SR_RESET_BIT: u8 = 0x80;
+    /// Power-on and reset state of the control register: 16KB PRG banks, last bank fixed.
+    const CONTROL_POWER_ON: u8 = 0x0c;
 
     pub fn new(prg_ram: Option<Vec<u8>>, prg_rom: Vec<u8>, chr_rom: Vec<u8>) -> Self {
-        Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; 8 * KIB]
+        } else {
+            chr_rom
+        };
+
+        let mut mmc1 = Self {
             prg_ram,
+            battery: false,
             prg_rom,
-            _chr_rom: chr_rom,
+            chr,
+            chr_is_ram,
+            vram: [0; 0x800],
 
             sr: 0,
             sr_write_counter: 0,
+            cycles_since_sr_write: u8::MAX,
 
-            // TODO: Maybe right, maybe not
             arrangement: NametableArrangement::OneScreenLower,
-            // TODO: Maybe right, maybe not
-            prg_mode: PrgBankMode::Big,
-            // TODO: Start at last bank?
-            prg_bank_offset: 0,
-            // TODO: Maybe right, maybe not
+            prg_mode: PrgBankMode::SplitFixLast,
             chr_mode: ChrBankMode::Big,
-        }
+            prg_bank: 0,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_ram_enabled: true,
+        };
+        mmc1.write_control(Self::CONTROL_POWER_ON);
+        mmc1
+    }
+
+    /// Off by default.
+    pub fn set_battery(&mut self, battery: bool) {
+        self.battery = battery;
     }
 
     /// CPU RAM $6000-$7fff
@@ -86,33 +121,72 @@ impl Mmc1 {
         let Some(prg_ram) = &self.prg_ram else {
             return 0;
         };
-        prg_ram[addr as usize]
+        if !self.prg_ram_enabled {
+            return 0;
+        }
+        prg_ram[(addr as usize - 0x6000) % prg_ram.len()]
+    }
+
+    /// CPU RAM $6000-$7fff
+    fn write_prg_ram(&mut self, addr: u16, value: u8) {
+        if !self.prg_ram_enabled {
+            return;
+        }
+        let Some(prg_ram) = &mut self.prg_ram else {
+            return;
+        };
+        let len = prg_ram.len();
+        prg_ram[(addr as usize - 0x6000) % len] = value;
     }
 
     /// CPU ROM $8000-$ffff
     fn read_prg_rom(&self, addr: u16) -> u8 {
-        let local_addr = (addr - 0x8000) as usize;
-
-        let off_in_bank = match self.prg_mode {
-            PrgBankMode::Big => local_addr % (32 * KIB),
-            PrgBankMode::SplitFixFirst | PrgBankMode::SplitFixLast => local_addr % (16 * KIB),
+        // SUROM and friends use CHR bank bit 4 to select the 256KB outer PRG bank.
+        let outer_bank = if self.prg_rom.len() > 256 * KIB {
+            self.chr_bank_0 & 0x10
+        } else {
+            0
         };
+        let last_bank = outer_bank | 0x0f;
 
-        let mapped_addr = match self.prg_mode {
-            PrgBankMode::Big => off_in_bank + self.prg_bank_offset,
+        let bank_no = match self.prg_mode {
+            PrgBankMode::Big => match addr {
+                0x8000..=0xbfff => outer_bank | (self.prg_bank & 0x0e),
+                _ => outer_bank | (self.prg_bank & 0x0e) | 1,
+            },
             PrgBankMode::SplitFixFirst => match addr {
-                0x8000..=0xbfff => off_in_bank,
-                _ => off_in_bank + self.prg_bank_offset,
+                0x8000..=0xbfff => outer_bank,
+                _ => outer_bank | self.prg_bank,
             },
             PrgBankMode::SplitFixLast => match addr {
-                0xc000..=0xffff => off_in_bank,
-                _ => off_in_bank + self.prg_bank_offset,
+                0x8000..=0xbfff => outer_bank | self.prg_bank,
+                _ => last_bank,
             },
         };
 
+        let off_in_bank = addr as usize % (16 * KIB);
+        let mapped_addr = bank_no * 16 * KIB + off_in_bank;
+
         self.prg_rom[mapped_addr % self.prg_rom.len()]
     }
 
+    /// PPU pattern tables $0000-$1fff
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let off_in_bank = addr as usize % (4 * KIB);
+        let bank_no = match self.chr_mode {
+            ChrBankMode::Big => match addr {
+                0x0000..=0x0fff => self.chr_bank_0 & 0x1e,
+                _ => (self.chr_bank_0 & 0x1e) | 1,
+            },
+            ChrBankMode::Split => match addr {
+                0x0000..=0x0fff => self.chr_bank_0,
+                _ => self.chr_bank_1,
+            },
+        };
+
+        (bank_no * 4 * KIB + off_in_bank) % self.chr.len()
+    }
+
     pub fn write_sr(&mut self, addr: u16, value: u8) {
         //       7      0
         // bits: R______D
@@ -120,9 +194,18 @@ impl Mmc1 {
         // D: Data
         // R: Reset
 
+        // Of two writes on consecutive cycles, like the dummy write and the real one of a
+        // read-modify-write instruction, MMC1 only sees the first.
+        let consecutive = self.cycles_since_sr_write <= 1;
+        self.cycles_since_sr_write = 0;
+        if consecutive {
+            return;
+        }
+
         if value & Self::SR_RESET_BIT > 0 {
             self.sr_write_counter = 0;
             self.sr = 0;
+            self.write_control(self.control() | Self::CONTROL_POWER_ON);
         } else {
             self.sr_write_counter += 1;
             self.sr >>= 1;
@@ -131,7 +214,7 @@ impl Mmc1 {
             if self.sr_write_counter == 5 {
                 match addr {
                     0x0000..=0x7fff => unreachable!(),
-                    0x8000..=0x9fff => self.commit_control(),
+                    0x8000..=0x9fff => self.write_control(self.sr),
                     0xa000..=0xbfff => self.commit_chr_0(),
                     0xc000..=0xdfff => self.commit_chr_1(),
                     0xe000..=0xffff => self.commit_prg(),
@@ -142,7 +225,29 @@ impl Mmc1 {
         }
     }
 
-    fn commit_control(&mut self) {
+    /// Reassemble the control register from its decoded parts.
+    fn control(&self) -> u8 {
+        let arrangement = match self.arrangement {
+            NametableArrangement::OneScreenLower => 0,
+            NametableArrangement::OneScreenUpper => 1,
+            NametableArrangement::HorizontalArrangement => 2,
+            NametableArrangement::VerticalArrangement => 3,
+            // MMC1 can't be set to it.
+            NametableArrangement::FourScreen => unreachable!(),
+        };
+        let prg_mode = match self.prg_mode {
+            PrgBankMode::Big => 0,
+            PrgBankMode::SplitFixFirst => 2,
+            PrgBankMode::SplitFixLast => 3,
+        };
+        let chr_mode = match self.chr_mode {
+            ChrBankMode::Big => 0,
+            ChrBankMode::Split => 1,
+        };
+        arrangement | (prg_mode << 2) | (chr_mode << 4)
+    }
+
+    fn write_control(&mut self, value: u8) {
         //       4   0
         // bits: CPPMM
         //
@@ -150,34 +255,39 @@ impl Mmc1 {
         // P: PRG bank mode
         // M: Arrangement
 
-        self.arrangement = NametableArrangement::from_sr_byte(self.sr);
-        self.prg_mode = PrgBankMode::from_sr_byte(self.sr);
-        self.chr_mode = ChrBankMode::from_sr_byte(self.sr);
+        self.arrangement = NametableArrangement::from_sr_byte(value);
+        self.prg_mode = PrgBankMode::from_sr_byte(value);
+        self.chr_mode = ChrBankMode::from_sr_byte(value);
     }
 
     fn commit_chr_0(&mut self) {
+        //       4   0
+        // bits: CCCCC
         //
+        // C: CHR bank no. 4KB, low bit ignored in 8KB mode.
+        //    Bit 4 doubles as the outer PRG bank on 512KB boards.
+
+        self.chr_bank_0 = self.sr as usize & 0x1f;
     }
 
     fn commit_chr_1(&mut self) {
+        //       4   0
+        // bits: CCCCC
         //
+        // C: CHR bank no. 4KB, ignored in 8KB mode.
+
+        self.chr_bank_1 = self.sr as usize & 0x1f;
     }
 
     fn commit_prg(&mut self) {
         //       4   0
         // bits: RPPPP
         //
-        // R: (UNUSED in MMC1A) PRG-RAM enable TODO: Rest of the revs
-        // P: PRG bank offset 16KB
+        // R: PRG-RAM disable (MMC1B and later. MMC1A ignores this.)
+        // P: PRG bank no. 16KB, low bit ignored in 32KB mode.
 
-        let mut bank_no = self.sr as usize & 0x0f;
-
-        // 32KB mode ignores least significant bit, doesn't switch in 16KB steps.
-        if let PrgBankMode::Big = self.prg_mode {
-            bank_no &= 0xfe;
-        }
-
-        self.prg_bank_offset = bank_no * 16 * KIB;
+        self.prg_bank = self.sr as usize & 0x0f;
+        self.prg_ram_enabled = self.sr & 0x10 == 0;
     }
 }
 
@@ -190,24 +300,374 @@ impl MapperIo for Mmc1 {
         }
     }
 
-    fn write_cpu(&mut self, addr: u16, _value: u8) {
+    fn write_cpu(&mut self, addr: u16, value: u8) {
         match addr {
-            0x0000..=0x7fff => (),
-            0x8000..=0xffff => todo!(),
+            0x0000..=0x5fff => (),
+            0x6000..=0x7fff => self.write_prg_ram(addr, value),
+            0x8000..=0xffff => self.write_sr(addr, value),
         }
     }
 
     fn read_ppu(&self, addr: u16) -> u8 {
-        let _addr = self.arrangement.map_addr(addr);
-        todo!()
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff => self.chr[self.map_chr_addr(addr)],
+            0x2000..=0x27ff => self.vram[addr as usize - 0x2000],
+            _ => 0,
+        }
     }
 
-    fn write_ppu(&mut self, addr: u16, _value: u8) {
-        let _addr = self.arrangement.map_addr(addr);
-        todo!()
+    fn write_ppu(&mut self, addr: u16, value: u8) {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff if self.chr_is_ram => {
+                let chr_addr = self.map_chr_addr(addr);
+                self.chr[chr_addr] = value;
+            }
+            0x2000..=0x27ff => self.vram[addr as usize - 0x2000] = value,
+            _ => (),
+        }
     }
 
     fn arrangement(&self) -> NametableArrangement {
         self.arrangement
     }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.as_deref()
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.as_deref_mut()
+    }
+
+    fn battery(&self) -> bool {
+        self.battery
+    }
+
+    fn clock_cpu(&mut self) {
+        self.cycles_since_sr_write = self.cycles_since_sr_write.saturating_add(1);
+    }
+}
+
+impl Snapshot for Mmc1 {
+    fn snapshot(&self, w: &mut StateWriter) {
+        if let Some(prg_ram) = &self.prg_ram {
+            w.vec(prg_ram);
+        }
+        if self.chr_is_ram {
+            w.bytes(&self.chr);
+        }
+        w.bytes(&self.vram);
+
+        w.u8(self.sr);
+        w.u8(self.sr_write_counter);
+        w.u8(self.cycles_since_sr_write);
+
+        w.u8(self.control());
+        w.u8(self.prg_bank as u8);
+        w.u8(self.chr_bank_0 as u8);
+        w.u8(self.chr_bank_1 as u8);
+        w.bool(self.prg_ram_enabled);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        if let Some(prg_ram) = &mut self.prg_ram {
+            r.vec_into(prg_ram)?;
+        }
+        if self.chr_is_ram {
+            r.bytes(&mut self.chr)?;
+        }
+        r.bytes(&mut self.vram)?;
+
+        self.sr = r.u8()? & 0x1f;
+        self.sr_write_counter = r.u8()?;
+        if self.sr_write_counter >= 5 {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+        self.cycles_since_sr_write = r.u8()?;
+
+        let control = r.u8()?;
+        self.write_control(control);
+        self.prg_bank = r.u8()? as usize & 0x0f;
+        self.chr_bank_0 = r.u8()? as usize & 0x1f;
+        self.chr_bank_1 = r.u8()? as usize & 0x1f;
+        self.prg_ram_enabled = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NesMachine;
+
+    /// Write to the mapper the way a CPU store instruction would, a few cycles after the last.
+    fn write(mmc1: &mut Mmc1, addr: u16, value: u8) {
+        for _ in 0..4 {
+            mmc1.clock_cpu();
+        }
+        mmc1.write_cpu(addr, value);
+    }
+
+    /// Write a 5-bit value to an MMC1 register, one bit at a time.
+    fn write_reg(mmc1: &mut Mmc1, addr: u16, value: u8) {
+        for i in 0..5 {
+            write(mmc1, addr, (value >> i) & 1);
+        }
+    }
+
+    /// PRG ROM where every byte holds its own 16KB bank no.
+    fn numbered_prg(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; 16 * KIB]).collect()
+    }
+
+    /// CHR ROM where every byte holds its own 4KB bank no.
+    fn numbered_chr(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; 4 * KIB]).collect()
+    }
+
+    #[test]
+    fn test_power_on_fixes_last_bank() {
+        let mmc1 = Mmc1::new(None, numbered_prg(8), numbered_chr(8));
+        assert_eq!(mmc1.read_cpu(0x8000), 0);
+        assert_eq!(mmc1.read_cpu(0xc000), 7);
+        assert_eq!(mmc1.read_cpu(0xfffc), 7);
+    }
+
+    #[test]
+    fn test_prg_modes() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(8), numbered_chr(8));
+
+        write_reg(&mut mmc1, 0xe000, 3);
+        assert_eq!(mmc1.read_cpu(0x8000), 3);
+        assert_eq!(mmc1.read_cpu(0xc000), 7);
+
+        // Fix first
+        write_reg(&mut mmc1, 0x8000, 0b01000);
+        assert_eq!(mmc1.read_cpu(0x8000), 0);
+        assert_eq!(mmc1.read_cpu(0xc000), 3);
+
+        // 32KB
+        write_reg(&mut mmc1, 0x8000, 0b00000);
+        assert_eq!(mmc1.read_cpu(0x8000), 2);
+        assert_eq!(mmc1.read_cpu(0xc000), 3);
+    }
+
+    #[test]
+    fn test_sr_reset_restores_fixed_last_bank() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(8), numbered_chr(8));
+        write_reg(&mut mmc1, 0x8000, 0b00000);
+        write_reg(&mut mmc1, 0xe000, 4);
+        assert_eq!(mmc1.read_cpu(0xc000), 5);
+
+        // Half-written value is discarded too
+        write(&mut mmc1, 0xe000, 1);
+        write(&mut mmc1, 0x8000, 0x80);
+        assert_eq!(mmc1.read_cpu(0x8000), 4);
+        assert_eq!(mmc1.read_cpu(0xc000), 7);
+    }
+
+    #[test]
+    fn test_consecutive_write_ignored() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(8), numbered_chr(8));
+        write(&mut mmc1, 0xe000, 0);
+        mmc1.clock_cpu();
+        mmc1.write_cpu(0xe000, 1);
+        for i in 1..5 {
+            write(&mut mmc1, 0xe000, (2 >> i) & 1);
+        }
+        assert_eq!(mmc1.read_cpu(0x8000), 2);
+    }
+
+    #[test]
+    fn test_chr_modes() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(2), numbered_chr(8));
+
+        // 8KB
+        write_reg(&mut mmc1, 0xa000, 5);
+        assert_eq!(mmc1.read_ppu(0x0000), 4);
+        assert_eq!(mmc1.read_ppu(0x1000), 5);
+
+        // 4KB
+        write_reg(&mut mmc1, 0x8000, 0b11100);
+        write_reg(&mut mmc1, 0xc000, 2);
+        assert_eq!(mmc1.read_ppu(0x0000), 5);
+        assert_eq!(mmc1.read_ppu(0x1000), 2);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(2), vec![]);
+        mmc1.write_ppu(0x1234, 0xab);
+        assert_eq!(mmc1.read_ppu(0x1234), 0xab);
+
+        let mut mmc1 = Mmc1::new(None, numbered_prg(2), numbered_chr(2));
+        mmc1.write_ppu(0x1234, 0xab);
+        assert_eq!(mmc1.read_ppu(0x1234), 1);
+    }
+
+    #[test]
+    fn test_prg_ram_enable() {
+        let mut mmc1 = Mmc1::new(Some(vec![0; 8 * KIB]), numbered_prg(2), numbered_chr(2));
+        mmc1.write_cpu(0x6000, 0x42);
+        assert_eq!(mmc1.read_cpu(0x6000), 0x42);
+
+        write_reg(&mut mmc1, 0xe000, 0x10);
+        assert_eq!(mmc1.read_cpu(0x6000), 0);
+        mmc1.write_cpu(0x6000, 0x24);
+
+        write_reg(&mut mmc1, 0xe000, 0x00);
+        assert_eq!(mmc1.read_cpu(0x6000), 0x42);
+    }
+
+    #[test]
+    fn test_arrangement() {
+        let mut mmc1 = Mmc1::new(None, numbered_prg(2), numbered_chr(2));
+        write_reg(&mut mmc1, 0x8000, 0b01110);
+        assert_eq!(
+            mmc1.arrangement(),
+            NametableArrangement::HorizontalArrangement
+        );
+        mmc1.write_ppu(0x2000, 0x11);
+        assert_eq!(mmc1.read_ppu(0x2800), 0x11);
+
+        write_reg(&mut mmc1, 0x8000, 0b01111);
+        assert_eq!(
+            mmc1.arrangement(),
+            NametableArrangement::VerticalArrangement
+        );
+        assert_eq!(mmc1.read_ppu(0x2400), 0x11);
+
+        write_reg(&mut mmc1, 0x8000, 0b01101);
+        assert_eq!(mmc1.arrangement(), NametableArrangement::OneScreenUpper);
+        mmc1.write_ppu(0x2000, 0x22);
+        assert_eq!(mmc1.read_ppu(0x2c00), 0x22);
+    }
+
+    /// Build an MMC1 iNES image with 8 PRG banks. Every bank is filled with its bank no.
+    /// The last bank holds `code` at $c000 and the reset vector pointing to it.
+    fn build_rom(code: &[u8]) -> Vec<u8> {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([8, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut prg = numbered_prg(8);
+        let last = 7 * 16 * KIB;
+        prg[last..last + code.len()].copy_from_slice(code);
+        prg[last + 0x3ffc] = 0x00;
+        prg[last + 0x3ffd] = 0xc0;
+        rom.extend(prg);
+        rom.extend(vec![0; 8 * KIB]);
+        rom
+    }
+
+    /// 6502 code: write 5-bit `value` to the MMC1 register at `addr`.
+    fn asm_write_reg(addr: u16, value: u8) -> Vec<u8> {
+        let [lo, hi] = addr.to_le_bytes();
+        let mut code = vec![0xa9, value]; // LDA #value
+        for i in 0..5 {
+            code.extend([0x8d, lo, hi]); // STA addr
+            if i < 4 {
+                code.push(0x4a); // LSR A
+            }
+        }
+        code
+    }
+
+    fn run(machine: &mut NesMachine, instructions: usize) {
+        for _ in 0..instructions {
+            machine.step_instruction();
+        }
+    }
+
+    #[test]
+    fn test_run_rom_bank_switch() {
+        let mut code = vec![];
+        code.extend(asm_write_reg(0xe000, 5));
+        code.extend([0xad, 0x00, 0x80]); // LDA $8000
+        code.extend([0x85, 0x00]); // STA $00
+        code.extend(asm_write_reg(0xe000, 2));
+        code.extend([0xad, 0x00, 0x80]); // LDA $8000
+        code.extend([0x85, 0x01]); // STA $01
+        code.extend([0xad, 0xf0, 0xff]); // LDA $fff0
+        code.extend([0x85, 0x02]); // STA $02
+        let [lo, hi] = (0xc000 + code.len() as u16).to_le_bytes();
+        code.extend([0x4c, lo, hi]); // JMP self
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&build_rom(&code)).unwrap();
+        run(&mut machine, 40);
+
+        assert_eq!(machine.bus.read_immutable(0x00), 5);
+        assert_eq!(machine.bus.read_immutable(0x01), 2);
+        assert_eq!(machine.bus.read_immutable(0x02), 7);
+    }
+
+    #[test]
+    fn test_save_state_mid_write() {
+        let mut code = vec![];
+        code.extend(asm_write_reg(0xe000, 5));
+        code.extend(asm_write_reg(0x8000, 0b11110));
+        code.extend([0xa9, 0x42, 0x8d, 0x00, 0x60]); // LDA #$42, STA $6000
+        let [lo, hi] = (0xc000 + code.len() as u16).to_le_bytes();
+        code.extend([0x4c, lo, hi]); // JMP self
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&build_rom(&code)).unwrap();
+        // In the middle of the second register write
+        run(&mut machine, 12);
+        let state = machine.save_state(false);
+        run(&mut machine, 20);
+        let expected = machine.save_state(false);
+
+        machine.load_state(&state).unwrap();
+        run(&mut machine, 20);
+        assert_eq!(machine.save_state(false), expected);
+        assert_eq!(machine.bus.read_immutable(0x6000), 0x42);
+    }
+
+    #[test]
+    fn test_run_rom_rmw_reset() {
+        let mut code = vec![];
+        // Two bits of a write in flight
+        code.extend([0xa9, 0x01, 0x8d, 0x00, 0xe0, 0x8d, 0x00, 0xe0]); // LDA #1, STA $e000 x2
+        // Writes $ff, then $00 on the next cycle. Only the reset may count.
+        code.extend([0xee, 0x00, 0x80]); // INC $8000
+        code.extend(asm_write_reg(0xe000, 5));
+        code.extend([0xad, 0x00, 0x80]); // LDA $8000
+        code.extend([0x85, 0x00]); // STA $00
+        let [lo, hi] = (0xc000 + code.len() as u16).to_le_bytes();
+        code.extend([0x4c, lo, hi]); // JMP self
+
+        let mut rom = build_rom(&code);
+        // $8000 in bank 0
+        rom[16] = 0xff;
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        run(&mut machine, 40);
+
+        assert_eq!(machine.bus.read_immutable(0x00), 5);
+    }
+
+    #[test]
+    fn test_run_rom_sr_reset() {
+        let mut code = vec![];
+        code.extend(asm_write_reg(0xe000, 0x0f));
+        code.extend(asm_write_reg(0x8000, 0b00000)); // 32KB mode
+        code.extend([0xad, 0xf0, 0xbf]); // LDA $bff0
+        code.extend([0x85, 0x00]); // STA $00
+        code.extend([0xa9, 0x80, 0x8d, 0x00, 0x80]); // LDA #$80, STA $8000
+        code.extend([0xad, 0xf0, 0xbf]); // LDA $bff0
+        code.extend([0x85, 0x01]); // STA $01
+        let [lo, hi] = (0xc000 + code.len() as u16).to_le_bytes();
+        code.extend([0x4c, lo, hi]); // JMP self
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&build_rom(&code)).unwrap();
+        run(&mut machine, 40);
+
+        assert_eq!(machine.bus.read_immutable(0x00), 6);
+        assert_eq!(machine.bus.read_immutable(0x01), 7);
+    }
 }
@@ -0,0 +1,606 @@
+use super::{MapperIo, NametableArrangement};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+const KIB: usize = 1024;
+
+/// MMC3 (mapper 4): 8KB PRG banks, 1KB/2KB CHR banks and a scanline IRQ counter.
+#[derive(Debug, Clone)]
+pub struct Mmc3 {
+    prg_ram: Vec<u8>,
+    /// PRG-RAM is battery-backed.
+    battery: bool,
+    prg_rom: Vec<u8>,
+    /// CHR ROM, or 8 KB of CHR RAM if the cart has no CHR ROM.
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    /// Belongs to console, but routed by mapper. The upper 2KB is on the cartridge, and only
+    /// used with four-screen arrangement.
+    vram: [u8; 0x1000],
+    arrangement: NametableArrangement,
+
+    /// Bank register written next by $8001
+    bank_select: u8,
+    /// $c000 is switchable and $8000 fixed to the second last bank, instead of the opposite.
+    prg_inversion: bool,
+    /// 1KB banks at $0000, 2KB banks at $1000, instead of the opposite.
+    chr_inversion: bool,
+    /// R0-R5: CHR banks in 1KB units, R6-R7: PRG banks in 8KB units
+    banks: [u8; 8],
+
+    prg_ram_enabled: bool,
+    prg_ram_write_protect: bool,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    /// PPU A12 was high on the last PPU bus access
+    a12: bool,
+    /// CPU cycles since A12 went low
+    a12_low_cycles: u8,
+}
+
+impl Mmc3 {
+    /// A12 has to stay low for this many CPU cycles before a rise clocks the IRQ counter.
+    /// Filters out the short drops between pattern fetches.
+    const A12_LOW_CYCLES: u8 = 3;
+
+    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, arrangement: NametableArrangement) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; 8 * KIB]
+        } else {
+            chr_rom
+        };
+
+        Self {
+            prg_ram: vec![0; 8 * KIB],
+            battery: false,
+            prg_rom,
+            chr,
+            chr_is_ram,
+            vram: [0; 0x1000],
+            arrangement,
+
+            bank_select: 0,
+            prg_inversion: false,
+            chr_inversion: false,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+
+            prg_ram_enabled: true,
+            prg_ram_write_protect: false,
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+
+            a12: false,
+            a12_low_cycles: 0,
+        }
+    }
+
+    /// Off by default.
+    pub fn set_battery(&mut self, battery: bool) {
+        self.battery = battery;
+    }
+
+    /// CPU ROM $8000-$ffff
+    fn read_prg_rom(&self, addr: u16) -> u8 {
+        let bank_count = self.prg_rom.len() / (8 * KIB);
+        let second_last = bank_count - 2;
+        let bank_no = match (addr, self.prg_inversion) {
+            (0x8000..=0x9fff, false) => self.banks[6] as usize,
+            (0x8000..=0x9fff, true) => second_last,
+            (0xa000..=0xbfff, _) => self.banks[7] as usize,
+            (0xc000..=0xdfff, false) => second_last,
+            (0xc000..=0xdfff, true) => self.banks[6] as usize,
+            _ => bank_count - 1,
+        };
+        let off_in_bank = addr as usize % (8 * KIB);
+        self.prg_rom[(bank_no % bank_count) * 8 * KIB + off_in_bank]
+    }
+
+    /// PPU pattern tables $0000-$1fff
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        // Inversion swaps the 2KB and 1KB halves.
+        let addr = if self.chr_inversion {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank_no = match addr {
+            0x0000..=0x07ff => self.banks[0] as usize & 0xfe,
+            0x0800..=0x0fff => self.banks[1] as usize & 0xfe,
+            _ => self.banks[2 + (addr as usize - 0x1000) / KIB] as usize,
+        };
+        let bank_no = match addr {
+            0x0400..=0x07ff | 0x0c00..=0x0fff => bank_no | 1,
+            _ => bank_no,
+        };
+        let bank_count = self.chr.len() / KIB;
+        (bank_no % bank_count) * KIB + addr as usize % KIB
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let even = addr.is_multiple_of(2);
+        match (addr, even) {
+            (0x8000..=0x9fff, true) => {
+                //       7  bit  0
+                // bits: CPxx xRRR
+                //
+                // C: CHR inversion
+                // P: PRG inversion
+                // R: Bank register to update
+                self.bank_select = value & 0x07;
+                self.prg_inversion = value & 0x40 != 0;
+                self.chr_inversion = value & 0x80 != 0;
+            }
+            (0x8000..=0x9fff, false) => {
+                let mask = if self.bank_select >= 6 { 0x3f } else { 0xff };
+                self.banks[self.bank_select as usize] = value & mask;
+            }
+            // Four-screen boards are wired past the mapper.
+            (0xa000..=0xbfff, true) if self.arrangement == NametableArrangement::FourScreen => (),
+            (0xa000..=0xbfff, true) => {
+                self.arrangement = if value & 0x01 == 0 {
+                    NametableArrangement::HorizontalArrangement
+                } else {
+                    NametableArrangement::VerticalArrangement
+                };
+            }
+            (0xa000..=0xbfff, false) => {
+                //       7  bit  0
+                // bits: EWxx xxxx
+                //
+                // E: PRG-RAM enable
+                // W: Write protect
+                self.prg_ram_enabled = value & 0x80 != 0;
+                self.prg_ram_write_protect = value & 0x40 != 0;
+            }
+            (0xc000..=0xdfff, true) => self.irq_latch = value,
+            (0xc000..=0xdfff, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (0xe000..=0xffff, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (0xe000..=0xffff, false) => self.irq_enabled = true,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocked on filtered A12 rises, once per scanline during rendering.
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl MapperIo for Mmc3 {
+    fn read_cpu(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x5fff => 0,
+            0x6000..=0x7fff if self.prg_ram_enabled => self.prg_ram[addr as usize - 0x6000],
+            0x6000..=0x7fff => 0,
+            0x8000..=0xffff => self.read_prg_rom(addr),
+        }
+    }
+
+    fn write_cpu(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x5fff => (),
+            0x6000..=0x7fff => {
+                if self.prg_ram_enabled && !self.prg_ram_write_protect {
+                    self.prg_ram[addr as usize - 0x6000] = value;
+                }
+            }
+            0x8000..=0xffff => self.write_register(addr, value),
+        }
+    }
+
+    fn read_ppu(&self, addr: u16) -> u8 {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff => self.chr[self.map_chr_addr(addr)],
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000],
+            _ => 0,
+        }
+    }
+
+    fn write_ppu(&mut self, addr: u16, value: u8) {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff if self.chr_is_ram => {
+                let chr_addr = self.map_chr_addr(addr);
+                self.chr[chr_addr] = value;
+            }
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000] = value,
+            _ => (),
+        }
+    }
+
+    fn arrangement(&self) -> NametableArrangement {
+        self.arrangement
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn battery(&self) -> bool {
+        self.battery
+    }
+
+    fn ppu_bus_addr(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.a12 && self.a12_low_cycles >= Self::A12_LOW_CYCLES {
+            self.clock_irq_counter();
+        }
+        if a12 {
+            self.a12_low_cycles = 0;
+        }
+        self.a12 = a12;
+    }
+
+    fn clock_cpu(&mut self) {
+        if !self.a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+}
+
+impl Snapshot for Mmc3 {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            w.bytes(&self.chr);
+        }
+        w.bytes(&self.vram);
+        w.bool(self.arrangement == NametableArrangement::VerticalArrangement);
+
+        w.u8(self.bank_select);
+        w.bool(self.prg_inversion);
+        w.bool(self.chr_inversion);
+        w.bytes(&self.banks);
+
+        w.bool(self.prg_ram_enabled);
+        w.bool(self.prg_ram_write_protect);
+
+        w.u8(self.irq_latch);
+        w.u8(self.irq_counter);
+        w.bool(self.irq_reload);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_pending);
+
+        w.bool(self.a12);
+        w.u8(self.a12_low_cycles);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        r.bytes(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            r.bytes(&mut self.chr)?;
+        }
+        r.bytes(&mut self.vram)?;
+        let vertical = r.bool()?;
+        if self.arrangement != NametableArrangement::FourScreen {
+            self.arrangement = if vertical {
+                NametableArrangement::VerticalArrangement
+            } else {
+                NametableArrangement::HorizontalArrangement
+            };
+        }
+
+        self.bank_select = r.u8()? & 0x07;
+        self.prg_inversion = r.bool()?;
+        self.chr_inversion = r.bool()?;
+        r.bytes(&mut self.banks)?;
+
+        self.prg_ram_enabled = r.bool()?;
+        self.prg_ram_write_protect = r.bool()?;
+
+        self.irq_latch = r.u8()?;
+        self.irq_counter = r.u8()?;
+        self.irq_reload = r.bool()?;
+        self.irq_enabled = r.bool()?;
+        self.irq_pending = r.bool()?;
+
+        self.a12 = r.bool()?;
+        self.a12_low_cycles = r.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NesMachine;
+
+    /// PRG ROM where every byte holds its own 8KB bank no.
+    fn numbered_prg(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; 8 * KIB]).collect()
+    }
+
+    /// CHR ROM where every byte holds its own 1KB bank no.
+    fn numbered_chr(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; KIB]).collect()
+    }
+
+    /// A12 low for a while, then high: one counter clock.
+    fn scanline(mmc3: &mut Mmc3) {
+        mmc3.ppu_bus_addr(0x0000);
+        for _ in 0..10 {
+            mmc3.clock_cpu();
+        }
+        mmc3.ppu_bus_addr(0x1000);
+    }
+
+    #[test]
+    fn test_prg_banks() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(16),
+            numbered_chr(8),
+            NametableArrangement::VerticalArrangement,
+        );
+        mmc3.write_cpu(0x8000, 6);
+        mmc3.write_cpu(0x8001, 3);
+        mmc3.write_cpu(0x8000, 7);
+        mmc3.write_cpu(0x8001, 5);
+        assert_eq!(mmc3.read_cpu(0x8000), 3);
+        assert_eq!(mmc3.read_cpu(0xa000), 5);
+        assert_eq!(mmc3.read_cpu(0xc000), 14);
+        assert_eq!(mmc3.read_cpu(0xe000), 15);
+
+        // PRG inversion
+        mmc3.write_cpu(0x8000, 0x40);
+        assert_eq!(mmc3.read_cpu(0x8000), 14);
+        assert_eq!(mmc3.read_cpu(0xa000), 5);
+        assert_eq!(mmc3.read_cpu(0xc000), 3);
+        assert_eq!(mmc3.read_cpu(0xffff), 15);
+    }
+
+    #[test]
+    fn test_chr_banks() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(32),
+            NametableArrangement::VerticalArrangement,
+        );
+        for (reg, bank) in [(0, 9), (1, 20), (2, 3), (3, 4), (4, 5), (5, 6)] {
+            mmc3.write_cpu(0x8000, reg);
+            mmc3.write_cpu(0x8001, bank);
+        }
+
+        // 2KB banks ignore the low bit.
+        let expected = [8, 9, 20, 21, 3, 4, 5, 6];
+        for (i, bank) in expected.iter().enumerate() {
+            assert_eq!(mmc3.read_ppu(i as u16 * 0x400), *bank);
+        }
+
+        // CHR inversion
+        mmc3.write_cpu(0x8000, 0x80);
+        let expected = [3, 4, 5, 6, 8, 9, 20, 21];
+        for (i, bank) in expected.iter().enumerate() {
+            assert_eq!(mmc3.read_ppu(i as u16 * 0x400), *bank);
+        }
+    }
+
+    #[test]
+    fn test_arrangement() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(8),
+            NametableArrangement::VerticalArrangement,
+        );
+        mmc3.write_cpu(0xa000, 0);
+        assert_eq!(
+            mmc3.arrangement(),
+            NametableArrangement::HorizontalArrangement
+        );
+        mmc3.write_cpu(0xa000, 1);
+        assert_eq!(
+            mmc3.arrangement(),
+            NametableArrangement::VerticalArrangement
+        );
+    }
+
+    #[test]
+    fn test_four_screen() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(8),
+            NametableArrangement::FourScreen,
+        );
+        // Mirroring writes are ignored.
+        mmc3.write_cpu(0xa000, 1);
+        assert_eq!(mmc3.arrangement(), NametableArrangement::FourScreen);
+
+        mmc3.write_ppu(0x2000, 1);
+        mmc3.write_ppu(0x2c00, 2);
+        assert_eq!(mmc3.read_ppu(0x2000), 1);
+        assert_eq!(mmc3.read_ppu(0x2800), 0);
+        assert_eq!(mmc3.read_ppu(0x2c00), 2);
+
+        let mut w = StateWriter::default();
+        mmc3.snapshot(&mut w);
+        mmc3.restore(&mut StateReader::new(&w.into_bytes()))
+            .unwrap();
+        assert_eq!(mmc3.arrangement(), NametableArrangement::FourScreen);
+    }
+
+    #[test]
+    fn test_prg_ram_protect() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(8),
+            NametableArrangement::VerticalArrangement,
+        );
+        mmc3.write_cpu(0x6000, 0x42);
+        assert_eq!(mmc3.read_cpu(0x6000), 0x42);
+
+        // Write protected
+        mmc3.write_cpu(0xa001, 0xc0);
+        mmc3.write_cpu(0x6000, 0x24);
+        assert_eq!(mmc3.read_cpu(0x6000), 0x42);
+
+        // Disabled
+        mmc3.write_cpu(0xa001, 0x00);
+        assert_eq!(mmc3.read_cpu(0x6000), 0);
+        mmc3.write_cpu(0xa001, 0x80);
+        mmc3.write_cpu(0x7fff, 0x24);
+        assert_eq!(mmc3.read_cpu(0x7fff), 0x24);
+    }
+
+    #[test]
+    fn test_irq_counter() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(8),
+            NametableArrangement::VerticalArrangement,
+        );
+        mmc3.write_cpu(0xc000, 3);
+        mmc3.write_cpu(0xc001, 0);
+        mmc3.write_cpu(0xe001, 0);
+
+        // Reload, then 3, 2, 1, 0
+        for _ in 0..3 {
+            scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        // Acknowledge and disable
+        mmc3.write_cpu(0xe000, 0);
+        assert!(!mmc3.irq());
+        for _ in 0..8 {
+            scanline(&mut mmc3);
+        }
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn test_a12_filter() {
+        let mut mmc3 = Mmc3::new(
+            numbered_prg(4),
+            numbered_chr(8),
+            NametableArrangement::VerticalArrangement,
+        );
+        mmc3.write_cpu(0xc000, 0);
+        mmc3.write_cpu(0xe001, 0);
+
+        // Short drops between fetches don't count.
+        for _ in 0..8 {
+            mmc3.ppu_bus_addr(0x2000);
+            mmc3.clock_cpu();
+            mmc3.ppu_bus_addr(0x1000);
+        }
+        assert!(!mmc3.irq());
+
+        // Latch 0 raises the IRQ on every clock.
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    /// Build an MMC3 iNES image with 4 PRG banks. `code` goes at $e000 with the reset vector
+    /// pointing to it, and `irq_handler` at $e800. CHR bank 0 has tiles of color 1.
+    fn build_rom(code: &[u8], irq_handler: &[u8]) -> Vec<u8> {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([2, 1, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut prg = numbered_prg(4);
+        let last = 3 * 8 * KIB;
+        prg[last..last + code.len()].copy_from_slice(code);
+        prg[last + 0x800..last + 0x800 + irq_handler.len()].copy_from_slice(irq_handler);
+        prg[last + 0x1ffc..].copy_from_slice(&[0x00, 0xe0, 0x00, 0xe8]);
+        rom.extend(prg);
+
+        let mut chr = vec![0; 8 * KIB];
+        for tile in chr.chunks_mut(16) {
+            tile[..8].fill(0xff);
+        }
+        rom.extend(chr);
+        rom
+    }
+
+    #[test]
+    fn test_run_rom_scanline_irq() {
+        #[rustfmt::skip]
+        let code = [
+            0xa9, 0x40, 0x8d, 0x17, 0x40, // LDA #$40; STA $4017. No APU frame IRQ.
+            0xa9, 0x1e, 0x8d, 0x01, 0x20, // LDA #$1e; STA $2001. Show BG and sprites.
+            0xa9, 0x08, 0x8d, 0x00, 0x20, // LDA #$08; STA $2000. Sprites from $1000.
+            0xa9, 0x14, 0x8d, 0x00, 0xc0, // LDA #20; STA $c000
+            0x8d, 0x01, 0xc0,             // STA $c001
+            0x8d, 0x01, 0xe0,             // STA $e001
+            0x58,                         // CLI
+            0x4c, 0x1b, 0xe0,             // JMP self
+        ];
+        #[rustfmt::skip]
+        let irq_handler = [
+            0xad, 0x02, 0x20, 0x85, 0x00, // LDA $2002; STA $00
+            0x8d, 0x00, 0xe0,             // STA $e000
+            0x40,                         // RTI
+        ];
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&build_rom(&code, &irq_handler)).unwrap();
+
+        let mut irq_scanlines = vec![];
+        for _ in 0..3 * 341 * 262 {
+            machine.step_instruction();
+            if machine.cpu.pc == 0xe800 {
+                irq_scanlines.push(machine.ppu.scanline());
+            }
+        }
+
+        // Counter reloads on the first rendered line and counts 20 more.
+        assert!(!irq_scanlines.is_empty());
+        for scanline in irq_scanlines {
+            assert_eq!(scanline, 20);
+        }
+    }
+
+    #[test]
+    fn test_open_unexpected_len() {
+        // NES 2.0 exponent sizes: 8KB PRG, 512 bytes CHR
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([0x34, 0x01, 0x40, 0x08, 0, 0x0f, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 8 * KIB + 8 * KIB]);
+        let mut machine = NesMachine::default();
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::MapperUnexpectedPrgRomLen(0x2000))
+        ));
+
+        rom[4] = 0x02;
+        rom[5] = 0x24;
+        rom[9] = 0xf0;
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::MapperUnexpectedChrRomLen(0x200))
+        ));
+    }
+}
@@ -1,7 +1,11 @@
 //! Cartridge / "rom" module
 
+mod cnrom;
+mod header;
 mod mmc1;
+mod mmc3;
 mod nrom;
+mod uxrom;
 
 use std::{
     fs::File,
@@ -9,19 +13,64 @@ use std::{
     path::Path,
 };
 
+pub use cnrom::Cnrom;
+pub use header::{ConsoleType, HeaderFormat, INesHeader, Timing};
 pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
 use nrom::Nrom;
+pub use uxrom::Uxrom;
 
 use crate::nes_machine::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
 use super::{CpuDevice, PpuDevice};
 
+const KIB: usize = 1024;
+
 pub trait MapperIo {
     fn read_cpu(&self, addr: u16) -> u8;
     fn write_cpu(&mut self, addr: u16, value: u8);
     fn read_ppu(&self, addr: u16) -> u8;
     fn write_ppu(&mut self, addr: u16, value: u8);
     fn arrangement(&self) -> NametableArrangement;
+
+    /// PRG-RAM at $6000-$7fff, if the board has any.
+    fn prg_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// PRG-RAM is battery-backed and keeps its contents with the power off.
+    fn battery(&self) -> bool {
+        false
+    }
+
+    /// Non-volatile RAM, to be kept between sessions
+    fn save_ram(&self) -> Option<&[u8]> {
+        if self.battery() { self.prg_ram() } else { None }
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.battery() {
+            self.prg_ram_mut()
+        } else {
+            None
+        }
+    }
+
+    /// Address put on the PPU bus. For mappers that watch it, like MMC3 does with A12.
+    fn ppu_bus_addr(&mut self, _addr: u16) {}
+
+    /// Called once every CPU cycle.
+    fn clock_cpu(&mut self) {}
+
+    /// IRQ line. Level-triggered, stays asserted until the mapper acknowledges it.
+    fn irq(&self) -> bool {
+        false
+    }
 }
 
 #[non_exhaustive]
@@ -33,11 +82,13 @@ pub enum NametableArrangement {
     HorizontalArrangement,
     /// Horizontal mirroring
     VerticalArrangement,
+    /// No mirroring. The cartridge has 2KB of extra nametable RAM for $2800-$2fff.
+    FourScreen,
 }
 
 impl NametableArrangement {
     pub fn from_sr_byte(value: u8) -> Self {
-        match value | 0x03 {
+        match value & 0x03 {
             0 => Self::OneScreenLower,
             1 => Self::OneScreenUpper,
             2 => Self::HorizontalArrangement,
@@ -46,6 +97,17 @@ impl NametableArrangement {
         }
     }
 
+    /// Hard-wired arrangement, for boards that can't switch it.
+    pub fn from_header(header: &INesHeader) -> Self {
+        if header.alt_nametable_layout {
+            Self::FourScreen
+        } else if header.v_mirroring {
+            Self::HorizontalArrangement
+        } else {
+            Self::VerticalArrangement
+        }
+    }
+
     fn is_mirror(&self, addr: u16) -> bool {
         match self {
             Self::OneScreenLower => matches!(addr, 0x2400..=0x2fff),
@@ -56,6 +118,7 @@ impl NametableArrangement {
             Self::VerticalArrangement => matches!(addr,
                 0x2400..=0x27ff |
                 0x2c00..=0x2fff ),
+            Self::FourScreen => false,
         }
     }
 
@@ -89,6 +152,7 @@ impl NametableArrangement {
                 0x2c00..=0x2fff => addr - 0x800,
                 _ => addr,
             },
+            Self::FourScreen => addr,
         }
     }
 
@@ -103,60 +167,17 @@ impl NametableArrangement {
     }
 }
 
-#[derive(Debug)]
-pub struct INesHeader {
-    len_prg_rom: usize,
-    len_chr_rom: usize,
-    mapper_id: u8,
-
-    v_mirroring: bool,
-}
-
-impl INesHeader {
-    const MAGIC: &[u8; 4] = b"NES\x1a";
-
-    pub fn read<R: Read>(reader: &mut BufReader<R>) -> Result<Self, NesMachineError> {
-        let mut header_buf = [0_u8; 16];
-        reader.read_exact(&mut header_buf)?;
-
-        if header_buf[0..=3] != *Self::MAGIC {
-            return Err(NesMachineError::FileInvalidSig);
-        }
-
-        let len_prg_rom = header_buf[4] as usize * 16384;
-        let len_chr_rom = header_buf[5] as usize * 8192;
-        let mapper_id = (header_buf[6] >> 4) + (header_buf[7] & 0xf0);
-
-        let v_mirroring = header_buf[6] & 0x1 != 0;
-        let battery = header_buf[6] & 0x2 != 0;
-        let trainer = header_buf[6] & 0x4 != 0;
-        let alt_nametable_layout = header_buf[6] & 0x8 != 0;
-
-        let vs_unisystem = header_buf[7] & 0x1 != 0;
-        let playchoice = header_buf[7] & 0x2 != 0;
-        let nes2_0 = (header_buf[7] >> 2) & 0x3 == 2;
-
-        if battery | trainer | alt_nametable_layout | vs_unisystem | playchoice | nes2_0 {
-            return Err(NesMachineError::MapperUnsupportedFeatures);
-        }
-
-        Ok(Self {
-            len_prg_rom,
-            len_chr_rom,
-            mapper_id,
-            v_mirroring,
-        })
-    }
-}
-
 // Lint complaint: size difference between types. It's okay at least for now.
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub enum Mapper {
     #[default]
     None,
     Nrom(Nrom),
     Mmc1(Mmc1),
+    Uxrom(Uxrom),
+    Cnrom(Cnrom),
+    Mmc3(Mmc3),
 }
 
 impl CpuDevice for Mapper {
@@ -169,6 +190,9 @@ impl CpuDevice for Mapper {
             Mapper::None => 0,
             Mapper::Nrom(nrom) => nrom.read_cpu(addr),
             Mapper::Mmc1(mmc1) => mmc1.read_cpu(addr),
+            Mapper::Uxrom(uxrom) => uxrom.read_cpu(addr),
+            Mapper::Cnrom(cnrom) => cnrom.read_cpu(addr),
+            Mapper::Mmc3(mmc3) => mmc3.read_cpu(addr),
         }
     }
 
@@ -177,6 +201,9 @@ impl CpuDevice for Mapper {
             Mapper::None => (),
             Mapper::Nrom(nrom) => nrom.write_cpu(addr, value),
             Mapper::Mmc1(mmc1) => mmc1.write_cpu(addr, value),
+            Mapper::Uxrom(uxrom) => uxrom.write_cpu(addr, value),
+            Mapper::Cnrom(cnrom) => cnrom.write_cpu(addr, value),
+            Mapper::Mmc3(mmc3) => mmc3.write_cpu(addr, value),
         }
     }
 }
@@ -187,6 +214,9 @@ impl PpuDevice for Mapper {
             Mapper::None => 0,
             Mapper::Nrom(nrom) => nrom.read_ppu(addr),
             Mapper::Mmc1(mmc1) => mmc1.read_ppu(addr),
+            Mapper::Uxrom(uxrom) => uxrom.read_ppu(addr),
+            Mapper::Cnrom(cnrom) => cnrom.read_ppu(addr),
+            Mapper::Mmc3(mmc3) => mmc3.read_ppu(addr),
         }
     }
 
@@ -195,6 +225,50 @@ impl PpuDevice for Mapper {
             Mapper::None => (),
             Mapper::Nrom(nrom) => nrom.write_ppu(addr, value),
             Mapper::Mmc1(mmc1) => mmc1.write_ppu(addr, value),
+            Mapper::Uxrom(uxrom) => uxrom.write_ppu(addr, value),
+            Mapper::Cnrom(cnrom) => cnrom.write_ppu(addr, value),
+            Mapper::Mmc3(mmc3) => mmc3.write_ppu(addr, value),
+        }
+    }
+}
+
+impl Snapshot for Mapper {
+    fn snapshot(&self, w: &mut StateWriter) {
+        match self {
+            Mapper::None => w.u8(0),
+            Mapper::Nrom(nrom) => {
+                w.u8(1);
+                nrom.snapshot(w);
+            }
+            Mapper::Mmc1(mmc1) => {
+                w.u8(2);
+                mmc1.snapshot(w);
+            }
+            Mapper::Uxrom(uxrom) => {
+                w.u8(3);
+                uxrom.snapshot(w);
+            }
+            Mapper::Cnrom(cnrom) => {
+                w.u8(4);
+                cnrom.snapshot(w);
+            }
+            Mapper::Mmc3(mmc3) => {
+                w.u8(5);
+                mmc3.snapshot(w);
+            }
+        }
+    }
+
+    /// Fails if the state was saved with a different mapper.
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        match (r.u8()?, self) {
+            (0, Mapper::None) => Ok(()),
+            (1, Mapper::Nrom(nrom)) => nrom.restore(r),
+            (2, Mapper::Mmc1(mmc1)) => mmc1.restore(r),
+            (3, Mapper::Uxrom(uxrom)) => uxrom.restore(r),
+            (4, Mapper::Cnrom(cnrom)) => cnrom.restore(r),
+            (5, Mapper::Mmc3(mmc3)) => mmc3.restore(r),
+            _ => Err(NesMachineError::SaveStateMismatch),
         }
     }
 }
@@ -207,50 +281,106 @@ impl Mapper {
 
     pub fn from_reader<R: Read>(reader: &mut BufReader<R>) -> Result<Self, NesMachineError> {
         let header = INesHeader::read(reader)?;
+        Self::from_header(&header, reader)
+    }
 
-        println!("{header:x?}");
-
-        match header.mapper_id {
+    /// Build the mapper from ROM data that follows `header` in `reader`.
+    pub fn from_header<R: Read>(
+        header: &INesHeader,
+        reader: &mut BufReader<R>,
+    ) -> Result<Self, NesMachineError> {
+        // Sits between the header and PRG ROM.
+        let trainer = if header.trainer {
+            let mut trainer = vec![0_u8; 512];
+            reader.read_exact(&mut trainer)?;
+            Some(trainer)
+        } else {
+            None
+        };
+        let arrangement = NametableArrangement::from_header(header);
+
+        check_rom_len(header)?;
+        let mut mapper = match header.mapper_id {
             0 => {
                 // 16KB OR 32KB
-                let mut prg_rom = vec![0_u8; header.len_prg_rom];
-                match header.len_prg_rom {
-                    0x4000 => reader.read_exact(&mut prg_rom)?,
-                    0x8000 => reader.read_exact(&mut prg_rom)?,
-                    _ => {
-                        return Err(NesMachineError::MapperUnexpectedPrgRomLen(
-                            header.len_chr_rom,
-                        ));
-                    }
+                if !matches!(header.len_prg_rom, 0x4000 | 0x8000) {
+                    return Err(NesMachineError::MapperUnexpectedPrgRomLen(
+                        header.len_prg_rom,
+                    ));
                 }
-
-                // 8KB
-                let mut chr_rom = vec![0_u8; 0x2000];
-                match header.len_chr_rom {
-                    0x2000 => reader.read_exact(&mut chr_rom)?,
-                    _ => {
-                        return Err(NesMachineError::MapperUnexpectedChrRomLen(
-                            header.len_chr_rom,
-                        ));
-                    }
+                // 8KB. No CHR ROM means the board has 8KB of CHR RAM instead.
+                if !matches!(header.len_chr_rom, 0x2000 | 0) {
+                    return Err(NesMachineError::MapperUnexpectedChrRomLen(
+                        header.len_chr_rom,
+                    ));
                 }
+                let prg_rom = read_rom(reader, header.len_prg_rom)?;
+                let chr_rom = read_rom(reader, header.len_chr_rom)?;
 
-                Ok(Self::Nrom(Nrom::new(prg_rom, chr_rom, header.v_mirroring)))
+                let mut nrom = Nrom::new(prg_rom, chr_rom, arrangement);
+                nrom.set_battery(header.battery);
+                Self::Nrom(nrom)
             }
             1 => {
-                let prg_ram = Some(vec![0_u8; 32 * 1024]);
-                let mut prg_rom = vec![0_u8; header.len_prg_rom];
-                let mut chr_rom = vec![0_u8; header.len_chr_rom];
+                let prg_ram = Some(vec![0_u8; 8 * KIB]);
+                let prg_rom = read_rom(reader, header.len_prg_rom)?;
+                let chr_rom = read_rom(reader, header.len_chr_rom)?;
 
-                reader.read_exact(&mut prg_rom)?;
-                reader.read_exact(&mut chr_rom)?;
+                let mut mmc1 = Mmc1::new(prg_ram, prg_rom, chr_rom);
+                mmc1.set_battery(header.battery);
+                Self::Mmc1(mmc1)
+            }
+            2 => {
+                let prg_rom = read_rom(reader, header.len_prg_rom)?;
+                let chr_rom = read_rom(reader, header.len_chr_rom)?;
+
+                let mut uxrom = Uxrom::new(prg_rom, chr_rom, arrangement);
+                uxrom.set_prg_ram(optional_prg_ram(header));
+                uxrom.set_battery(header.battery);
+                Self::Uxrom(uxrom)
+            }
+            3 => {
+                if !matches!(header.len_prg_rom, 0x4000 | 0x8000) {
+                    return Err(NesMachineError::MapperUnexpectedPrgRomLen(
+                        header.len_prg_rom,
+                    ));
+                }
+                let prg_rom = read_rom(reader, header.len_prg_rom)?;
+                let chr_rom = read_rom(reader, header.len_chr_rom)?;
+
+                let mut cnrom = Cnrom::new(prg_rom, chr_rom, arrangement);
+                cnrom.set_prg_ram(optional_prg_ram(header));
+                cnrom.set_battery(header.battery);
+                // NES 2.0 submapper 1: no bus conflicts, 2: AND conflicts. 0 is unspecified.
+                cnrom.set_bus_conflicts(header.submapper != 1);
+                Self::Cnrom(cnrom)
+            }
+            4 => {
+                let prg_rom = read_rom(reader, header.len_prg_rom)?;
+                let chr_rom = read_rom(reader, header.len_chr_rom)?;
 
-                Ok(Self::Mmc1(Mmc1::new(prg_ram, prg_rom, chr_rom)))
+                let mut mmc3 = Mmc3::new(prg_rom, chr_rom, arrangement);
+                mmc3.set_battery(header.battery);
+                Self::Mmc3(mmc3)
             }
-            _ => Err(NesMachineError::MapperUnsupportedId(
-                header.mapper_id as usize,
-            )),
+            _ => {
+                return Err(NesMachineError::MapperUnsupportedId(
+                    header.mapper_id as usize,
+                ));
+            }
+        };
+
+        if let Some(trainer) = trainer {
+            // $7000-$71ff
+            let Some(prg_ram) = mapper
+                .prg_ram_mut()
+                .and_then(|prg_ram| prg_ram.get_mut(0x1000..0x1200))
+            else {
+                return Err(NesMachineError::MapperUnsupportedFeatures);
+            };
+            prg_ram.copy_from_slice(&trainer);
         }
+        Ok(mapper)
     }
 
     pub fn nt_arrangement(&self) -> Option<NametableArrangement> {
@@ -258,6 +388,116 @@ impl Mapper {
             Mapper::None => None,
             Mapper::Nrom(nrom) => Some(nrom.arrangement()),
             Mapper::Mmc1(mmc1) => Some(mmc1.arrangement()),
+            Mapper::Uxrom(uxrom) => Some(uxrom.arrangement()),
+            Mapper::Cnrom(cnrom) => Some(cnrom.arrangement()),
+            Mapper::Mmc3(mmc3) => Some(mmc3.arrangement()),
+        }
+    }
+
+    /// Address put on the PPU bus
+    pub fn ppu_bus_addr(&mut self, addr: u16) {
+        match self {
+            Mapper::None => (),
+            Mapper::Nrom(nrom) => nrom.ppu_bus_addr(addr),
+            Mapper::Mmc1(mmc1) => mmc1.ppu_bus_addr(addr),
+            Mapper::Uxrom(uxrom) => uxrom.ppu_bus_addr(addr),
+            Mapper::Cnrom(cnrom) => cnrom.ppu_bus_addr(addr),
+            Mapper::Mmc3(mmc3) => mmc3.ppu_bus_addr(addr),
+        }
+    }
+
+    /// Called once every CPU cycle.
+    pub fn clock_cpu(&mut self) {
+        match self {
+            Mapper::None => (),
+            Mapper::Nrom(nrom) => nrom.clock_cpu(),
+            Mapper::Mmc1(mmc1) => mmc1.clock_cpu(),
+            Mapper::Uxrom(uxrom) => uxrom.clock_cpu(),
+            Mapper::Cnrom(cnrom) => cnrom.clock_cpu(),
+            Mapper::Mmc3(mmc3) => mmc3.clock_cpu(),
+        }
+    }
+
+    /// PRG-RAM at $6000-$7fff, if the board has any.
+    pub fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            Mapper::None => None,
+            Mapper::Nrom(nrom) => nrom.prg_ram_mut(),
+            Mapper::Mmc1(mmc1) => mmc1.prg_ram_mut(),
+            Mapper::Uxrom(uxrom) => uxrom.prg_ram_mut(),
+            Mapper::Cnrom(cnrom) => cnrom.prg_ram_mut(),
+            Mapper::Mmc3(mmc3) => mmc3.prg_ram_mut(),
+        }
+    }
+
+    /// Non-volatile RAM, to be kept between sessions. None if the board has none.
+    pub fn save_ram(&self) -> Option<&[u8]> {
+        match self {
+            Mapper::None => None,
+            Mapper::Nrom(nrom) => nrom.save_ram(),
+            Mapper::Mmc1(mmc1) => mmc1.save_ram(),
+            Mapper::Uxrom(uxrom) => uxrom.save_ram(),
+            Mapper::Cnrom(cnrom) => cnrom.save_ram(),
+            Mapper::Mmc3(mmc3) => mmc3.save_ram(),
+        }
+    }
+
+    /// Put back RAM exported by [Self::save_ram]. Fails if the length doesn't match.
+    pub fn load_save_ram(&mut self, data: &[u8]) -> Result<(), NesMachineError> {
+        let save_ram = match self {
+            Mapper::None => None,
+            Mapper::Nrom(nrom) => nrom.save_ram_mut(),
+            Mapper::Mmc1(mmc1) => mmc1.save_ram_mut(),
+            Mapper::Uxrom(uxrom) => uxrom.save_ram_mut(),
+            Mapper::Cnrom(cnrom) => cnrom.save_ram_mut(),
+            Mapper::Mmc3(mmc3) => mmc3.save_ram_mut(),
+        };
+        match save_ram {
+            Some(save_ram) if save_ram.len() == data.len() => {
+                save_ram.copy_from_slice(data);
+                Ok(())
+            }
+            _ => Err(NesMachineError::SaveRamUnexpectedLen(data.len())),
+        }
+    }
+
+    /// PRG-RAM is battery-backed and keeps its contents with the power off.
+    pub fn battery(&self) -> bool {
+        match self {
+            Mapper::None => false,
+            Mapper::Nrom(nrom) => nrom.battery(),
+            Mapper::Mmc1(mmc1) => mmc1.battery(),
+            Mapper::Uxrom(uxrom) => uxrom.battery(),
+            Mapper::Cnrom(cnrom) => cnrom.battery(),
+            Mapper::Mmc3(mmc3) => mmc3.battery(),
+        }
+    }
+
+    /// Whether register writes are ANDed with the ROM byte at the same address, or None for
+    /// mappers that don't model bus conflicts
+    pub fn bus_conflicts(&self) -> Option<bool> {
+        match self {
+            Mapper::Cnrom(cnrom) => Some(cnrom.bus_conflicts()),
+            _ => None,
+        }
+    }
+
+    /// Ignored by mappers that don't model bus conflicts.
+    pub fn set_bus_conflicts(&mut self, enabled: bool) {
+        if let Mapper::Cnrom(cnrom) = self {
+            cnrom.set_bus_conflicts(enabled);
+        }
+    }
+
+    /// Mapper IRQ line
+    pub fn irq(&self) -> bool {
+        match self {
+            Mapper::None => false,
+            Mapper::Nrom(nrom) => nrom.irq(),
+            Mapper::Mmc1(mmc1) => mmc1.irq(),
+            Mapper::Uxrom(uxrom) => uxrom.irq(),
+            Mapper::Cnrom(cnrom) => cnrom.irq(),
+            Mapper::Mmc3(mmc3) => mmc3.irq(),
         }
     }
 
@@ -266,6 +506,52 @@ impl Mapper {
             Mapper::None => false,
             Mapper::Nrom(nrom) => nrom.arrangement().is_mirror(addr),
             Mapper::Mmc1(mmc1) => mmc1.arrangement().is_mirror(addr),
+            Mapper::Uxrom(uxrom) => uxrom.arrangement().is_mirror(addr),
+            Mapper::Cnrom(cnrom) => cnrom.arrangement().is_mirror(addr),
+            Mapper::Mmc3(mmc3) => mmc3.arrangement().is_mirror(addr),
         }
     }
 }
+
+/// Larger than anything the supported boards can address
+const MAX_ROM_LEN: usize = 16 * 1024 * KIB;
+
+/// PRG ROM and CHR ROM have to be whole banks of the mapper, and PRG ROM can't be empty.
+/// Checked before anything is read, since NES 2.0 sizes can be anything up to `usize::MAX`.
+fn check_rom_len(header: &INesHeader) -> Result<(), NesMachineError> {
+    let (prg_bank, chr_bank) = match header.mapper_id {
+        0 | 2 | 3 => (16 * KIB, 8 * KIB),
+        1 => (16 * KIB, 4 * KIB),
+        // Fixed banks are the last two 8KB ones.
+        4 => (16 * KIB, KIB),
+        _ => return Ok(()),
+    };
+    let prg_len = header.len_prg_rom;
+    if prg_len == 0 || !prg_len.is_multiple_of(prg_bank) || prg_len > MAX_ROM_LEN {
+        return Err(NesMachineError::MapperUnexpectedPrgRomLen(prg_len));
+    }
+    let chr_len = header.len_chr_rom;
+    if !chr_len.is_multiple_of(chr_bank) || chr_len > MAX_ROM_LEN {
+        return Err(NesMachineError::MapperUnexpectedChrRomLen(chr_len));
+    }
+    Ok(())
+}
+
+/// 8KB at $6000-$7fff for boards that normally have none, if the header implies some: a
+/// trainer to load there, a battery to back it, or NES 2.0 PRG-RAM sizes.
+fn optional_prg_ram(header: &INesHeader) -> Option<Vec<u8>> {
+    let wanted =
+        header.trainer || header.battery || header.len_prg_ram > 0 || header.len_prg_nvram > 0;
+    wanted.then(|| vec![0; 8 * KIB])
+}
+
+/// Read `len` bytes of ROM. The buffer grows with the data read, so a file shorter than its
+/// header says fails without allocating the whole size first.
+fn read_rom<R: Read>(reader: &mut BufReader<R>, len: usize) -> Result<Vec<u8>, NesMachineError> {
+    let mut data = vec![];
+    reader.by_ref().take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(NesMachineError::FileIo);
+    }
+    Ok(data)
+}
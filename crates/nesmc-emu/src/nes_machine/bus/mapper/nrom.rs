@@ -1,30 +1,51 @@
 use super::{MapperIo, NametableArrangement};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
-#[derive(Debug)]
+const KIB: usize = 1024;
+
+#[derive(Debug, Clone)]
 pub struct Nrom {
+    /// CPU 0x6000..=0x7fff. Only Family BASIC has it, but test ROMs expect it to be there.
+    prg_ram: [u8; 0x2000],
+    /// PRG-RAM is battery-backed.
+    battery: bool,
     /// CPU 0x8000..=0xffff
     prg_rom: Vec<u8>,
-    /// PPU 0x0000..=0x1fff
-    chr_rom: Vec<u8>,
-    /// Belongs to console, but routed by mapper.
-    vram: [u8; 0x800],
+    /// PPU 0x0000..=0x1fff. CHR ROM, or 8 KB of CHR RAM if the cart has no CHR ROM.
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    /// Belongs to console, but routed by mapper. The upper 2KB is on the cartridge, and only
+    /// used with four-screen arrangement.
+    vram: [u8; 0x1000],
     arrangement: NametableArrangement,
 }
 
 impl Nrom {
-    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, v_mirroring: bool) -> Self {
+    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, arrangement: NametableArrangement) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; 8 * KIB]
+        } else {
+            chr_rom
+        };
+
         Self {
+            prg_ram: [0; 0x2000],
+            battery: false,
             prg_rom,
-            chr_rom,
-            vram: [0; 0x800],
-            arrangement: if v_mirroring {
-                NametableArrangement::HorizontalArrangement
-            } else {
-                NametableArrangement::VerticalArrangement
-            },
+            chr,
+            chr_is_ram,
+            vram: [0; 0x1000],
+            arrangement,
         }
     }
 
+    /// Off by default. Family BASIC keeps its PRG-RAM with batteries.
+    pub fn set_battery(&mut self, battery: bool) {
+        self.battery = battery;
+    }
+
     fn map_prg_rom_mirror(&self, addr: u16) -> usize {
         (addr as usize - 0x8000) % self.prg_rom.len()
     }
@@ -33,18 +54,23 @@ impl Nrom {
 impl MapperIo for Nrom {
     fn read_cpu(&self, addr: u16) -> u8 {
         match addr {
-            0x0000..=0x7fff => 0,
+            0x0000..=0x5fff => 0,
+            0x6000..=0x7fff => self.prg_ram[addr as usize - 0x6000],
             0x8000..=0xffff => self.prg_rom[self.map_prg_rom_mirror(addr)],
         }
     }
 
-    fn write_cpu(&mut self, _addr: u16, _value: u8) {}
+    fn write_cpu(&mut self, addr: u16, value: u8) {
+        if let 0x6000..=0x7fff = addr {
+            self.prg_ram[addr as usize - 0x6000] = value;
+        }
+    }
 
     fn read_ppu(&self, addr: u16) -> u8 {
         let addr = self.arrangement.map_addr(addr);
         match addr {
-            0x0000..=0x1fff => self.chr_rom[addr as usize],
-            0x2000..=0x27ff => self.vram[addr as usize - 0x2000],
+            0x0000..=0x1fff => self.chr[addr as usize],
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000],
             _ => 0,
         }
     }
@@ -52,8 +78,8 @@ impl MapperIo for Nrom {
     fn write_ppu(&mut self, addr: u16, value: u8) {
         let addr = self.arrangement.map_addr(addr);
         match addr {
-            0x0000..=0x1fff => self.chr_rom[addr as usize] = value,
-            0x2000..=0x27ff => self.vram[addr as usize - 0x2000] = value,
+            0x0000..=0x1fff if self.chr_is_ram => self.chr[addr as usize] = value,
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000] = value,
             _ => (),
         }
     }
@@ -61,4 +87,97 @@ impl MapperIo for Nrom {
     fn arrangement(&self) -> NametableArrangement {
         self.arrangement
     }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn battery(&self) -> bool {
+        self.battery
+    }
+}
+
+impl Snapshot for Nrom {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            w.bytes(&self.chr);
+        }
+        w.bytes(&self.vram);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        r.bytes(&mut self.prg_ram)?;
+        if self.chr_is_ram {
+            r.bytes(&mut self.chr)?;
+        }
+        r.bytes(&mut self.vram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NesMachine;
+
+    #[test]
+    fn test_chr_rom_is_read_only() {
+        let mut nrom = Nrom::new(
+            vec![0; 16 * KIB],
+            vec![0x42; 8 * KIB],
+            NametableArrangement::VerticalArrangement,
+        );
+        nrom.write_ppu(0x0123, 0xab);
+        assert_eq!(nrom.read_ppu(0x0123), 0x42);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut nrom = Nrom::new(
+            vec![0; 16 * KIB],
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        nrom.write_ppu(0x0000, 0x12);
+        nrom.write_ppu(0x1fff, 0x34);
+        assert_eq!(nrom.read_ppu(0x0000), 0x12);
+        assert_eq!(nrom.read_ppu(0x1fff), 0x34);
+
+        // CHR RAM goes into save states.
+        let mut w = StateWriter::default();
+        nrom.snapshot(&mut w);
+        nrom.write_ppu(0x0000, 0);
+        nrom.restore(&mut StateReader::new(&w.into_bytes()))
+            .unwrap();
+        assert_eq!(nrom.read_ppu(0x0000), 0x12);
+    }
+
+    #[test]
+    fn test_open_chr_ram() {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 16 * KIB]);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        machine.bus.write_ppu(0x1000, 0x56);
+        assert_eq!(machine.bus.read_ppu(0x1000), 0x56);
+    }
+
+    #[test]
+    fn test_open_unexpected_prg_len() {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 48 * KIB]);
+
+        let mut machine = NesMachine::default();
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::MapperUnexpectedPrgRomLen(0xc000))
+        ));
+    }
 }
@@ -0,0 +1,277 @@
+use super::{MapperIo, NametableArrangement};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+const KIB: usize = 1024;
+
+/// UxROM (mapper 2): switchable 16KB PRG bank at $8000, last bank fixed at $c000.
+#[derive(Debug, Clone)]
+pub struct Uxrom {
+    /// Optional 8 KB program ram at $6000-$7fff. Not on the original boards.
+    prg_ram: Option<Vec<u8>>,
+    /// PRG-RAM is battery-backed.
+    battery: bool,
+    prg_rom: Vec<u8>,
+    /// CHR ROM, or 8 KB of CHR RAM if the cart has no CHR ROM.
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    /// Belongs to console, but routed by mapper. The upper 2KB is on the cartridge, and only
+    /// used with four-screen arrangement.
+    vram: [u8; 0x1000],
+    arrangement: NametableArrangement,
+
+    /// 16KB bank no. at $8000
+    prg_bank: usize,
+}
+
+impl Uxrom {
+    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, arrangement: NametableArrangement) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; 8 * KIB]
+        } else {
+            chr_rom
+        };
+
+        Self {
+            prg_ram: None,
+            battery: false,
+            prg_rom,
+            chr,
+            chr_is_ram,
+            vram: [0; 0x1000],
+            arrangement,
+
+            prg_bank: 0,
+        }
+    }
+
+    /// None by default. Carts with a trainer or battery, or NES 2.0 PRG-RAM get 8 KB.
+    pub fn set_prg_ram(&mut self, prg_ram: Option<Vec<u8>>) {
+        self.prg_ram = prg_ram;
+    }
+
+    /// Off by default.
+    pub fn set_battery(&mut self, battery: bool) {
+        self.battery = battery;
+    }
+
+    fn bank_count(&self) -> usize {
+        self.prg_rom.len() / (16 * KIB)
+    }
+
+    /// CPU ROM $8000-$ffff
+    fn read_prg_rom(&self, addr: u16) -> u8 {
+        let bank_no = match addr {
+            0x8000..=0xbfff => self.prg_bank % self.bank_count(),
+            _ => self.bank_count() - 1,
+        };
+        let off_in_bank = addr as usize % (16 * KIB);
+        self.prg_rom[bank_no * 16 * KIB + off_in_bank]
+    }
+}
+
+impl MapperIo for Uxrom {
+    fn read_cpu(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x5fff => 0,
+            0x6000..=0x7fff => match &self.prg_ram {
+                Some(prg_ram) => prg_ram[(addr as usize - 0x6000) % prg_ram.len()],
+                None => 0,
+            },
+            0x8000..=0xffff => self.read_prg_rom(addr),
+        }
+    }
+
+    fn write_cpu(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x5fff => (),
+            0x6000..=0x7fff => {
+                if let Some(prg_ram) = &mut self.prg_ram {
+                    let len = prg_ram.len();
+                    prg_ram[(addr as usize - 0x6000) % len] = value;
+                }
+            }
+            // UNROM uses 3 bits, UOROM 4. Unused bits are masked by the bank count.
+            0x8000..=0xffff => self.prg_bank = value as usize,
+        }
+    }
+
+    fn read_ppu(&self, addr: u16) -> u8 {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff => self.chr[addr as usize],
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000],
+            _ => 0,
+        }
+    }
+
+    fn write_ppu(&mut self, addr: u16, value: u8) {
+        let addr = self.arrangement.map_addr(addr);
+        match addr {
+            0x0000..=0x1fff if self.chr_is_ram => self.chr[addr as usize] = value,
+            0x2000..=0x2fff => self.vram[addr as usize - 0x2000] = value,
+            _ => (),
+        }
+    }
+
+    fn arrangement(&self) -> NametableArrangement {
+        self.arrangement
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.as_deref()
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.as_deref_mut()
+    }
+
+    fn battery(&self) -> bool {
+        self.battery
+    }
+}
+
+impl Snapshot for Uxrom {
+    fn snapshot(&self, w: &mut StateWriter) {
+        if let Some(prg_ram) = &self.prg_ram {
+            w.vec(prg_ram);
+        }
+        if self.chr_is_ram {
+            w.bytes(&self.chr);
+        }
+        w.bytes(&self.vram);
+        w.u8(self.prg_bank as u8);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        if let Some(prg_ram) = &mut self.prg_ram {
+            r.vec_into(prg_ram)?;
+        }
+        if self.chr_is_ram {
+            r.bytes(&mut self.chr)?;
+        }
+        r.bytes(&mut self.vram)?;
+        self.prg_bank = r.u8()? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NesMachine;
+
+    /// PRG ROM where every byte holds its own 16KB bank no.
+    fn numbered_prg(banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|i| vec![i as u8; 16 * KIB]).collect()
+    }
+
+    #[test]
+    fn test_power_on() {
+        let uxrom = Uxrom::new(
+            numbered_prg(8),
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        assert_eq!(uxrom.read_cpu(0x8000), 0);
+        assert_eq!(uxrom.read_cpu(0xbfff), 0);
+        assert_eq!(uxrom.read_cpu(0xc000), 7);
+        assert_eq!(uxrom.read_cpu(0xfffc), 7);
+    }
+
+    #[test]
+    fn test_bank_switch() {
+        let mut uxrom = Uxrom::new(
+            numbered_prg(8),
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+
+        for bank in 0..8 {
+            uxrom.write_cpu(0x8000 + bank as u16 * 0x1000, bank);
+            assert_eq!(uxrom.read_cpu(0x8000), bank);
+            assert_eq!(uxrom.read_cpu(0xbfff), bank);
+            assert_eq!(uxrom.read_cpu(0xc000), 7);
+        }
+
+        // High bits wrap around the bank count.
+        uxrom.write_cpu(0xffff, 0x0a);
+        assert_eq!(uxrom.read_cpu(0x8000), 2);
+        assert_eq!(uxrom.read_cpu(0xc000), 7);
+
+        let mut uoxrom = Uxrom::new(
+            numbered_prg(16),
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        uoxrom.write_cpu(0x8000, 0x0a);
+        assert_eq!(uoxrom.read_cpu(0x8000), 10);
+        assert_eq!(uoxrom.read_cpu(0xc000), 15);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut uxrom = Uxrom::new(
+            numbered_prg(2),
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        uxrom.write_ppu(0x0000, 0x12);
+        uxrom.write_ppu(0x1fff, 0x34);
+        assert_eq!(uxrom.read_ppu(0x0000), 0x12);
+        assert_eq!(uxrom.read_ppu(0x1fff), 0x34);
+    }
+
+    #[test]
+    fn test_arrangement() {
+        let mut uxrom = Uxrom::new(
+            numbered_prg(2),
+            vec![],
+            NametableArrangement::HorizontalArrangement,
+        );
+        assert_eq!(
+            uxrom.arrangement(),
+            NametableArrangement::HorizontalArrangement
+        );
+        uxrom.write_ppu(0x2000, 0x11);
+        assert_eq!(uxrom.read_ppu(0x2800), 0x11);
+
+        let mut uxrom = Uxrom::new(
+            numbered_prg(2),
+            vec![],
+            NametableArrangement::VerticalArrangement,
+        );
+        assert_eq!(
+            uxrom.arrangement(),
+            NametableArrangement::VerticalArrangement
+        );
+        uxrom.write_ppu(0x2000, 0x11);
+        assert_eq!(uxrom.read_ppu(0x2400), 0x11);
+    }
+
+    #[test]
+    fn test_run_rom_bank_switch() {
+        // LDA #3; STA $8000; LDA $8000; STA $00; JMP self
+        let code = [
+            0xa9, 0x03, 0x8d, 0x00, 0x80, 0xad, 0x00, 0x80, 0x85, 0x00, 0x4c, 0x0a, 0xc0,
+        ];
+        let mut prg = numbered_prg(8);
+        let last = 7 * 16 * KIB;
+        prg[last..last + code.len()].copy_from_slice(&code);
+        prg[last + 0x3ffc] = 0x00;
+        prg[last + 0x3ffd] = 0xc0;
+
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([8, 0, 0x21, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(prg);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        for _ in 0..5 {
+            machine.step_instruction();
+        }
+        assert_eq!(machine.bus.read_immutable(0x00), 3);
+        assert_eq!(machine.bus.read_immutable(0xc000), 0xa9);
+    }
+}
@@ -7,11 +7,15 @@ mod ppu_registers;
 
 pub use apu::Apu;
 pub use i_ram::IRam;
-pub use input::Input;
-pub use mapper::Mapper;
+pub use input::{Buttons, Input};
+pub use mapper::{ConsoleType, HeaderFormat, INesHeader, Mapper, NametableArrangement, Timing};
 pub use p_ram::PRam;
 pub use ppu_registers::*;
 
+use super::cpu::CpuBus;
+use super::save_state::{Snapshot, StateReader, StateWriter};
+use crate::NesMachineError;
+
 pub trait Device {
     /// Reset button behavior
     fn reset(&mut self);
@@ -33,7 +37,7 @@ pub trait PpuDevice {
     fn write_ppu(&mut self, addr: u16, value: u8);
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Bus {
     pub iram: IRam,
     pub ppu_regs: PpuRegisters,
@@ -43,6 +47,9 @@ pub struct Bus {
     pub pram: PRam,
 
     pub cart: Mapper,
+
+    /// Page written to $4014, waiting for the DMA to start
+    pub oam_dma_page: Option<u8>,
 }
 
 impl Bus {
@@ -50,6 +57,11 @@ impl Bus {
     pub fn read(&mut self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1fff => self.iram.read(addr),
+            0x2000..=0x3fff if self.reads_palette(addr) => {
+                let value = self.pram.read_ppu(self.ppu_regs.v);
+                self.ppu_regs.read(addr);
+                value
+            }
             0x2000..=0x3fff => self.ppu_regs.read(addr),
             0x4000..=0x4013 => self.apu.read(addr),
             0x4014 => 0,
@@ -64,6 +76,7 @@ impl Bus {
     pub fn read_immutable(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1fff => self.iram.read_immutable(addr),
+            0x2000..=0x3fff if self.reads_palette(addr) => self.pram.read_ppu(self.ppu_regs.v),
             0x2000..=0x3fff => self.ppu_regs.read_immutable(addr),
             0x4000..=0x4013 => self.apu.read_immutable(addr),
             0x4014 => 0,
@@ -78,9 +91,15 @@ impl Bus {
     pub fn write(&mut self, addr: u16, value: u8) {
         match addr {
             0x0000..=0x1fff => self.iram.write(addr, value),
-            0x2000..=0x3fff => self.ppu_regs.write(addr, value),
+            0x2000..=0x3fff => {
+                self.ppu_regs.write(addr, value);
+                // Second $2006 write puts the new address on the PPU bus.
+                if addr % 8 == 6 && !self.ppu_regs.w {
+                    self.cart.ppu_bus_addr(self.ppu_regs.v);
+                }
+            }
             0x4000..=0x4013 => self.apu.write(addr, value),
-            0x4014 => (), // TODO: OAM DMA
+            0x4014 => self.oam_dma_page = Some(value),
             0x4015 => self.apu.write(addr, value),
             0x4016 => self.input.write(addr, value),
             0x4017 => self.apu.write(addr, value),
@@ -89,6 +108,24 @@ impl Bus {
         }
     }
 
+    /// Step the APU one CPU cycle.
+    /// Returns the no. of cycles the CPU is stalled for by a DMC sample fetch.
+    pub fn step_apu(&mut self) -> usize {
+        self.apu.step();
+
+        let Some(addr) = self.apu.dmc_fetch_addr() else {
+            return 0;
+        };
+        let value = self.read(addr);
+        self.apu.dmc_fill(value);
+        4
+    }
+
+    /// PPUDATA reads from palette RAM are immediate instead of going through the read buffer.
+    fn reads_palette(&self, addr: u16) -> bool {
+        addr % 8 == 7 && self.ppu_regs.v & 0x3fff >= 0x3f00
+    }
+
     /// Read PPU address space
     pub fn read_ppu(&self, addr: u16) -> u8 {
         match addr {
@@ -98,6 +135,17 @@ impl Bus {
         }
     }
 
+    /// Read PPU address space as the PPU does, with the address visible to the cart.
+    pub fn fetch_ppu(&mut self, addr: u16) -> u8 {
+        self.cart.ppu_bus_addr(addr);
+        self.read_ppu(addr)
+    }
+
+    /// IRQ line, shared by everything that can pull it
+    pub fn irq(&self) -> bool {
+        self.apu.irq() || self.cart.irq()
+    }
+
     /// Write PPU address space
     pub fn write_ppu(&mut self, addr: u16, value: u8) {
         match addr {
@@ -109,5 +157,45 @@ impl Bus {
 
     pub fn reset(&mut self) {
         self.ppu_regs.reset();
+        self.apu.reset();
+    }
+}
+
+/// Memory map only: nothing else runs along with the CPU and the interrupt lines stay
+/// inactive. [NesMachine](crate::NesMachine) wraps the bus to run the rest of the machine.
+impl CpuBus for Bus {
+    fn read(&mut self, addr: u16) -> u8 {
+        Bus::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        Bus::write(self, addr, value)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Bus::read_immutable(self, addr)
+    }
+}
+
+impl Snapshot for Bus {
+    fn snapshot(&self, w: &mut StateWriter) {
+        self.iram.snapshot(w);
+        self.ppu_regs.snapshot(w);
+        self.apu.snapshot(w);
+        self.input.snapshot(w);
+        self.pram.snapshot(w);
+        self.cart.snapshot(w);
+        w.option_u8(self.oam_dma_page);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.iram.restore(r)?;
+        self.ppu_regs.restore(r)?;
+        self.apu.restore(r)?;
+        self.input.restore(r)?;
+        self.pram.restore(r)?;
+        self.cart.restore(r)?;
+        self.oam_dma_page = r.option_u8()?;
+        Ok(())
     }
 }
@@ -1,25 +1,73 @@
 use super::PpuDevice;
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
 /// PPU Palette RAM
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PRam(pub [u8; Self::SIZE]);
 
 impl PRam {
     pub const SIZE: usize = 0x20;
+
+    /// Contents at power-up are unspecified. These are the values from one console.
+    const POWER_UP: [u8; Self::SIZE] = [
+        0x09, 0x01, 0x00, 0x01, 0x00, 0x02, 0x02, 0x0d, 0x08, 0x10, 0x08, 0x24, 0x00, 0x00, 0x04,
+        0x2c, 0x09, 0x01, 0x34, 0x03, 0x00, 0x04, 0x00, 0x14, 0x08, 0x3a, 0x00, 0x02, 0x00, 0x20,
+        0x2c, 0x08,
+    ];
+
+    /// $3F10, $3F14, $3F18 and $3F1C mirror the backdrop entries of the BG palettes.
+    fn index(addr: u16) -> usize {
+        let idx = addr as usize % Self::SIZE;
+        if idx & 0x13 == 0x10 { idx & 0x0f } else { idx }
+    }
 }
 
 impl Default for PRam {
     fn default() -> Self {
-        Self([0xff; Self::SIZE])
+        Self(Self::POWER_UP)
     }
 }
 
 impl PpuDevice for PRam {
     fn read_ppu(&self, addr: u16) -> u8 {
-        self.0[addr as usize % Self::SIZE]
+        self.0[Self::index(addr)]
     }
 
+    /// Entries are 6 bits wide.
     fn write_ppu(&mut self, addr: u16, value: u8) {
-        self.0[addr as usize % Self::SIZE] = value;
+        self.0[Self::index(addr)] = value & 0x3f;
+    }
+}
+
+impl Snapshot for PRam {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bytes(&self.0);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        r.bytes(&mut self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backdrop_mirrors() {
+        let mut pram = PRam::default();
+        for (i, addr) in [0x3f10, 0x3f14, 0x3f18, 0x3f1c].into_iter().enumerate() {
+            pram.write_ppu(addr, i as u8 + 1);
+            assert_eq!(pram.read_ppu(addr - 0x10), i as u8 + 1);
+            pram.write_ppu(addr - 0x10, i as u8 + 0x10);
+            assert_eq!(pram.read_ppu(addr), i as u8 + 0x10);
+        }
+
+        // Other sprite palette entries are their own.
+        pram.write_ppu(0x3f11, 0x21);
+        pram.write_ppu(0x3f01, 0x22);
+        assert_eq!(pram.read_ppu(0x3f11), 0x21);
+        assert_eq!(pram.read_ppu(0x3f31), 0x21);
     }
 }
@@ -1,4 +1,6 @@
 use super::{CpuDevice, Device};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
 
 use std::fmt::Display;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,6 +77,18 @@ impl From<u8> for PpuCtrl {
     }
 }
 
+impl From<PpuCtrl> for u8 {
+    fn from(value: PpuCtrl) -> Self {
+        (((value.base_nametable_addr - 0x2000) >> 10) as u8)
+            | ((value.vram_big_increment as u8) << 2)
+            | (((value.base_sprite_pattern_addr != 0) as u8) << 3)
+            | (((value.base_bg_pattern_addr != 0) as u8) << 4)
+            | ((value.tall_sprites as u8) << 5)
+            | ((value.slave as u8) << 6)
+            | ((value.nmi_enable as u8) << 7)
+    }
+}
+
 impl PpuCtrl {
     /// Reset button behavior
     pub fn reset(&mut self) {
@@ -101,6 +115,26 @@ pub struct PpuMask {
     pub grayscale: bool,
 }
 
+impl From<PpuMask> for u8 {
+    fn from(value: PpuMask) -> Self {
+        (value.grayscale as u8)
+            | ((value.bg_mask as u8) << 1)
+            | ((value.sprite_mask as u8) << 2)
+            | ((value.bg as u8) << 3)
+            | ((value.sprite as u8) << 4)
+            | ((value.red as u8) << 5)
+            | ((value.green as u8) << 6)
+            | ((value.blue as u8) << 7)
+    }
+}
+
+impl PpuMask {
+    /// Color emphasis bits: R, G, B from lsb
+    pub fn emphasis(&self) -> u8 {
+        (self.red as u8) | ((self.green as u8) << 1) | ((self.blue as u8) << 2)
+    }
+}
+
 impl Default for PpuMask {
     fn default() -> Self {
         Self::from(0)
@@ -148,7 +182,7 @@ impl PpuMask {
 
 /// This is inside the bus to make it accessible to both CPU and PPU.
 /// PPU is not here so it can access devices through the bus.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PpuRegisters {
     // /-- PPU CTRL register
     pub ctrl: PpuCtrl,
@@ -162,15 +196,31 @@ pub struct PpuRegisters {
 
     pub oam_addr: u8,
     pub oam_data: u8,
-
-    pub scroll_x: u8,
-    pub scroll_y: u8,
-    pub scroll_last_x: bool,
-
-    pub ppu_addr: u16,
-    pub ppu_addr_last_msb: bool,
+    /// Primary OAM. 64 sprites, 4 bytes each: Y, tile, attributes, X.
+    pub oam: [u8; 256],
+
+    /// Current VRAM address (15 bits)
+    ///
+    /// ```text
+    /// yyy NN YYYYY XXXXX
+    /// ||| || ||||| +++++-- coarse X scroll
+    /// ||| || +++++-------- coarse Y scroll
+    /// ||| ++-------------- nametable select
+    /// +++----------------- fine Y scroll
+    /// ```
+    pub v: u16,
+    /// Temporary VRAM address (15 bits). Same layout as `v`.
+    pub t: u16,
+    /// Fine x scroll (3 bits)
+    pub x: u8,
+    /// Write toggle, shared by $2005 and $2006
+    pub w: bool,
+
+    /// VRAM address of the pending CPU access through $2007
+    pub ppu_access_addr: u16,
     pub ppu_read_buf: u8,
     pub ppu_read_refresh: bool,
+    pub ppu_write_buf: u8,
     pub ppu_written: bool,
 
     pub oam_dma: u8,
@@ -188,13 +238,15 @@ impl Default for PpuRegisters {
 
             oam_addr: 0,
             oam_data: 0,
-            scroll_x: 0,
-            scroll_y: 0,
-            scroll_last_x: false,
-            ppu_addr: 0,
-            ppu_addr_last_msb: Default::default(),
+            oam: [0; 256],
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            ppu_access_addr: 0,
             ppu_read_buf: 0,
             ppu_read_refresh: false,
+            ppu_write_buf: 0,
             ppu_written: false,
             oam_dma: Default::default(),
         }
@@ -216,18 +268,26 @@ impl PpuRegisters {
         value
     }
 
-    /// Reading clears vblank flag
+    /// Reading clears vblank flag and the write toggle
     fn status_destructive(&mut self) -> u8 {
         let status = self.status();
         self.vblank = false;
+        self.w = false;
         status
     }
 
     fn read_oamdata(&self) -> u8 {
-        0
+        let value = self.oam[self.oam_addr as usize];
+        // Unimplemented attribute bits don't exist in OAM and read back as 0.
+        if self.oam_addr % 4 == 2 {
+            value & 0xe3
+        } else {
+            value
+        }
     }
 
     fn read_vram(&mut self) -> u8 {
+        self.ppu_access_addr = self.v;
         self.inc_vram_addr();
         self.ppu_read_refresh = true;
         self.ppu_read_buf
@@ -235,6 +295,8 @@ impl PpuRegisters {
 
     fn write_ppuctrl(&mut self, value: u8) {
         self.ctrl = PpuCtrl::from(value);
+        // t: ...GH.. ........ <- d: ......GH
+        self.t = (self.t & !0x0c00) | ((value as u16 & 0x03) << 10);
     }
 
     fn write_ppumask(&mut self, value: u8) {
@@ -245,46 +307,108 @@ impl PpuRegisters {
         self.oam_addr = value;
     }
 
-    fn write_oamdata(&mut self, _value: u8) {}
+    fn write_oamdata(&mut self, value: u8) {
+        self.oam[self.oam_addr as usize] = value;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
 
     fn write_ppuscroll(&mut self, value: u8) {
-        if self.scroll_last_x {
-            self.scroll_y = value;
+        let value = value as u16;
+        if self.w {
+            // t: FGH..AB CDE..... <- d: ABCDEFGH
+            self.t = (self.t & !0x73e0) | ((value & 0x07) << 12) | ((value & 0xf8) << 2);
         } else {
-            self.scroll_x = value;
+            // t: ....... ...ABCDE <- d: ABCDE...
+            // x:              FGH <- d: .....FGH
+            self.t = (self.t & !0x001f) | (value >> 3);
+            self.x = value as u8 & 0x07;
         }
-        self.scroll_last_x = !self.scroll_last_x;
+        self.w = !self.w;
     }
 
     fn write_ppuaddr(&mut self, value: u8) {
         let value = value as u16;
-        if self.ppu_addr_last_msb {
-            self.ppu_addr &= 0xff00;
-            self.ppu_addr += value;
+        if self.w {
+            // t: ....... ABCDEFGH <- d: ABCDEFGH
+            // v: <...all bits...> <- t: <...all bits...>
+            self.t = (self.t & 0xff00) | value;
+            self.v = self.t;
         } else {
-            self.ppu_addr &= 0x00ff;
-            self.ppu_addr += value << 8;
+            // t: .CDEFGH ........ <- d: ..CDEFGH
+            //        <unused>     <- d: AB......
+            // t: Z...... ........ <- 0 (bit Z is cleared)
+            self.t = (self.t & 0x00ff) | ((value & 0x3f) << 8);
         }
-        self.ppu_addr_last_msb = !self.ppu_addr_last_msb;
+        self.w = !self.w;
     }
 
     fn write_vram(&mut self, value: u8) {
+        self.ppu_access_addr = self.v;
         self.inc_vram_addr();
-        self.ppu_read_buf = value;
+        self.ppu_write_buf = value;
         self.ppu_written = true;
     }
 
     fn inc_vram_addr(&mut self) {
-        self.ppu_addr += if self.ctrl.vram_big_increment { 32 } else { 1 };
+        let inc = if self.ctrl.vram_big_increment { 32 } else { 1 };
+        self.v = self.v.wrapping_add(inc) & 0x7fff;
+    }
+
+    /// PPU /NMI output. The CPU triggers on it going active.
+    pub fn nmi_line(&self) -> bool {
+        self.vblank && self.ctrl.nmi_enable
+    }
+
+    /// Rendering uses the PPU address space when either layer is enabled.
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask.bg || self.mask.sprite
+    }
+
+    /// Move to the next tile horizontally, wrapping into the neighboring nametable.
+    pub fn increment_coarse_x(&mut self) {
+        if self.v & 0x001f == 31 {
+            self.v &= !0x001f;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
     }
 
-    /// OAM DMA hi addr
-    fn write_oamdma(&self, _value: u8) {}
+    /// Move to the next pixel row, wrapping into the neighboring nametable after row 29.
+    pub fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03e0) >> 5;
+        match coarse_y {
+            29 => {
+                coarse_y = 0;
+                self.v ^= 0x0800;
+            }
+            // Out of bounds row, wraps without switching nametable
+            31 => coarse_y = 0,
+            _ => coarse_y += 1,
+        }
+        self.v = (self.v & !0x03e0) | (coarse_y << 5);
+    }
+
+    /// v: ....A.. ...BCDEF <- t: ....A.. ...BCDEF
+    pub fn transfer_x(&mut self) {
+        self.v = (self.v & !0x041f) | (self.t & 0x041f);
+    }
+
+    /// v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
+    pub fn transfer_y(&mut self) {
+        self.v = (self.v & !0x7be0) | (self.t & 0x7be0);
+    }
 }
 
 impl Device for PpuRegisters {
     fn reset(&mut self) {
-        self.scroll_last_x = false;
+        self.w = false;
     }
 }
 
@@ -316,11 +440,6 @@ impl CpuDevice for PpuRegisters {
     }
 
     fn write(&mut self, addr: u16, value: u8) {
-        if addr == 0x4014 {
-            self.write_oamdma(value);
-            return;
-        }
-
         if let 0x2000..=0x3fff = addr {
             match addr % 8 {
                 0x00 => self.write_ppuctrl(value),
@@ -335,3 +454,147 @@ impl CpuDevice for PpuRegisters {
         }
     }
 }
+
+impl Snapshot for PpuRegisters {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.u8(self.ctrl.into());
+        w.u8(self.mask.into());
+        w.bool(self.vblank);
+        w.bool(self.sprite_0_hit);
+        w.bool(self.sprite_overflow);
+
+        w.u8(self.oam_addr);
+        w.u8(self.oam_data);
+        w.bytes(&self.oam);
+
+        w.u16(self.v);
+        w.u16(self.t);
+        w.u8(self.x);
+        w.bool(self.w);
+
+        w.u16(self.ppu_access_addr);
+        w.u8(self.ppu_read_buf);
+        w.bool(self.ppu_read_refresh);
+        w.u8(self.ppu_write_buf);
+        w.bool(self.ppu_written);
+
+        w.u8(self.oam_dma);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.ctrl = PpuCtrl::from(r.u8()?);
+        self.mask = PpuMask::from(r.u8()?);
+        self.vblank = r.bool()?;
+        self.sprite_0_hit = r.bool()?;
+        self.sprite_overflow = r.bool()?;
+
+        self.oam_addr = r.u8()?;
+        self.oam_data = r.u8()?;
+        r.bytes(&mut self.oam)?;
+
+        self.v = r.u16()?;
+        self.t = r.u16()?;
+        self.x = r.u8()?;
+        self.w = r.bool()?;
+
+        self.ppu_access_addr = r.u16()?;
+        self.ppu_read_buf = r.u8()?;
+        self.ppu_read_refresh = r.bool()?;
+        self.ppu_write_buf = r.u8()?;
+        self.ppu_written = r.bool()?;
+
+        self.oam_dma = r.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ctrl_and_mask_bytes() {
+        for value in 0..=0xff {
+            assert_eq!(u8::from(PpuCtrl::from(value)), value);
+            assert_eq!(u8::from(PpuMask::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_scroll_and_addr_writes() {
+        // Example sequence from the nesdev wiki "PPU scrolling" article
+        let mut regs = PpuRegisters::default();
+
+        regs.write(0x2000, 0x00);
+        assert_eq!(regs.t & 0x0c00, 0);
+        regs.read(0x2002);
+        assert!(!regs.w);
+
+        regs.write(0x2005, 0x7d);
+        assert_eq!(regs.t, 0x000f);
+        assert_eq!(regs.x, 0b101);
+        assert!(regs.w);
+
+        regs.write(0x2005, 0x5e);
+        assert_eq!(regs.t, 0x616f);
+        assert!(!regs.w);
+
+        regs.write(0x2006, 0x3d);
+        assert_eq!(regs.t, 0x3d6f);
+        assert!(regs.w);
+
+        regs.write(0x2006, 0xf0);
+        assert_eq!(regs.t, 0x3df0);
+        assert_eq!(regs.v, regs.t);
+        assert!(!regs.w);
+    }
+
+    #[test]
+    fn test_oam_access() {
+        let mut regs = PpuRegisters::default();
+        regs.write(0x2003, 0xfe);
+        regs.write(0x2004, 0x11);
+        regs.write(0x2004, 0xff);
+        regs.write(0x2004, 0x33);
+        assert_eq!(regs.oam_addr, 0x01);
+        assert_eq!(regs.oam[0], 0x33);
+
+        // Attribute byte, masked. Reading doesn't increment.
+        regs.write(0x2003, 0xfe);
+        assert_eq!(regs.read(0x2004), 0x01);
+        assert_eq!(regs.read(0x2004), 0x01);
+        assert_eq!(regs.oam_addr, 0xfe);
+
+        regs.write(0x2003, 0xff);
+        assert_eq!(regs.read(0x2004), 0xff);
+    }
+
+    #[test]
+    fn test_increment_coarse_x_wraps_nametable() {
+        let mut regs = PpuRegisters {
+            v: 0x001f,
+            ..Default::default()
+        };
+        regs.increment_coarse_x();
+        assert_eq!(regs.v, 0x0400);
+    }
+
+    #[test]
+    fn test_increment_y() {
+        let mut regs = PpuRegisters {
+            v: 0x7000 | (29 << 5),
+            ..Default::default()
+        };
+        regs.increment_y();
+        assert_eq!(regs.v, 0x0800);
+
+        // Row 31 wraps without switching nametables
+        regs.v = 0x7000 | (31 << 5);
+        regs.increment_y();
+        assert_eq!(regs.v, 0);
+
+        regs.v = 0x2000 | (3 << 5);
+        regs.increment_y();
+        assert_eq!(regs.v, 0x3000 | (3 << 5));
+    }
+}
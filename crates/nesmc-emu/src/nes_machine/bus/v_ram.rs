@@ -1,7 +1,7 @@
 use super::PpuDevice;
 
 /// PPU VRAM
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VRam([u8; Self::SIZE]);
 
 impl VRam {
@@ -0,0 +1,135 @@
+/// Address space of the CPU. [Bus](crate::bus::Bus) is the NES implementation, but the CPU
+/// runs on anything that implements this.
+///
+/// Every CPU cycle is exactly one [Self::tick] followed by one read or write, dummy accesses
+/// included. Interrupt lines are sampled at the end of each cycle.
+pub trait CpuBus {
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Read without side effects, for debuggers and the like
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Called at the start of every CPU cycle, before its read or write
+    fn tick(&mut self) {}
+
+    /// NMI input. Edge-triggered.
+    fn nmi_line(&self) -> bool {
+        false
+    }
+
+    /// IRQ input. Level-triggered.
+    fn irq_line(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CpuBus;
+    use crate::nes_machine::cpu::Cpu;
+
+    /// 64 KB of RAM that counts cycles and logs accesses
+    struct RamBus {
+        ram: Vec<u8>,
+        cycles: usize,
+        /// Address, value, and true for writes
+        log: Vec<(u16, u8, bool)>,
+    }
+
+    impl RamBus {
+        fn new(code: &[u8]) -> Self {
+            let mut ram = vec![0; 0x10000];
+            ram[0x0200..0x0200 + code.len()].copy_from_slice(code);
+            ram[0xfffc..0xfffe].copy_from_slice(&[0x00, 0x02]);
+            Self {
+                ram,
+                cycles: 0,
+                log: vec![],
+            }
+        }
+    }
+
+    impl CpuBus for RamBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            let value = self.ram[addr as usize];
+            self.log.push((addr, value, false));
+            value
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.log.push((addr, value, true));
+            self.ram[addr as usize] = value;
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        fn tick(&mut self) {
+            self.cycles += 1;
+        }
+    }
+
+    #[test]
+    fn test_run_without_nes() {
+        // Sum 1..=10 to $10
+        #[rustfmt::skip]
+        let code = [
+            0xa2, 0x0a, // LDX #10
+            0xa9, 0x00, // LDA #0
+            0x18,       // CLC
+            0x86, 0x11, // STX $11
+            0x65, 0x11, // ADC $11
+            0xca,       // DEX
+            0xd0, 0xf8, // BNE -8
+            0x85, 0x10, // STA $10
+        ];
+        let mut bus = RamBus::new(&code);
+        let mut cpu = Cpu::new(&bus);
+        assert_eq!(cpu.pc, 0x0200);
+
+        while cpu.pc != 0x0200 + code.len() as u16 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(bus.peek(0x10), 55);
+        // LDX, LDA: 4. Loop: 10 * (CLC 2 + STX 3 + ADC 3 + DEX 2 + BNE 3) - 1. STA: 3.
+        assert_eq!(bus.cycles, 4 + 129 + 3);
+    }
+
+    #[test]
+    fn test_one_access_per_cycle() {
+        #[rustfmt::skip]
+        let code = [
+            0xbd, 0xf0, 0x02, // LDA $02f0,X
+            0x9d, 0x10, 0x03, // STA $0310,X
+            0xe6, 0x40,       // INC $40
+            0xe8,             // INX
+        ];
+        let mut bus = RamBus::new(&code);
+        bus.ram[0x40] = 0x41;
+        bus.ram[0x0300] = 0x99;
+        let mut cpu = Cpu::new(&bus);
+        cpu.x = 0x10;
+
+        for _ in 0..4 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(bus.cycles, bus.log.len());
+        #[rustfmt::skip]
+        assert_eq!(bus.log, [
+            // Page crossed: the wrong page is read first.
+            (0x0200, 0xbd, false), (0x0201, 0xf0, false), (0x0202, 0x02, false),
+            (0x0200, 0xbd, false), (0x0300, 0x99, false),
+            // Stores always read before writing.
+            (0x0203, 0x9d, false), (0x0204, 0x10, false), (0x0205, 0x03, false),
+            (0x0320, 0x00, false), (0x0320, 0x99, true),
+            // The old value is written back before the new one.
+            (0x0206, 0xe6, false), (0x0207, 0x40, false),
+            (0x0040, 0x41, false), (0x0040, 0x41, true), (0x0040, 0x42, true),
+            // Implied instructions read the next byte and throw it away.
+            (0x0208, 0xe8, false), (0x0209, 0x00, false),
+        ]);
+    }
+}
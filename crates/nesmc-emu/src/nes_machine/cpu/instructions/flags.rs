@@ -1,45 +1,45 @@
-use crate::nes_machine::cpu::Cpu;
+use crate::nes_machine::cpu::{Cpu, CpuBus};
 
 impl Cpu {
     /// Clear carry
-    pub(super) fn instr_clc(&mut self) -> usize {
+    pub(super) fn instr_clc(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.c = false;
-        2
     }
 
     /// Clear decimal
-    pub(super) fn instr_cld(&mut self) -> usize {
+    pub(super) fn instr_cld(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.d = false;
-        2
     }
 
     /// Clear interrupt disable
-    pub(super) fn instr_cli(&mut self) -> usize {
+    pub(super) fn instr_cli(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.i = false;
-        2
     }
 
     /// Clear overflow
-    pub(super) fn instr_clv(&mut self) -> usize {
+    pub(super) fn instr_clv(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.v = false;
-        2
     }
 
     /// Set carry
-    pub(super) fn instr_sec(&mut self) -> usize {
+    pub(super) fn instr_sec(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.c = true;
-        2
     }
 
     /// Set decimal
-    pub(super) fn instr_sed(&mut self) -> usize {
+    pub(super) fn instr_sed(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.d = true;
-        2
     }
 
     /// Set interrupt disable
-    pub(super) fn instr_sei(&mut self) -> usize {
+    pub(super) fn instr_sei(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
         self.status.i = true;
-        2
     }
 }
@@ -1,4 +1,4 @@
-use crate::{bus::Bus, nes_machine::cpu::Cpu};
+use crate::nes_machine::cpu::{Cpu, CpuBus, operand::unfixed_address};
 
 impl Cpu {
     fn instr_lax(&mut self, value: u8) {
@@ -8,144 +8,114 @@ impl Cpu {
         self.x = value;
     }
 
-    pub(super) fn instr_nop_imm(&mut self, bus: &mut Bus) -> usize {
+    /// Lock up the CPU. PC is left on the opcode.
+    pub(super) fn instr_jam(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
+        self.halted = true;
+        self.pc = self.pc.wrapping_sub(1);
+    }
+
+    pub(super) fn instr_nop_imm(&mut self, bus: &mut impl CpuBus) {
         let _ = self.fetch_operand_imm(bus);
-        2
     }
 
-    pub(super) fn instr_nop_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_nop_abs(&mut self, bus: &mut impl CpuBus) {
         let _ = self.fetch_operand_abs(bus);
-        4
     }
 
-    pub(super) fn instr_nop_absx(&mut self, bus: &mut Bus) -> usize {
-        let mut cycles = 4;
-        let addr = self.fetch_address_absx(bus);
-        // Page boundary crossed
-        if addr & 0xff < self.x as u16 {
-            cycles += 1
-        }
-        cycles
+    pub(super) fn instr_nop_absx(&mut self, bus: &mut impl CpuBus) {
+        let _ = self.fetch_operand_absx(bus);
     }
 
-    pub(super) fn instr_nop_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_nop_zpg(&mut self, bus: &mut impl CpuBus) {
         let _ = self.fetch_operand_zpg(bus);
-        3
     }
 
-    pub(super) fn instr_nop_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_nop_zpgx(&mut self, bus: &mut impl CpuBus) {
         let _ = self.fetch_operand_zpgx(bus);
-        4
     }
 
-    pub(super) fn instr_lax_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_lax_abs(&mut self, bus: &mut impl CpuBus) {
         let value = self.fetch_operand_abs(bus);
         self.instr_lax(value);
-        4
     }
-    pub(super) fn instr_lax_absy(&mut self, bus: &mut Bus) -> usize {
-        let mut cycles = 4;
-        let addr = self.fetch_address_absy(bus);
-        // Page boundary crossed
-        if addr & 0xff < self.y as u16 {
-            cycles += 1
-        }
-        self.instr_lax(bus.read(addr));
-        cycles
+    pub(super) fn instr_lax_absy(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_absy(bus);
+        self.instr_lax(value);
     }
-    pub(super) fn instr_lax_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_lax_xind(&mut self, bus: &mut impl CpuBus) {
         let value = self.fetch_operand_xind(bus);
         self.instr_lax(value);
-        6
     }
-    pub(super) fn instr_lax_indy(&mut self, bus: &mut Bus) -> usize {
-        let mut cycles = 5;
-        let addr = self.fetch_address_indy(bus);
-        // Page boundary crossed
-        if addr & 0xff < self.y as u16 {
-            cycles += 1
-        }
-        self.instr_lax(bus.read(addr));
-        cycles
+    pub(super) fn instr_lax_indy(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_indy(bus);
+        self.instr_lax(value);
     }
-    pub(super) fn instr_lax_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_lax_zpg(&mut self, bus: &mut impl CpuBus) {
         let value = self.fetch_operand_zpg(bus);
         self.instr_lax(value);
-        3
     }
-    pub(super) fn instr_lax_zpgy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_lax_zpgy(&mut self, bus: &mut impl CpuBus) {
         let value = self.fetch_operand_zpgy(bus);
         self.instr_lax(value);
-        4
     }
 
-    pub(super) fn instr_sax_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sax_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
-        bus.write(addr, self.a & self.x);
-        4
+        self.write(bus, addr, self.a & self.x);
     }
-    pub(super) fn instr_sax_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sax_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
-        bus.write(addr, self.a & self.x);
-        6
+        self.write(bus, addr, self.a & self.x);
     }
-    pub(super) fn instr_sax_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sax_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
-        bus.write(addr, self.a & self.x);
-        3
+        self.write(bus, addr, self.a & self.x);
     }
-    pub(super) fn instr_sax_zpgy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sax_zpgy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgy(bus);
-        bus.write(addr, self.a & self.x);
-        4
+        self.write(bus, addr, self.a & self.x);
     }
 
-    fn instr_dcp(&mut self, bus: &mut Bus, addr: u16) {
-        let value = bus.read(addr).wrapping_sub(1);
-        bus.write(addr, value);
+    fn instr_dcp(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let value = self.read_modify(bus, addr).wrapping_sub(1);
+        self.write(bus, addr, value);
         self.status.c = self.a >= value;
         self.status.z = self.a == value;
         self.set_negative(self.a.wrapping_sub(value));
     }
-    pub(super) fn instr_dcp_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_dcp(bus, addr);
-        6
     }
-    pub(super) fn instr_dcp_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_dcp(bus, addr);
-        7
     }
-    pub(super) fn instr_dcp_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_dcp(bus, addr);
-        7
     }
-    pub(super) fn instr_dcp_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_dcp(bus, addr);
-        8
     }
-    pub(super) fn instr_dcp_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_dcp(bus, addr);
-        8
     }
-    pub(super) fn instr_dcp_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_dcp(bus, addr);
-        5
     }
-    pub(super) fn instr_dcp_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_dcp_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_dcp(bus, addr);
-        6
     }
 
-    fn instr_isc(&mut self, bus: &mut Bus, addr: u16) {
-        let value = bus.read(addr).wrapping_add(1);
-        bus.write(addr, value);
+    fn instr_isc(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let value = self.read_modify(bus, addr).wrapping_add(1);
+        self.write(bus, addr, value);
 
         let carry = if self.status.c { 1 } else { 0 };
         let result = self.a.overflowing_add(!value).0;
@@ -156,140 +126,119 @@ impl Cpu {
         self.set_overflow(!value, result);
         self.a = result;
     }
-    pub(super) fn instr_isc_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_isc(bus, addr);
-        6
     }
-    pub(super) fn instr_isc_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_isc(bus, addr);
-        7
     }
-    pub(super) fn instr_isc_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_isc(bus, addr);
-        7
     }
-    pub(super) fn instr_isc_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_isc(bus, addr);
-        8
     }
-    pub(super) fn instr_isc_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_isc(bus, addr);
-        8
     }
-    pub(super) fn instr_isc_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_isc(bus, addr);
-        5
     }
-    pub(super) fn instr_isc_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_isc_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_isc(bus, addr);
-        6
     }
 
-    fn instr_slo(&mut self, bus: &mut Bus, addr: u16) {
-        let value = bus.read(addr);
+    fn instr_slo(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let value = self.read_modify(bus, addr);
         self.status.c = value & 0x80 != 0;
         let result = value << 1;
-        bus.write(addr, result);
+        self.write(bus, addr, result);
         self.a |= result;
         self.set_zero(self.a);
         self.set_negative(self.a);
     }
-    pub(super) fn instr_slo_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_slo(bus, addr);
-        6
     }
-    pub(super) fn instr_slo_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_slo(bus, addr);
-        7
     }
-    pub(super) fn instr_slo_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_slo(bus, addr);
-        7
     }
-    pub(super) fn instr_slo_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_slo(bus, addr);
-        8
     }
-    pub(super) fn instr_slo_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_slo(bus, addr);
-        8
     }
-    pub(super) fn instr_slo_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_slo(bus, addr);
-        5
     }
-    pub(super) fn instr_slo_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_slo_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_slo(bus, addr);
-        6
     }
 
-    fn instr_rla(&mut self, bus: &mut Bus, addr: u16) {
-        let data = bus.read(addr);
+    fn instr_rla(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let data = self.read_modify(bus, addr);
         let carry = if self.status.c { 1 } else { 0 };
         let shifted = (data << 1) | carry;
         self.status.c = data & 0x80 != 0;
-        bus.write(addr, shifted);
+        self.write(bus, addr, shifted);
         let result = self.a & shifted;
         self.set_zero(result);
         self.set_negative(result);
         self.a = result;
     }
-    pub(super) fn instr_rla_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_rla(bus, addr);
-        6
     }
-    pub(super) fn instr_rla_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_rla(bus, addr);
-        7
     }
-    pub(super) fn instr_rla_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_rla(bus, addr);
-        7
     }
-    pub(super) fn instr_rla_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_rla(bus, addr);
-        8
     }
-    pub(super) fn instr_rla_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_rla(bus, addr);
-        8
     }
-    pub(super) fn instr_rla_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_rla(bus, addr);
-        5
     }
-    pub(super) fn instr_rla_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rla_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_rla(bus, addr);
-        6
     }
 
-    fn instr_rra(&mut self, bus: &mut Bus, addr: u16) {
-        let value = bus.read(addr);
+    fn instr_rra(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let value = self.read_modify(bus, addr);
         let carry = if self.status.c { 0x80 } else { 0 };
         let shifted = (value >> 1) | carry;
         self.status.c = value & 0x01 != 0;
-        bus.write(addr, shifted);
+        self.write(bus, addr, shifted);
 
         let carry = if self.status.c { 1 } else { 0 };
         let (result, ov1) = self.a.overflowing_add(shifted); // add data
@@ -300,84 +249,274 @@ impl Cpu {
         self.set_overflow(shifted, result);
         self.a = result;
     }
-    pub(super) fn instr_rra_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_rra(bus, addr);
-        6
     }
-    pub(super) fn instr_rra_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_rra(bus, addr);
-        7
     }
-    pub(super) fn instr_rra_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_rra(bus, addr);
-        7
     }
-    pub(super) fn instr_rra_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_rra(bus, addr);
-        8
     }
-    pub(super) fn instr_rra_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_rra(bus, addr);
-        8
     }
-    pub(super) fn instr_rra_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_rra(bus, addr);
-        5
     }
-    pub(super) fn instr_rra_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_rra_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_rra(bus, addr);
-        6
     }
 
-    fn instr_sre(&mut self, bus: &mut Bus, addr: u16) {
-        let data = bus.read(addr);
+    fn instr_sre(&mut self, bus: &mut impl CpuBus, addr: u16) {
+        let data = self.read_modify(bus, addr);
         let result = data >> 1;
-        bus.write(addr, result);
+        self.write(bus, addr, result);
         self.a ^= result;
         self.set_zero(self.a);
         self.set_negative(self.a);
         self.status.c = data & 0x01 != 0;
     }
-    pub(super) fn instr_sre_abs(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_abs(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_abs(bus);
         self.instr_sre(bus, addr);
-        6
     }
-    pub(super) fn instr_sre_absx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_absx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absx(bus);
         self.instr_sre(bus, addr);
-        7
     }
-    pub(super) fn instr_sre_absy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_absy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_absy(bus);
         self.instr_sre(bus, addr);
-        7
     }
-    pub(super) fn instr_sre_xind(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_xind(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_xind(bus);
         self.instr_sre(bus, addr);
-        8
     }
-    pub(super) fn instr_sre_indy(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_indy(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_indy(bus);
         self.instr_sre(bus, addr);
-        8
     }
-    pub(super) fn instr_sre_zpg(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_zpg(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpg(bus);
         self.instr_sre(bus, addr);
-        5
     }
-    pub(super) fn instr_sre_zpgx(&mut self, bus: &mut Bus) -> usize {
+    pub(super) fn instr_sre_zpgx(&mut self, bus: &mut impl CpuBus) {
         let addr = self.fetch_address_zpgx(bus);
         self.instr_sre(bus, addr);
-        6
+    }
+
+    /// ANE/XAA and LXA mix A with a chip-dependent constant. This is the common value.
+    const UNSTABLE_MAGIC: u8 = 0xee;
+
+    /// AND, copy N to C
+    pub(super) fn instr_anc_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_imm(bus);
+        self.a &= value;
+        self.set_zero(self.a);
+        self.set_negative(self.a);
+        self.status.c = self.status.n;
+    }
+
+    /// AND, then LSR A
+    pub(super) fn instr_alr_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.a & self.fetch_operand_imm(bus);
+        self.status.c = value & 0x01 != 0;
+        self.a = value >> 1;
+        self.set_zero(self.a);
+        self.set_negative(self.a);
+    }
+
+    /// AND, then ROR A. C and V come from bits 6 and 5 of the result.
+    pub(super) fn instr_arr_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.a & self.fetch_operand_imm(bus);
+        let carry = if self.status.c { 0x80 } else { 0 };
+        self.a = (value >> 1) | carry;
+        self.set_zero(self.a);
+        self.set_negative(self.a);
+        self.status.c = self.a & 0x40 != 0;
+        self.status.v = ((self.a >> 6) ^ (self.a >> 5)) & 0x01 != 0;
+    }
+
+    /// Unstable: A = (A | magic) & X & imm
+    pub(super) fn instr_ane_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_imm(bus);
+        self.a = (self.a | Self::UNSTABLE_MAGIC) & self.x & value;
+        self.set_zero(self.a);
+        self.set_negative(self.a);
+    }
+
+    /// Unstable: A = X = (A | magic) & imm
+    pub(super) fn instr_lxa_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_imm(bus);
+        self.instr_lax((self.a | Self::UNSTABLE_MAGIC) & value);
+    }
+
+    /// X = (A & X) - imm, without borrow. Flags like CMP.
+    pub(super) fn instr_sbx_imm(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_imm(bus);
+        let ax = self.a & self.x;
+        self.status.c = ax >= value;
+        self.x = ax.wrapping_sub(value);
+        self.set_zero(self.x);
+        self.set_negative(self.x);
+    }
+
+    /// A = X = SP = memory & SP
+    pub(super) fn instr_las_absy(&mut self, bus: &mut impl CpuBus) {
+        let value = self.fetch_operand_absy(bus) & self.sp;
+        self.sp = value;
+        self.instr_lax(value);
+    }
+
+    /// Unstable stores (SHA, SHX, SHY, TAS) write `value & (H + 1)`, where H is the high byte of
+    /// the base address. If indexing crosses a page, the written value also replaces the high
+    /// byte of the target address.
+    fn unstable_store(&mut self, bus: &mut impl CpuBus, base: u16, index: u8, value: u8) {
+        let addr = base.wrapping_add(index as u16);
+        self.read(bus, unfixed_address(base, addr));
+        let value = value & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if addr & 0xff00 != base & 0xff00 {
+            ((value as u16) << 8) | (addr & 0x00ff)
+        } else {
+            addr
+        };
+        self.write(bus, addr, value);
+    }
+
+    pub(super) fn instr_sha_absy(&mut self, bus: &mut impl CpuBus) {
+        let base = self.fetch_address_abs(bus);
+        self.unstable_store(bus, base, self.y, self.a & self.x);
+    }
+
+    pub(super) fn instr_sha_indy(&mut self, bus: &mut impl CpuBus) {
+        let base = self.fetch_pointer_zpg(bus);
+        self.unstable_store(bus, base, self.y, self.a & self.x);
+    }
+
+    pub(super) fn instr_shx_absy(&mut self, bus: &mut impl CpuBus) {
+        let base = self.fetch_address_abs(bus);
+        self.unstable_store(bus, base, self.y, self.x);
+    }
+
+    pub(super) fn instr_shy_absx(&mut self, bus: &mut impl CpuBus) {
+        let base = self.fetch_address_abs(bus);
+        self.unstable_store(bus, base, self.x, self.y);
+    }
+
+    /// SP = A & X, then SHA
+    pub(super) fn instr_tas_absy(&mut self, bus: &mut impl CpuBus) {
+        let base = self.fetch_address_abs(bus);
+        self.sp = self.a & self.x;
+        self.unstable_store(bus, base, self.y, self.sp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::NesMachine;
+
+    /// Run one instruction from $0300 with the given A, X and Y.
+    fn run(code: &[u8], a: u8, x: u8, y: u8) -> NesMachine {
+        let mut machine = NesMachine::default();
+        for (i, byte) in code.iter().enumerate() {
+            machine.bus.write(0x0300 + i as u16, *byte);
+        }
+        machine.cpu.pc = 0x0300;
+        machine.cpu.a = a;
+        machine.cpu.x = x;
+        machine.cpu.y = y;
+        machine.step_instruction();
+        machine
+    }
+
+    #[test]
+    fn test_anc_alr_arr() {
+        let machine = run(&[0x0b, 0xf0], 0x9f, 0, 0);
+        assert_eq!(machine.cpu.a, 0x90);
+        assert!(machine.cpu.status.c && machine.cpu.status.n);
+
+        let machine = run(&[0x4b, 0x0f], 0x0b, 0, 0);
+        assert_eq!(machine.cpu.a, 0x05);
+        assert!(machine.cpu.status.c);
+
+        // Carry in is clear at power on. $c0 >> 1 = $60: C from bit 6, V from bit 6 ^ bit 5.
+        let machine = run(&[0x6b, 0xff], 0xc0, 0, 0);
+        assert_eq!(machine.cpu.a, 0x60);
+        assert!(machine.cpu.status.c);
+        assert!(!machine.cpu.status.v);
+        let machine = run(&[0x6b, 0xff], 0x80, 0, 0);
+        assert_eq!(machine.cpu.a, 0x40);
+        assert!(machine.cpu.status.c && machine.cpu.status.v);
+    }
+
+    #[test]
+    fn test_sbx_las() {
+        let machine = run(&[0xcb, 0x02], 0x0f, 0x3c, 0);
+        assert_eq!(machine.cpu.x, 0x0a);
+        assert!(machine.cpu.status.c);
+
+        // LAS $0400,Y
+        let mut machine = NesMachine::default();
+        machine.bus.write(0x0410, 0x3c);
+        for (i, byte) in [0xbb, 0x00, 0x04].iter().enumerate() {
+            machine.bus.write(0x0300 + i as u16, *byte);
+        }
+        machine.cpu.pc = 0x0300;
+        machine.cpu.y = 0x10;
+        machine.step_instruction();
+        assert_eq!(machine.cpu.sp, 0x3c & 0xfd);
+        assert_eq!(machine.cpu.a, machine.cpu.sp);
+        assert_eq!(machine.cpu.x, machine.cpu.sp);
+    }
+
+    #[test]
+    fn test_unstable_magic() {
+        // ANE #$ff
+        let machine = run(&[0x8b, 0xff], 0x00, 0x3f, 0);
+        assert_eq!(machine.cpu.a, 0x2e);
+        // LXA #$ff
+        let machine = run(&[0xab, 0xff], 0x01, 0, 0);
+        assert_eq!(machine.cpu.a, 0xef);
+        assert_eq!(machine.cpu.x, 0xef);
+    }
+
+    #[test]
+    fn test_unstable_stores() {
+        // SHX $0410,Y. No page cross: stores X & ($04 + 1).
+        let machine = run(&[0x9e, 0x10, 0x04], 0, 0xff, 0x10);
+        assert_eq!(machine.bus.read_immutable(0x0420), 0x05);
+
+        // SHY $04f0,X. Page cross: the value replaces the high byte of the address.
+        let machine = run(&[0x9c, 0xf0, 0x04], 0, 0x20, 0x03);
+        assert_eq!(machine.bus.read_immutable(0x0110), 0x01);
+
+        // TAS $0400,Y
+        let machine = run(&[0x9b, 0x00, 0x04], 0x3c, 0x0f, 0x01);
+        assert_eq!(machine.cpu.sp, 0x0c);
+        assert_eq!(machine.bus.read_immutable(0x0401), 0x04);
+
+        // SHA ($10),Y with ($10) = $05f0
+        let mut machine = NesMachine::default();
+        machine.bus.write(0x0010, 0xf0);
+        machine.bus.write(0x0011, 0x05);
+        for (i, byte) in [0x93, 0x10].iter().enumerate() {
+            machine.bus.write(0x0300 + i as u16, *byte);
+        }
+        machine.cpu.pc = 0x0300;
+        (machine.cpu.a, machine.cpu.x, machine.cpu.y) = (0xff, 0xff, 0x08);
+        machine.step_instruction();
+        assert_eq!(machine.bus.read_immutable(0x05f8), 0x06);
+        assert_eq!(machine.cycle_count, 7 + 6);
     }
 }
@@ -9,17 +9,13 @@ mod shift;
 
 use nesmc_types::instruction::OpCode;
 
-use crate::bus::Bus;
-
-use super::Cpu;
+use super::{Cpu, CpuBus};
 
 impl Cpu {
-    /// Returns the number of CPU cycles spent.
-    pub(super) fn exec_instruction(&mut self, bus: &mut Bus, op_code: OpCode) -> usize {
+    pub(super) fn exec_instruction(&mut self, bus: &mut impl CpuBus, op_code: OpCode) {
         match op_code {
             // Illegal
-            OpCode::Illegal(_) => 0,
-            OpCode::Jam => 0,
+            OpCode::Jam => self.instr_jam(bus),
             OpCode::NopImm => self.instr_nop_imm(bus),
             OpCode::NopAbs => self.instr_nop_abs(bus),
             OpCode::NopAbsX => self.instr_nop_absx(bus),
@@ -31,6 +27,18 @@ impl Cpu {
             OpCode::LaxIndY => self.instr_lax_indy(bus),
             OpCode::LaxZpg => self.instr_lax_zpg(bus),
             OpCode::LaxZpgY => self.instr_lax_zpgy(bus),
+            OpCode::AncImm => self.instr_anc_imm(bus),
+            OpCode::AlrImm => self.instr_alr_imm(bus),
+            OpCode::ArrImm => self.instr_arr_imm(bus),
+            OpCode::AneImm => self.instr_ane_imm(bus),
+            OpCode::LxaImm => self.instr_lxa_imm(bus),
+            OpCode::SbxImm => self.instr_sbx_imm(bus),
+            OpCode::LasAbsY => self.instr_las_absy(bus),
+            OpCode::ShaAbsY => self.instr_sha_absy(bus),
+            OpCode::ShaIndY => self.instr_sha_indy(bus),
+            OpCode::ShxAbsY => self.instr_shx_absy(bus),
+            OpCode::ShyAbsX => self.instr_shy_absx(bus),
+            OpCode::TasAbsY => self.instr_tas_absy(bus),
             OpCode::SaxAbs => self.instr_sax_abs(bus),
             OpCode::SaxXInd => self.instr_sax_xind(bus),
             OpCode::SaxZpg => self.instr_sax_zpg(bus),
@@ -95,7 +103,7 @@ impl Cpu {
             OpCode::AndXInd => self.instr_and_xind(bus),
             OpCode::AndZpg => self.instr_and_zpg(bus),
             OpCode::AndZpgX => self.instr_and_zpgx(bus),
-            OpCode::AslA => self.instr_asl_a(),
+            OpCode::AslA => self.instr_asl_a(bus),
             OpCode::AslAbs => self.instr_asl_abs(bus),
             OpCode::AslAbsX => self.instr_asl_absx(bus),
             OpCode::AslZpg => self.instr_asl_zpg(bus),
@@ -111,10 +119,10 @@ impl Cpu {
             OpCode::BrkImpl => self.instr_brk_impl(bus),
             OpCode::BvcRel => self.instr_bvc_rel(bus),
             OpCode::BvsRel => self.instr_bvs_rel(bus),
-            OpCode::ClcImpl => self.instr_clc(),
-            OpCode::CldImpl => self.instr_cld(),
-            OpCode::CliImpl => self.instr_cli(),
-            OpCode::ClvImpl => self.instr_clv(),
+            OpCode::ClcImpl => self.instr_clc(bus),
+            OpCode::CldImpl => self.instr_cld(bus),
+            OpCode::CliImpl => self.instr_cli(bus),
+            OpCode::ClvImpl => self.instr_clv(bus),
             OpCode::CmpAbs => self.instr_cmp_abs(bus),
             OpCode::CmpAbsX => self.instr_cmp_absx(bus),
             OpCode::CmpAbsY => self.instr_cmp_absy(bus),
@@ -133,8 +141,8 @@ impl Cpu {
             OpCode::DecAbsX => self.instr_dec_absx(bus),
             OpCode::DecZpg => self.instr_dec_zpg(bus),
             OpCode::DecZpgX => self.instr_dec_zpgx(bus),
-            OpCode::DexImpl => self.instr_dex_impl(),
-            OpCode::DeyImpl => self.instr_dey_impl(),
+            OpCode::DexImpl => self.instr_dex_impl(bus),
+            OpCode::DeyImpl => self.instr_dey_impl(bus),
             OpCode::EorAbs => self.instr_eor_abs(bus),
             OpCode::EorAbsX => self.instr_eor_absx(bus),
             OpCode::EorAbsY => self.instr_eor_absy(bus),
@@ -147,8 +155,8 @@ impl Cpu {
             OpCode::IncAbsX => self.instr_inc_absx(bus),
             OpCode::IncZpg => self.instr_inc_zpg(bus),
             OpCode::IncZpgX => self.instr_inc_zpgx(bus),
-            OpCode::InxImpl => self.instr_inx_impl(),
-            OpCode::InyImpl => self.instr_iny_impl(),
+            OpCode::InxImpl => self.instr_inx_impl(bus),
+            OpCode::InyImpl => self.instr_iny_impl(bus),
             OpCode::JmpAbs => self.instr_jmp_abs(bus),
             OpCode::JmpInd => self.instr_jmp_ind(bus),
             OpCode::JsrAbs => self.instr_jsr_abs(bus),
@@ -170,12 +178,12 @@ impl Cpu {
             OpCode::LdyImm => self.instr_ldy_imm(bus),
             OpCode::LdyZpg => self.instr_ldy_zpg(bus),
             OpCode::LdyZpgX => self.instr_ldy_zpgx(bus),
-            OpCode::LsrA => self.instr_lsr_a(),
+            OpCode::LsrA => self.instr_lsr_a(bus),
             OpCode::LsrAbs => self.instr_lsr_abs(bus),
             OpCode::LsrAbsX => self.instr_lsr_absx(bus),
             OpCode::LsrZpg => self.instr_lsr_zpg(bus),
             OpCode::LsrZpgX => self.instr_lsr_zpgx(bus),
-            OpCode::NopImpl => self.instr_nop_impl(),
+            OpCode::NopImpl => self.instr_nop_impl(bus),
             OpCode::OraAbs => self.instr_ora_abs(bus),
             OpCode::OraAbsX => self.instr_ora_absx(bus),
             OpCode::OraAbsY => self.instr_ora_absy(bus),
@@ -188,12 +196,12 @@ impl Cpu {
             OpCode::PhpImpl => self.instr_php_impl(bus),
             OpCode::PlaImpl => self.instr_pla_impl(bus),
             OpCode::PlpImpl => self.instr_plp_impl(bus),
-            OpCode::RolA => self.instr_rol_a(),
+            OpCode::RolA => self.instr_rol_a(bus),
             OpCode::RolAbs => self.instr_rol_abs(bus),
             OpCode::RolAbsX => self.instr_rol_absx(bus),
             OpCode::RolZpg => self.instr_rol_zpg(bus),
             OpCode::RolZpgX => self.instr_rol_zpgx(bus),
-            OpCode::RorA => self.instr_ror_a(),
+            OpCode::RorA => self.instr_ror_a(bus),
             OpCode::RorAbs => self.instr_ror_abs(bus),
             OpCode::RorAbsX => self.instr_ror_absx(bus),
             OpCode::RorZpg => self.instr_ror_zpg(bus),
@@ -208,9 +216,9 @@ impl Cpu {
             OpCode::SbcXInd => self.instr_sbc_xind(bus),
             OpCode::SbcZpg => self.instr_sbc_zpg(bus),
             OpCode::SbcZpgX => self.instr_sbc_zpgx(bus),
-            OpCode::SecImpl => self.instr_sec(),
-            OpCode::SedImpl => self.instr_sed(),
-            OpCode::SeiImpl => self.instr_sei(),
+            OpCode::SecImpl => self.instr_sec(bus),
+            OpCode::SedImpl => self.instr_sed(bus),
+            OpCode::SeiImpl => self.instr_sei(bus),
             OpCode::StaAbs => self.instr_sta_abs(bus),
             OpCode::StaAbsX => self.instr_sta_absx(bus),
             OpCode::StaAbsY => self.instr_sta_absy(bus),
@@ -224,16 +232,16 @@ impl Cpu {
             OpCode::StyAbs => self.instr_sty_abs(bus),
             OpCode::StyZpg => self.instr_sty_zpg(bus),
             OpCode::StyZpgX => self.instr_sty_zpgx(bus),
-            OpCode::TaxImpl => self.instr_tax_impl(),
-            OpCode::TayImpl => self.instr_tay_impl(),
-            OpCode::TsxImpl => self.instr_tsx_impl(),
-            OpCode::TxaImpl => self.instr_txa_impl(),
-            OpCode::TxsImpl => self.instr_txs_impl(),
-            OpCode::TyaImpl => self.instr_tya_impl(),
+            OpCode::TaxImpl => self.instr_tax_impl(bus),
+            OpCode::TayImpl => self.instr_tay_impl(bus),
+            OpCode::TsxImpl => self.instr_tsx_impl(bus),
+            OpCode::TxaImpl => self.instr_txa_impl(bus),
+            OpCode::TxsImpl => self.instr_txs_impl(bus),
+            OpCode::TyaImpl => self.instr_tya_impl(bus),
         }
     }
 
-    fn instr_nop_impl(&self) -> usize {
-        2
+    fn instr_nop_impl(&mut self, bus: &mut impl CpuBus) {
+        self.dummy_read_pc(bus);
     }
 }
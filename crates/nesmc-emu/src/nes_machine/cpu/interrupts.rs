@@ -0,0 +1,361 @@
+use super::{Cpu, CpuBus};
+use crate::NesMachineError;
+use crate::nes_machine::save_state::{Snapshot, StateReader, StateWriter};
+
+const NMI_VECTOR_ADDR: u16 = 0xfffa;
+const IRQ_VECTOR_ADDR: u16 = 0xfffe;
+
+/// B flag. Only exists on the stack, set when pushed by BRK or PHP.
+pub(super) const BRK_FLAG: u8 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    Nmi,
+    Irq,
+}
+
+/// Interrupt inputs and sequencing. The lines are sampled at the end of every cycle, and what
+/// they were at the end of an instruction's second last cycle decides if an interrupt runs next.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Interrupts {
+    /// NMI line level on the last cycle, for edge detection
+    nmi_line: bool,
+    /// NMI edge detected, but not yet serviced
+    nmi_pending: bool,
+    /// [Self::nmi_pending] one cycle ago
+    prev_nmi_pending: bool,
+    /// IRQ line active and not masked by I on the last cycle
+    irq_active: bool,
+    /// [Self::irq_active] one cycle ago
+    prev_irq_active: bool,
+    /// Interrupt to run instead of the next instruction, decided when polled
+    pending: Option<Interrupt>,
+}
+
+impl Cpu {
+    /// Called at the end of every CPU cycle. The NMI input is edge-triggered, so it's latched
+    /// here until serviced. IRQ is level-triggered and masked by I.
+    pub(super) fn end_cycle(&mut self, bus: &impl CpuBus) {
+        let interrupts = &mut self.interrupts;
+
+        interrupts.prev_nmi_pending = interrupts.nmi_pending;
+        let nmi_line = bus.nmi_line();
+        if nmi_line && !interrupts.nmi_line {
+            interrupts.nmi_pending = true;
+        }
+        interrupts.nmi_line = nmi_line;
+
+        interrupts.prev_irq_active = interrupts.irq_active;
+        interrupts.irq_active = bus.irq_line() && !self.status.i;
+    }
+
+    /// Called at the end of an instruction. Decides whether an interrupt runs instead of the
+    /// next one. CLI, SEI and PLP change I on their last cycle, so their effect is delayed by
+    /// an instruction.
+    pub(super) fn poll_interrupts(&mut self) {
+        self.interrupts.pending = if self.interrupts.prev_nmi_pending {
+            Some(Interrupt::Nmi)
+        } else if self.interrupts.prev_irq_active {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        };
+    }
+
+    /// Taken branches that don't cross a page skip polling on their last cycle. An IRQ that
+    /// shows up on the cycle before waits until after the next instruction.
+    pub(super) fn delay_new_irq(&mut self) {
+        if self.interrupts.irq_active && !self.interrupts.prev_irq_active {
+            self.interrupts.irq_active = false;
+        }
+    }
+
+    /// Run the pending interrupt sequence, if one was polled. Returns false if there was none.
+    pub(super) fn service_interrupt(&mut self, bus: &mut impl CpuBus) -> bool {
+        if self.interrupts.pending.take().is_none() {
+            return false;
+        }
+        self.dummy_read_pc(bus);
+        self.dummy_read_pc(bus);
+        self.interrupt_sequence(bus, false);
+        true
+    }
+
+    /// Push PC and status, and jump through the interrupt vector. Shared by BRK and hardware
+    /// interrupts. The vector is picked after PC is pushed: an NMI detected by then takes over
+    /// the sequence, even if it was started by BRK or IRQ.
+    pub(super) fn interrupt_sequence(&mut self, bus: &mut impl CpuBus, brk: bool) {
+        self.push_stack((self.pc >> 8) as u8, bus);
+        self.push_stack((self.pc & 0xff) as u8, bus);
+
+        let vector_addr = if std::mem::take(&mut self.interrupts.nmi_pending) {
+            NMI_VECTOR_ADDR
+        } else {
+            IRQ_VECTOR_ADDR
+        };
+        let status = u8::from(self.status) | if brk { BRK_FLAG } else { 0 };
+        self.push_stack(status, bus);
+        self.status.i = true;
+
+        let lo = self.read(bus, vector_addr) as u16;
+        let hi = self.read(bus, vector_addr + 1) as u16;
+        self.pc = (hi << 8) | lo;
+
+        // The handler's first instruction always runs, even if NMI came on the last cycles.
+        self.interrupts.prev_nmi_pending = false;
+    }
+
+    /// Interrupt to run instead of the next instruction
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        self.interrupts.pending
+    }
+}
+
+impl Snapshot for Interrupts {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.bool(self.nmi_line);
+        w.bool(self.nmi_pending);
+        w.bool(self.prev_nmi_pending);
+        w.bool(self.irq_active);
+        w.bool(self.prev_irq_active);
+        w.u8(match self.pending {
+            None => 0,
+            Some(Interrupt::Nmi) => 1,
+            Some(Interrupt::Irq) => 2,
+        });
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.nmi_line = r.bool()?;
+        self.nmi_pending = r.bool()?;
+        self.prev_nmi_pending = r.bool()?;
+        self.irq_active = r.bool()?;
+        self.prev_irq_active = r.bool()?;
+        self.pending = match r.u8()? {
+            0 => None,
+            1 => Some(Interrupt::Nmi),
+            2 => Some(Interrupt::Irq),
+            _ => return Err(NesMachineError::SaveStateInvalid),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::NesMachine;
+    use crate::nes_machine::cpu::{Cpu, CpuBus};
+
+    const NMI_HANDLER: u16 = 0x8100;
+    const IRQ_HANDLER: u16 = 0x8200;
+
+    /// NROM machine running `code` from $8000. Both handlers are `JMP self`.
+    fn machine_with_code(code: &[u8]) -> NesMachine {
+        let mut prg = vec![0xea; 0x4000];
+        prg[..code.len()].copy_from_slice(code);
+        prg[0x100..0x103].copy_from_slice(&[0x4c, 0x00, 0x81]);
+        prg[0x200..0x203].copy_from_slice(&[0x4c, 0x00, 0x82]);
+        prg[0x3ffa..].copy_from_slice(&[0x00, 0x81, 0x00, 0x80, 0x00, 0x82]);
+
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(prg);
+        rom.extend(vec![0; 0x2000]);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        machine
+    }
+
+    /// Return address and status pushed by the last interrupt
+    fn pushed(machine: &NesMachine) -> (u16, u8) {
+        let sp = machine.cpu.sp as u16;
+        let status = machine.bus.read_immutable(0x0101 + sp);
+        let lo = machine.bus.read_immutable(0x0102 + sp) as u16;
+        let hi = machine.bus.read_immutable(0x0103 + sp) as u16;
+        ((hi << 8) | lo, status)
+    }
+
+    fn run_until_pc(machine: &mut NesMachine, pc: u16, max_instructions: usize) -> bool {
+        for _ in 0..max_instructions {
+            if machine.cpu.pc == pc {
+                return true;
+            }
+            machine.step_instruction();
+        }
+        machine.cpu.pc == pc
+    }
+
+    /// Spin in a delay loop with I set until the APU frame IRQ is asserted, then run `tail`.
+    fn frame_irq_code(tail: &[u8]) -> Vec<u8> {
+        #[rustfmt::skip]
+        let mut code = vec![
+            0xa9, 0x00, 0x8d, 0x17, 0x40, // LDA #0; STA $4017. 4-step mode with IRQ.
+            0xa2, 0x00, 0xa0, 0x20,       // LDX #0; LDY #$20
+            0xca, 0xd0, 0xfd,             // DEX; BNE -3
+            0x88, 0xd0, 0xfa,             // DEY; BNE -6
+        ];
+        code.extend(tail);
+        code
+    }
+
+    #[test]
+    fn test_irq_masked_by_i() {
+        // JMP self
+        let mut machine = machine_with_code(&frame_irq_code(&[0x4c, 0x0f, 0x80]));
+        assert!(run_until_pc(&mut machine, 0x800f, 100_000));
+        assert!(machine.bus.irq());
+        assert!(!run_until_pc(&mut machine, IRQ_HANDLER, 1000));
+    }
+
+    #[test]
+    fn test_irq() {
+        // CLI; JMP self
+        let mut machine = machine_with_code(&frame_irq_code(&[0x58, 0x4c, 0x10, 0x80]));
+        assert!(run_until_pc(&mut machine, IRQ_HANDLER, 100_000));
+
+        // B clear, bit 5 set
+        let (ret_addr, status) = pushed(&machine);
+        assert_eq!(ret_addr, 0x8010);
+        assert_eq!(status & 0x30, 0x20);
+        assert_eq!(status & 0x04, 0);
+        assert!(machine.cpu.status.i);
+    }
+
+    #[test]
+    fn test_cli_sei_delay() {
+        // CLI; SEI; NOP. The IRQ is polled before SEI takes effect and runs after it.
+        let mut machine = machine_with_code(&frame_irq_code(&[0x58, 0x78, 0xea]));
+        assert!(run_until_pc(&mut machine, IRQ_HANDLER, 100_000));
+        let (ret_addr, status) = pushed(&machine);
+        assert_eq!(ret_addr, 0x8011);
+        assert_eq!(status & 0x04, 0x04);
+    }
+
+    #[test]
+    fn test_brk() {
+        // BRK; padding
+        let mut machine = machine_with_code(&[0x00, 0xff]);
+        machine.step_instruction();
+        assert_eq!(machine.cpu.pc, IRQ_HANDLER);
+        let (ret_addr, status) = pushed(&machine);
+        assert_eq!(ret_addr, 0x8002);
+        assert_eq!(status & 0x30, 0x30);
+    }
+
+    #[test]
+    fn test_nmi_edge() {
+        let mut machine = machine_with_code(&[0x4c, 0x00, 0x80]); // JMP self
+        while machine.ppu.scanline() != 245 {
+            machine.step_instruction();
+        }
+
+        // Enabling NMI during vblank makes an edge.
+        machine.bus.write(0x2000, 0x80);
+        let sp = machine.cpu.sp;
+        assert!(run_until_pc(&mut machine, NMI_HANDLER, 10));
+        let (ret_addr, status) = pushed(&machine);
+        assert_eq!(ret_addr, 0x8000);
+        assert_eq!(status & 0x30, 0x20);
+
+        // Line stays active for the rest of vblank, but the NMI only runs once.
+        while machine.ppu.scanline() != 0 {
+            machine.step_instruction();
+        }
+        assert_eq!(machine.cpu.sp, sp.wrapping_sub(3));
+
+        // Next vblank
+        while machine.ppu.scanline() != 242 {
+            machine.step_instruction();
+        }
+        assert_eq!(machine.cpu.sp, sp.wrapping_sub(6));
+    }
+
+    /// RAM with a BRK at $8000 and an NMI line that goes high at the end of a given cycle
+    struct NmiBus {
+        ram: Vec<u8>,
+        cycles: usize,
+        nmi_cycle: usize,
+    }
+
+    impl CpuBus for NmiBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.ram[addr as usize] = value;
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        fn tick(&mut self) {
+            self.cycles += 1;
+        }
+
+        fn nmi_line(&self) -> bool {
+            self.cycles >= self.nmi_cycle
+        }
+    }
+
+    /// Run a BRK with NMI raised on its `nmi_cycle`th cycle. The NROM test machine can't
+    /// raise NMI mid-instruction, so these run on their own bus.
+    fn brk_with_nmi_on(nmi_cycle: usize) -> (Cpu, NmiBus) {
+        let mut ram = vec![0xea; 0x10000];
+        ram[0x8000..0x8002].copy_from_slice(&[0x00, 0xff]);
+        ram[0xfffa..].copy_from_slice(&[0x00, 0x81, 0x00, 0x80, 0x00, 0x82]);
+        let mut bus = NmiBus {
+            ram,
+            cycles: 0,
+            nmi_cycle,
+        };
+        let mut cpu = Cpu::new(&bus);
+        cpu.step(&mut bus);
+        assert_eq!(bus.cycles, 7);
+        (cpu, bus)
+    }
+
+    /// Return address and status pushed by the last interrupt
+    fn pushed_on(cpu: &Cpu, bus: &NmiBus) -> (u16, u8) {
+        let sp = cpu.sp as u16;
+        let status = bus.peek(0x0101 + sp);
+        let lo = bus.peek(0x0102 + sp) as u16;
+        let hi = bus.peek(0x0103 + sp) as u16;
+        ((hi << 8) | lo, status)
+    }
+
+    #[test]
+    fn test_brk_hijack() {
+        // Detected by the end of cycle 4, just before the vector is picked
+        for nmi_cycle in 1..=4 {
+            let (mut cpu, mut bus) = brk_with_nmi_on(nmi_cycle);
+            assert_eq!(cpu.pc, NMI_HANDLER);
+
+            // Still a BRK on the stack
+            let (ret_addr, status) = pushed_on(&cpu, &bus);
+            assert_eq!(ret_addr, 0x8002);
+            assert_eq!(status & 0x30, 0x30);
+
+            // The NMI was used up.
+            let sp = cpu.sp;
+            cpu.step(&mut bus);
+            cpu.step(&mut bus);
+            assert_eq!(cpu.sp, sp);
+        }
+    }
+
+    #[test]
+    fn test_brk_late_nmi() {
+        // Too late to hijack. The handler's first instruction runs before the NMI.
+        for nmi_cycle in 5..=7 {
+            let (mut cpu, mut bus) = brk_with_nmi_on(nmi_cycle);
+            assert_eq!(cpu.pc, IRQ_HANDLER);
+            cpu.step(&mut bus);
+            assert_eq!(cpu.pc, IRQ_HANDLER + 1);
+            cpu.step(&mut bus);
+            assert_eq!(cpu.pc, NMI_HANDLER);
+        }
+    }
+}
@@ -1,14 +1,19 @@
+mod cpu_bus;
 mod flags;
 mod instructions;
+mod interrupts;
 mod operand;
 mod status;
 
+pub use cpu_bus::CpuBus;
+pub use interrupts::Interrupts;
 use nesmc_types::instruction::OpCode;
 pub use status::CpuStatus;
 
-use super::bus::Bus;
+use super::save_state::{Snapshot, StateReader, StateWriter};
+use crate::NesMachineError;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cpu {
     pub a: u8,
     pub x: u8,
@@ -16,6 +21,9 @@ pub struct Cpu {
     pub pc: u16,
     pub sp: u8,
     pub status: CpuStatus,
+    pub(crate) interrupts: Interrupts,
+    /// Stopped by a JAM opcode. Only reset gets it running again.
+    pub(crate) halted: bool,
 }
 
 impl std::fmt::Display for Cpu {
@@ -31,55 +39,83 @@ impl std::fmt::Display for Cpu {
 impl Cpu {
     const INIT_VECTOR: u16 = 0xfffc;
 
-    pub fn new(bus: &mut Bus) -> Self {
+    pub fn new(bus: &impl CpuBus) -> Self {
         Self {
             a: 0,
             x: 0,
             y: 0,
-            pc: read_u16(bus, Self::INIT_VECTOR),
+            pc: peek_u16(bus, Self::INIT_VECTOR),
             sp: 0xfd,
             status: CpuStatus::default(),
+            interrupts: Interrupts::default(),
+            halted: false,
         }
     }
 
     /// Reset button behavior
-    pub fn reset(&mut self, bus: &mut Bus) {
-        self.pc = read_u16(bus, Self::INIT_VECTOR);
+    pub fn reset(&mut self, bus: &impl CpuBus) {
+        self.pc = peek_u16(bus, Self::INIT_VECTOR);
         self.sp = self.sp.wrapping_sub(3);
         self.status.reset();
+        self.interrupts = Interrupts::default();
+        self.halted = false;
     }
 
-    /// Step one CPU instruction
-    /// Returns the number of CPU cycles spent.
-    pub fn step(&mut self, bus: &mut Bus) -> usize {
-        // vblank interrupt
-        /*let ppu_status = bus.read(0x2002);
-        if ppu_status & 0b1000_0000 != 0 {
-            bus.write(0x2002, ppu_status & 0b0111_1111);
-            self.nmi(bus);
+    /// Stopped by a JAM opcode until reset
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Step one CPU instruction, or the interrupt sequence polled during the last one.
+    /// Every cycle spent is one [CpuBus::tick] and one access on `bus`.
+    pub fn step(&mut self, bus: &mut impl CpuBus) {
+        // Nothing is fetched and interrupts are ignored, but time goes on.
+        if self.halted {
+            bus.tick();
             return;
-        }*/
-        // Fetch instruction
-        let op_code = OpCode::from(bus.read(self.pc));
-        self.inc_pc();
-        self.exec_instruction(bus, op_code)
+        }
+        if self.service_interrupt(bus) {
+            return;
+        }
+        let op_code = OpCode::from(self.fetch(bus));
+        self.exec_instruction(bus, op_code);
+        self.poll_interrupts();
     }
 
-    pub fn nmi(&mut self, bus: &mut Bus) {
-        let pc_lo = (self.pc & 0x00ff) as u8;
-        let pc_hi = (self.pc >> 8) as u8;
-        self.push_stack(pc_lo, bus);
-        self.push_stack(pc_hi, bus);
-        self.push_stack(self.status.into(), bus);
+    /// One CPU cycle reading `addr`
+    fn read(&mut self, bus: &mut impl CpuBus, addr: u16) -> u8 {
+        bus.tick();
+        let value = bus.read(addr);
+        self.end_cycle(bus);
+        value
+    }
 
-        self.status.i = true;
+    /// One CPU cycle writing `value` to `addr`
+    fn write(&mut self, bus: &mut impl CpuBus, addr: u16, value: u8) {
+        bus.tick();
+        bus.write(addr, value);
+        self.end_cycle(bus);
+    }
 
-        const NMI_VECTOR_ADDR: u16 = 0xfffa;
+    /// First two cycles of a read-modify-write instruction: the value is read, and written back
+    /// unmodified while the CPU works on it.
+    fn read_modify(&mut self, bus: &mut impl CpuBus, addr: u16) -> u8 {
+        let value = self.read(bus, addr);
+        self.write(bus, addr, value);
+        value
+    }
 
-        let pc_lo = bus.read(NMI_VECTOR_ADDR) as u16;
-        let pc_hi = bus.read(NMI_VECTOR_ADDR + 1) as u16;
+    /// Read the byte at PC and move past it
+    fn fetch(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let value = self.read(bus, self.pc);
+        self.inc_pc();
+        value
+    }
 
-        self.pc = pc_lo + (pc_hi << 8);
+    /// Read the byte at PC and throw it away. Single byte instructions spend their second cycle
+    /// on this.
+    fn dummy_read_pc(&mut self, bus: &mut impl CpuBus) {
+        self.read(bus, self.pc);
     }
 
     /// Increment PC convenience shortcut
@@ -87,19 +123,55 @@ impl Cpu {
         self.pc = self.pc.wrapping_add(1);
     }
 
-    fn push_stack(&mut self, value: u8, bus: &mut Bus) {
-        bus.write(0x0100 + self.sp as u16, value);
+    fn stack_addr(&self) -> u16 {
+        0x0100 + self.sp as u16
+    }
+
+    fn push_stack(&mut self, value: u8, bus: &mut impl CpuBus) {
+        self.write(bus, self.stack_addr(), value);
         self.sp = self.sp.wrapping_sub(1);
     }
 
-    fn pop_stack(&mut self, bus: &mut Bus) -> u8 {
+    fn pop_stack(&mut self, bus: &mut impl CpuBus) -> u8 {
         self.sp = self.sp.wrapping_add(1);
-        bus.read(0x0100 + self.sp as u16)
+        self.read(bus, self.stack_addr())
+    }
+
+    /// Read the top of the stack without pulling. Pulls spend a cycle on this before
+    /// incrementing SP.
+    fn dummy_read_stack(&mut self, bus: &mut impl CpuBus) {
+        self.read(bus, self.stack_addr());
     }
 }
 
-fn read_u16(bus: &mut Bus, addr: u16) -> u16 {
-    let lo_byte = bus.read(addr) as u16;
-    let hi_byte = bus.read(addr.wrapping_add(1)) as u16;
+/// Read a vector without spending cycles
+fn peek_u16(bus: &impl CpuBus, addr: u16) -> u16 {
+    let lo_byte = bus.peek(addr) as u16;
+    let hi_byte = bus.peek(addr.wrapping_add(1)) as u16;
     (hi_byte << 8) | lo_byte
 }
+
+impl Snapshot for Cpu {
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.u8(self.a);
+        w.u8(self.x);
+        w.u8(self.y);
+        w.u16(self.pc);
+        w.u8(self.sp);
+        w.u8(self.status.into());
+        self.interrupts.snapshot(w);
+        w.bool(self.halted);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.a = r.u8()?;
+        self.x = r.u8()?;
+        self.y = r.u8()?;
+        self.pc = r.u16()?;
+        self.sp = r.u8()?;
+        self.status = CpuStatus::from(r.u8()?);
+        self.interrupts.restore(r)?;
+        self.halted = r.bool()?;
+        Ok(())
+    }
+}
@@ -1,141 +1,150 @@
-use crate::bus::Bus;
+//! Addressing modes, one bus access per cycle
+//!
+//! `fetch_operand_*` is for instructions that only read their operand. Indexed modes skip the
+//! fix-up cycle when no page is crossed. `fetch_address_*` is for writes and read-modify-writes,
+//! which always spend it.
 
-use super::Cpu;
+use super::{Cpu, CpuBus};
 
 impl Cpu {
-    pub(super) fn fetch_operand_imm(&mut self, bus: &mut Bus) -> u8 {
-        let operand = bus.read(self.pc);
-        self.inc_pc();
-        operand
+    pub(super) fn fetch_operand_imm(&mut self, bus: &mut impl CpuBus) -> u8 {
+        self.fetch(bus)
     }
 
-    pub(super) fn fetch_operand_abs(&mut self, bus: &mut Bus) -> u8 {
-        let address = read_u16(bus, self.pc);
-        self.inc_pc();
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_abs(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let address = self.fetch_address_abs(bus);
+        self.read(bus, address)
     }
 
-    pub(super) fn fetch_operand_absx(&mut self, bus: &mut Bus) -> u8 {
-        let address = read_u16(bus, self.pc).wrapping_add(self.x as u16);
-        self.inc_pc();
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_absx(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let base = self.fetch_address_abs(bus);
+        self.read_indexed(bus, base, self.x)
     }
 
-    pub(super) fn fetch_operand_absy(&mut self, bus: &mut Bus) -> u8 {
-        let address = read_u16(bus, self.pc).wrapping_add(self.y as u16);
-        self.inc_pc();
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_absy(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let base = self.fetch_address_abs(bus);
+        self.read_indexed(bus, base, self.y)
     }
 
-    pub(super) fn fetch_operand_xind(&mut self, bus: &mut Bus) -> u8 {
+    pub(super) fn fetch_operand_xind(&mut self, bus: &mut impl CpuBus) -> u8 {
         let address = self.fetch_address_xind(bus);
-        bus.read(address)
+        self.read(bus, address)
     }
 
-    pub(super) fn fetch_operand_indy(&mut self, bus: &mut Bus) -> u8 {
-        let address = self.fetch_address_indy(bus);
-        bus.read(address)
+    pub(super) fn fetch_operand_indy(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let base = self.fetch_pointer_zpg(bus);
+        self.read_indexed(bus, base, self.y)
     }
 
-    pub(super) fn fetch_operand_rel(&mut self, bus: &mut Bus) -> u8 {
-        let operand = bus.read(self.pc);
-        self.inc_pc();
-        operand
+    pub(super) fn fetch_operand_rel(&mut self, bus: &mut impl CpuBus) -> u8 {
+        self.fetch(bus)
     }
 
-    pub(super) fn fetch_operand_zpg(&mut self, bus: &mut Bus) -> u8 {
-        let address = bus.read(self.pc) as u16;
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_zpg(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let address = self.fetch_address_zpg(bus);
+        self.read(bus, address)
     }
 
-    pub(super) fn fetch_operand_zpgx(&mut self, bus: &mut Bus) -> u8 {
-        let address = bus.read(self.pc).wrapping_add(self.x) as u16;
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_zpgx(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let address = self.fetch_address_zpgx(bus);
+        self.read(bus, address)
     }
 
-    pub(super) fn fetch_operand_zpgy(&mut self, bus: &mut Bus) -> u8 {
-        let address = bus.read(self.pc).wrapping_add(self.y) as u16;
-        self.inc_pc();
-        bus.read(address)
+    pub(super) fn fetch_operand_zpgy(&mut self, bus: &mut impl CpuBus) -> u8 {
+        let address = self.fetch_address_zpgy(bus);
+        self.read(bus, address)
     }
 
-    pub(crate) fn fetch_address_abs(&mut self, bus: &mut Bus) -> u16 {
-        let address = read_u16(bus, self.pc);
-        self.inc_pc();
-        self.inc_pc();
-        address
+    pub(crate) fn fetch_address_abs(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let lo = self.fetch(bus) as u16;
+        let hi = self.fetch(bus) as u16;
+        (hi << 8) | lo
     }
 
-    pub(crate) fn fetch_address_absx(&mut self, bus: &mut Bus) -> u16 {
-        let address = read_u16(bus, self.pc).wrapping_add(self.x as u16);
-        self.inc_pc();
-        self.inc_pc();
-        address
+    pub(crate) fn fetch_address_absx(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let base = self.fetch_address_abs(bus);
+        self.index_address(bus, base, self.x)
     }
 
-    pub(crate) fn fetch_address_absy(&mut self, bus: &mut Bus) -> u16 {
-        let address = read_u16(bus, self.pc).wrapping_add(self.y as u16);
-        self.inc_pc();
-        self.inc_pc();
-        address
+    pub(crate) fn fetch_address_absy(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let base = self.fetch_address_abs(bus);
+        self.index_address(bus, base, self.y)
     }
 
-    pub(crate) fn fetch_address_ind(&mut self, bus: &mut Bus) -> u16 {
-        let address_ptr = read_u16(bus, self.pc);
-        self.inc_pc();
-        self.inc_pc();
-        let lo = bus.read(address_ptr) as u16;
+    pub(crate) fn fetch_address_ind(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let address_ptr = self.fetch_address_abs(bus);
+        let lo = self.read(bus, address_ptr) as u16;
         // cpu wrapping bug
         let hi_ptr_hi = address_ptr & 0xff00;
         let hi_ptr_lo = address_ptr.wrapping_add(1) & 0x00ff;
-        let hi = (bus.read(hi_ptr_lo + hi_ptr_hi) as u16) << 8;
+        let hi = (self.read(bus, hi_ptr_lo + hi_ptr_hi) as u16) << 8;
         lo + hi
     }
 
-    pub(crate) fn fetch_address_xind(&mut self, bus: &mut Bus) -> u16 {
-        let operand = bus.read(self.pc);
-        self.inc_pc();
+    pub(crate) fn fetch_address_xind(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let operand = self.fetch(bus);
+        // Pointer is read before X is added.
+        self.read(bus, operand as u16);
         let address_ptr = operand.wrapping_add(self.x);
-        let lo = bus.read(address_ptr as u16) as u16;
-        let hi = (bus.read(address_ptr.wrapping_add(1) as u16) as u16) << 8;
+        let lo = self.read(bus, address_ptr as u16) as u16;
+        let hi = (self.read(bus, address_ptr.wrapping_add(1) as u16) as u16) << 8;
         lo + hi
     }
 
-    pub(crate) fn fetch_address_indy(&mut self, bus: &mut Bus) -> u16 {
-        let address_ptr = bus.read(self.pc);
-        self.inc_pc();
-        let lo = bus.read(address_ptr as u16) as u16;
-        let hi = (bus.read(address_ptr.wrapping_add(1) as u16) as u16) << 8;
-        (lo + hi).wrapping_add(self.y as u16)
+    pub(crate) fn fetch_address_indy(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let base = self.fetch_pointer_zpg(bus);
+        self.index_address(bus, base, self.y)
     }
 
-    pub(crate) fn fetch_address_zpg(&mut self, bus: &mut Bus) -> u16 {
-        let address = bus.read(self.pc) as u16;
-        self.inc_pc();
-        address
+    pub(crate) fn fetch_address_zpg(&mut self, bus: &mut impl CpuBus) -> u16 {
+        self.fetch(bus) as u16
     }
 
-    pub(crate) fn fetch_address_zpgx(&mut self, bus: &mut Bus) -> u16 {
-        let address = bus.read(self.pc).wrapping_add(self.x) as u16;
-        self.inc_pc();
-        address
+    pub(crate) fn fetch_address_zpgx(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let operand = self.fetch(bus);
+        // Address is read before X is added.
+        self.read(bus, operand as u16);
+        operand.wrapping_add(self.x) as u16
+    }
+
+    pub(crate) fn fetch_address_zpgy(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let operand = self.fetch(bus);
+        // Address is read before Y is added.
+        self.read(bus, operand as u16);
+        operand.wrapping_add(self.y) as u16
+    }
+
+    /// Operand byte, and the address it points to in zero page. Wraps within zero page.
+    pub(super) fn fetch_pointer_zpg(&mut self, bus: &mut impl CpuBus) -> u16 {
+        let address_ptr = self.fetch(bus);
+        let lo = self.read(bus, address_ptr as u16) as u16;
+        let hi = (self.read(bus, address_ptr.wrapping_add(1) as u16) as u16) << 8;
+        lo + hi
     }
 
-    pub(crate) fn fetch_address_zpgy(&mut self, bus: &mut Bus) -> u16 {
-        let address = bus.read(self.pc).wrapping_add(self.y) as u16;
-        println!("{:x}", address);
-        self.inc_pc();
+    /// Read `base + index`. The CPU reads from the wrong page first and only spends a cycle
+    /// on the right one if a page was crossed.
+    fn read_indexed(&mut self, bus: &mut impl CpuBus, base: u16, index: u8) -> u8 {
+        let address = base.wrapping_add(index as u16);
+        let unfixed = unfixed_address(base, address);
+        let value = self.read(bus, unfixed);
+        if unfixed == address {
+            value
+        } else {
+            self.read(bus, address)
+        }
+    }
+
+    /// `base + index` for writes. The read from the wrong page happens whether a page was
+    /// crossed or not.
+    fn index_address(&mut self, bus: &mut impl CpuBus, base: u16, index: u8) -> u16 {
+        let address = base.wrapping_add(index as u16);
+        self.read(bus, unfixed_address(base, address));
         address
     }
 }
 
-fn read_u16(bus: &mut Bus, addr: u16) -> u16 {
-    let lo_byte = bus.read(addr) as u16;
-    let hi_byte = bus.read(addr.wrapping_add(1)) as u16;
-    (hi_byte << 8) | lo_byte
+/// Address with the carry into the high byte not yet added
+pub(super) fn unfixed_address(base: u16, address: u16) -> u16 {
+    (base & 0xff00) | (address & 0x00ff)
 }
@@ -6,6 +6,12 @@ pub enum NesMachineError {
     MapperUnsupportedFeatures,
     MapperUnexpectedChrRomLen(usize),
     MapperUnexpectedPrgRomLen(usize),
+    PaletteUnexpectedLen(usize),
+    SaveStateInvalid,
+    SaveStateUnsupportedVersion(u32),
+    SaveStateMismatch,
+    SaveRamUnexpectedLen(usize),
+    SaveRamStorage,
 }
 
 impl std::fmt::Display for NesMachineError {
@@ -18,11 +24,25 @@ impl std::fmt::Display for NesMachineError {
                 write!(f, "Mapper has unsupported features")
             }
             NesMachineError::MapperUnexpectedChrRomLen(len) => {
-                write!(f, "Unexpected CHR ROM length: {len:x}")
+                write!(f, "Unexpected CHR ROM length: {len} bytes")
             }
             NesMachineError::MapperUnexpectedPrgRomLen(len) => {
-                write!(f, "Unexpected PRG ROM length: {len:x}")
+                write!(f, "Unexpected PRG ROM length: {len} bytes")
             }
+            NesMachineError::PaletteUnexpectedLen(len) => {
+                write!(f, "Unexpected palette file length: {len} bytes")
+            }
+            NesMachineError::SaveStateInvalid => write!(f, "Invalid save state"),
+            NesMachineError::SaveStateUnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version: {version}")
+            }
+            NesMachineError::SaveStateMismatch => {
+                write!(f, "Save state doesn't match the loaded ROM")
+            }
+            NesMachineError::SaveRamUnexpectedLen(len) => {
+                write!(f, "Unexpected save RAM length: {len} bytes")
+            }
+            NesMachineError::SaveRamStorage => write!(f, "Couldn't access save RAM storage"),
         }
     }
 }
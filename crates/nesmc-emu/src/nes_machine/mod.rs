@@ -1,28 +1,47 @@
+mod audio;
 pub mod bus;
 mod cpu;
 mod error;
 mod ppu;
+mod save_ram;
+mod save_state;
+mod system_bus;
+pub mod test_rom;
 
-use std::{io::BufReader, path::Path};
+use std::{
+    io::{BufReader, Read},
+    path::Path,
+};
 
-use bus::{Bus, Mapper};
-use cpu::Cpu;
+pub use audio::{AudioOutput, CPU_CLOCK_HZ};
+use bus::{Bus, Buttons, INesHeader, Mapper};
+pub use cpu::{Cpu, CpuBus, CpuStatus};
 pub use error::NesMachineError;
+pub use ppu::Palette;
 use ppu::Ppu;
+use save_ram::SaveRamStore;
+use save_state::{Snapshot, StateReader, StateWriter};
+use system_bus::SystemBus;
 
 #[derive(Debug)]
 pub struct NesMachine {
     pub bus: Bus,
     pub cpu: Cpu,
     pub ppu: Ppu,
+    /// CPU cycles since power on
     pub cycle_count: usize,
-    pub ppu_cycles: usize,
+    /// None while audio output is off
+    pub audio: Option<AudioOutput>,
+    /// Header of the open ROM
+    header: Option<INesHeader>,
+    /// None unless the open ROM has battery-backed RAM and was opened with a path
+    save_ram_store: Option<SaveRamStore>,
 }
 
 impl Default for NesMachine {
     fn default() -> Self {
-        let mut bus = Bus::default();
-        let cpu = Cpu::new(&mut bus);
+        let bus = Bus::default();
+        let cpu = Cpu::new(&bus);
         let ppu = Ppu::default();
 
         Self {
@@ -30,51 +49,224 @@ impl Default for NesMachine {
             cpu,
             ppu,
             cycle_count: 7,
-            ppu_cycles: 0,
+            audio: None,
+            header: None,
+            save_ram_store: None,
         }
     }
 }
 
 impl NesMachine {
+    /// Open a ROM file. Battery-backed RAM is kept next to it, see [Self::open_data_at].
     pub fn open_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), NesMachineError> {
-        self.bus.cart = Mapper::None;
-        self.bus.cart = Mapper::open(path)?;
-        self.cpu = Cpu::new(&mut self.bus);
-        Ok(())
+        let data = std::fs::read(&path)?;
+        self.open_data_at(&data, path)
     }
 
+    /// Open a ROM without a file. Battery-backed RAM isn't kept.
+    ///
+    /// The old ROM's save RAM is flushed first. If that fails, the new ROM is opened anyway
+    /// and the flush error is returned after.
     pub fn open_data(&mut self, data: &[u8]) -> Result<(), NesMachineError> {
-        let mut reader = BufReader::new(data);
+        let flushed = self.close_save_ram();
+        self.open_reader(&mut BufReader::new(data))?;
+        flushed
+    }
+
+    /// Open ROM data that was read from `path`. Battery-backed RAM is loaded from, and
+    /// [flushed](Self::flush_save_ram) to a `.sav` file next to it, or browser local storage on
+    /// wasm. If the save can't be loaded, the ROM isn't opened either, and the save is left
+    /// alone. The old ROM is flushed like in [Self::open_data].
+    pub fn open_data_at<P: AsRef<Path>>(
+        &mut self,
+        data: &[u8],
+        path: P,
+    ) -> Result<(), NesMachineError> {
+        let flushed = self.close_save_ram();
+        self.open_reader(&mut BufReader::new(data))?;
+        if self.bus.cart.save_ram().is_none() {
+            return flushed;
+        }
+        let mut store = SaveRamStore::for_rom(path.as_ref());
+        let loaded = match store.load() {
+            Ok(Some(save_ram)) => self.bus.cart.load_save_ram(&save_ram),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = loaded {
+            // Running without it would quietly throw away every save made in game.
+            self.unload();
+            return Err(e);
+        }
+        self.save_ram_store = Some(store);
+        flushed
+    }
+
+    /// Write battery-backed RAM out if it has changed. The machine doesn't do this on its own,
+    /// call it every now and then and before exiting. Opening another ROM flushes the old one.
+    pub fn flush_save_ram(&mut self) -> Result<(), NesMachineError> {
+        let (Some(store), Some(save_ram)) = (&mut self.save_ram_store, self.bus.cart.save_ram())
+        else {
+            return Ok(());
+        };
+        store.store(save_ram)
+    }
+
+    /// Flush the open ROM's save RAM and stop keeping it
+    fn close_save_ram(&mut self) -> Result<(), NesMachineError> {
+        let flushed = self.flush_save_ram();
+        self.save_ram_store = None;
+        flushed
+    }
+
+    /// Take the cartridge out
+    fn unload(&mut self) {
         self.bus.cart = Mapper::None;
-        self.bus.cart = Mapper::from_reader(&mut reader)?;
-        self.cpu = Cpu::new(&mut self.bus);
+        self.header = None;
+        self.cpu = Cpu::new(&self.bus);
+    }
+
+    fn open_reader<R: Read>(&mut self, reader: &mut BufReader<R>) -> Result<(), NesMachineError> {
+        self.unload();
+        let header = INesHeader::read(reader)?;
+        self.bus.cart = Mapper::from_header(&header, reader)?;
+        self.header = Some(header);
+        self.cpu = Cpu::new(&self.bus);
         Ok(())
     }
 
+    /// Header of the open ROM, or None if there isn't one
+    pub fn header(&self) -> Option<&INesHeader> {
+        self.header.as_ref()
+    }
+
+    /// Whether the cart's mapper register writes have bus conflicts, or None if its mapper
+    /// doesn't model them. Set from the NES 2.0 submapper when the ROM is opened.
+    pub fn bus_conflicts(&self) -> Option<bool> {
+        self.bus.cart.bus_conflicts()
+    }
+
+    /// Override the bus conflicts of the open cart, for ROMs whose header doesn't say. Ignored
+    /// by mappers that don't model them.
+    pub fn set_bus_conflicts(&mut self, enabled: bool) {
+        self.bus.cart.set_bus_conflicts(enabled);
+    }
+
     /// Reset button behavior
     pub fn reset(&mut self) {
         self.bus.reset();
-        self.cpu.reset(&mut self.bus);
+        self.cpu.reset(&self.bus);
         self.ppu.reset();
     }
 
-    /// Step one PPU instruction
-    pub fn step(&mut self) {
-        self.ppu.step(&mut self.bus);
-        self.ppu_cycles += 1;
+    /// CPU has hit a JAM opcode and stopped until reset. PC points to the opcode.
+    pub fn cpu_halted(&self) -> bool {
+        self.cpu.halted()
+    }
 
-        if self.ppu_cycles == 3 {
-            if self.ppu.nmi_fired {
-                self.cpu.nmi(&mut self.bus);
-                self.ppu.nmi_fired = false;
-                self.cycle_count += 7;
-            } else {
-                let cycles = self.cpu.step(&mut self.bus);
-                self.cycle_count += cycles;
-            }
+    /// Set the buttons held on the controller in port 1 (0) or 2 (1). Other ports are ignored.
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        self.bus.input.set_buttons(port, buttons);
+    }
+
+    /// Serialize the machine state. ROM data isn't included, so the state can only be loaded
+    /// back with the same ROM open. Leaving out the framebuffer makes the state ~180 KB smaller,
+    /// but the picture stays stale until the next frame is drawn after loading.
+    pub fn save_state(&self, include_framebuffer: bool) -> Vec<u8> {
+        let mut w = StateWriter::default();
+        w.bytes(save_state::MAGIC);
+        w.u32(save_state::VERSION);
+
+        w.usize(self.cycle_count);
+        self.cpu.snapshot(&mut w);
+        self.ppu.snapshot(&mut w);
+        self.bus.snapshot(&mut w);
+
+        w.bool(include_framebuffer);
+        if include_framebuffer {
+            w.bytes(self.ppu.framebuffer());
+        }
+        w.into_bytes()
+    }
 
-            self.ppu_cycles = 0;
+    /// Restore a state made by [Self::save_state]. On error, the machine is left as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), NesMachineError> {
+        let mut r = StateReader::new(data);
+        let mut magic = [0; 4];
+        r.bytes(&mut magic)?;
+        if magic != *save_state::MAGIC {
+            return Err(NesMachineError::SaveStateInvalid);
         }
+        let version = r.u32()?;
+        if version != save_state::VERSION {
+            return Err(NesMachineError::SaveStateUnsupportedVersion(version));
+        }
+
+        // Restored into copies, and only swapped in once the whole state has been read.
+        let cycle_count = r.usize()?;
+        let mut cpu = self.cpu.clone();
+        cpu.restore(&mut r)?;
+        let mut ppu = self.ppu.clone();
+        ppu.restore(&mut r)?;
+        let mut bus = self.bus.clone();
+        bus.restore(&mut r)?;
+
+        if r.bool()? {
+            r.bytes(ppu.framebuffer_mut())?;
+        }
+        if !r.is_empty() {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+
+        self.cycle_count = cycle_count;
+        self.cpu = cpu;
+        self.ppu = ppu;
+        self.bus = bus;
+        Ok(())
+    }
+
+    /// Step one CPU instruction, or interrupt sequence. The rest of the machine runs along
+    /// cycle by cycle.
+    pub fn step_instruction(&mut self) {
+        let mut bus = SystemBus {
+            bus: &mut self.bus,
+            ppu: &mut self.ppu,
+            audio: &mut self.audio,
+            cycle_count: &mut self.cycle_count,
+        };
+        self.cpu.step(&mut bus);
+    }
+
+    /// Step until the start of the next frame. Stops after the instruction that crosses it.
+    pub fn step_frame(&mut self) {
+        loop {
+            let scanline = self.ppu.scanline();
+            self.step_instruction();
+            if self.ppu.scanline() < scanline {
+                break;
+            }
+        }
+    }
+
+    /// Produce audio at `sample_rate` Hz, or turn audio output off with None.
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.audio = sample_rate.map(AudioOutput::new);
+    }
+
+    /// Take the audio samples produced since the last call, in range -1.0..=1.0
+    pub fn take_audio_f32(&mut self) -> Vec<f32> {
+        self.audio
+            .as_mut()
+            .map(AudioOutput::take_f32)
+            .unwrap_or_default()
+    }
+
+    /// Take the audio samples produced since the last call as signed 16 bit PCM
+    pub fn take_audio_i16(&mut self) -> Vec<i16> {
+        self.audio
+            .as_mut()
+            .map(AudioOutput::take_i16)
+            .unwrap_or_default()
     }
 }
 
@@ -93,11 +285,13 @@ mod tests {
             pc: u16::from_str_radix(&line[0..=3], 16).unwrap(),
             sp: u8::from_str_radix(&line[71..=72], 16).unwrap(),
             status: CpuStatus::from(u8::from_str_radix(&line[65..=66], 16).unwrap()),
+            interrupts: Interrupts::default(),
+            halted: false,
         }
     }
 
     fn parse_cycles(line: &str) -> usize {
-        usize::from_str_radix(&line[90..], 10).unwrap()
+        line[90..].parse().unwrap()
     }
 
     fn run_against_log(
@@ -126,9 +320,7 @@ mod tests {
             }
 
             println!("ln {ln:02} ok - {this_cpu} CYC:{this_cyc}");
-            machine.step();
-            machine.step();
-            machine.step();
+            machine.step_instruction();
         }
     }
 
@@ -152,6 +344,391 @@ mod tests {
         run_against_log(&mut machine, log, true, true);
     }
 
+    /// Machine without a cart, running `code` from $0000.
+    fn machine_with_iram_code(code: &[u8]) -> NesMachine {
+        let mut machine = NesMachine::default();
+        for (i, byte) in code.iter().enumerate() {
+            machine.bus.write(i as u16, *byte);
+        }
+        machine.cpu.pc = 0x0000;
+        machine
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        // LDA #$02; STA $4014; NOP
+        let mut machine = machine_with_iram_code(&[0xa9, 0x02, 0x8d, 0x14, 0x40, 0xea]);
+        for i in 0..0x100 {
+            machine.bus.write(0x0200 + i, i as u8);
+        }
+        machine.bus.write(0x2003, 0x10);
+
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 9);
+
+        // STA ends on cycle 13, so the DMA starts on an odd cycle. It halts the CPU on the
+        // NOP's first cycle.
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 13);
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 13 + 514 + 2);
+
+        assert_eq!(machine.bus.ppu_regs.oam[0x10], 0x00);
+        assert_eq!(machine.bus.ppu_regs.oam[0xff], 0xef);
+        assert_eq!(machine.bus.ppu_regs.oam[0x00], 0xf0);
+        assert_eq!(machine.bus.ppu_regs.oam_addr, 0x10);
+    }
+
+    #[test]
+    fn test_open_nes2() {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0, 0x08, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x4000 + 0x2000]);
+
+        let mut machine = NesMachine::default();
+        assert!(machine.header().is_none());
+        machine.open_data(&rom).unwrap();
+        let header = machine.header().unwrap();
+        assert_eq!(header.format, bus::HeaderFormat::Nes2);
+        assert_eq!((header.mapper_id, header.submapper), (0, 1));
+
+        // Failed open leaves no header behind.
+        rom[7] = 0x48;
+        assert!(machine.open_data(&rom).is_err());
+        assert!(machine.header().is_none());
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // CNROM, NES 2.0 submapper 1
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0x30, 0x08, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x4000 + 0x2000]);
+
+        let mut machine = NesMachine::default();
+        assert_eq!(machine.bus_conflicts(), None);
+        machine.open_data(&rom).unwrap();
+        assert_eq!(machine.bus_conflicts(), Some(false));
+
+        // Submapper 2
+        rom[8] = 0x20;
+        machine.open_data(&rom).unwrap();
+        assert_eq!(machine.bus_conflicts(), Some(true));
+        machine.set_bus_conflicts(false);
+        assert_eq!(machine.bus_conflicts(), Some(false));
+
+        // iNES
+        rom[7] = 0;
+        rom[8] = 0;
+        machine.open_data(&rom).unwrap();
+        assert_eq!(machine.bus_conflicts(), Some(true));
+    }
+
+    #[test]
+    fn test_open_nes2_exponent_len() {
+        // PRG ROM 2^63 * 7 bytes, saturates
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([0xff, 0, 0x20, 0x08, 0, 0x0f, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x4000]);
+        let mut machine = NesMachine::default();
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::MapperUnexpectedPrgRomLen(usize::MAX))
+        ));
+
+        // 2^7 * 3 = 384 bytes, not a whole UxROM bank
+        rom[4] = 0x1d;
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::MapperUnexpectedPrgRomLen(384))
+        ));
+
+        // 2^22 = 4MB is valid, but the file is shorter.
+        rom[4] = 0x58;
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::FileIo)
+        ));
+    }
+
+    #[test]
+    fn test_open_trainer_and_battery() {
+        // NROM, battery and trainer
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0x06, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend((0..0x200).map(|i| i as u8));
+        rom.extend(vec![0; 0x4000 + 0x2000]);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        assert!(machine.bus.cart.battery());
+        assert_eq!(machine.bus.read_immutable(0x6fff), 0);
+        assert_eq!(machine.bus.read_immutable(0x7000), 0);
+        assert_eq!(machine.bus.read_immutable(0x7001), 1);
+        assert_eq!(machine.bus.read_immutable(0x71ff), 0xff);
+        assert_eq!(machine.bus.read_immutable(0x7200), 0);
+
+        // UxROM gets PRG-RAM to put it in.
+        rom[6] = 0x24;
+        machine.open_data(&rom).unwrap();
+        assert!(!machine.bus.cart.battery());
+        assert_eq!(machine.bus.read_immutable(0x7001), 1);
+        assert_eq!(machine.bus.read_immutable(0x71ff), 0xff);
+        machine.bus.write(0x6000, 0x42);
+        assert_eq!(machine.bus.read_immutable(0x6000), 0x42);
+
+        // Without a trainer it has none.
+        rom[6] = 0x20;
+        rom.drain(16..16 + 0x200);
+        machine.open_data(&rom).unwrap();
+        machine.bus.write(0x6000, 0x42);
+        assert_eq!(machine.bus.read_immutable(0x6000), 0);
+    }
+
+    #[test]
+    fn test_save_ram_persistence() {
+        let dir = std::env::temp_dir().join("nesmachine_test_save_ram_persistence");
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.nes");
+        let sav_path = dir.join("game.sav");
+        let _ = std::fs::remove_file(&sav_path);
+
+        // MMC1 with battery
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([2, 1, 0x12, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x8000 + 0x2000]);
+        std::fs::write(&rom_path, &rom).unwrap();
+
+        let mut machine = NesMachine::default();
+        machine.open_path(&rom_path).unwrap();
+        machine.bus.write(0x6000, 0x12);
+        machine.bus.write(0x7fff, 0x34);
+        machine.flush_save_ram().unwrap();
+        let save_ram = std::fs::read(&sav_path).unwrap();
+        assert_eq!(save_ram.len(), 0x2000);
+        assert_eq!((save_ram[0], save_ram[0x1fff]), (0x12, 0x34));
+
+        // Opening another ROM flushes the old one.
+        machine.bus.write(0x6001, 0x56);
+        machine.open_data(&rom).unwrap();
+        assert_eq!(machine.bus.read_immutable(0x6000), 0);
+        assert_eq!(std::fs::read(&sav_path).unwrap()[1], 0x56);
+
+        machine.open_path(&rom_path).unwrap();
+        assert_eq!(machine.bus.read_immutable(0x6000), 0x12);
+        assert_eq!(machine.bus.read_immutable(0x6001), 0x56);
+
+        // A failed flush doesn't keep the next ROM from opening.
+        std::fs::remove_file(&sav_path).unwrap();
+        std::fs::create_dir(&sav_path).unwrap();
+        machine.bus.write(0x6000, 0x78);
+        assert!(matches!(
+            machine.open_data(&rom),
+            Err(NesMachineError::FileIo)
+        ));
+        assert!(machine.header().is_some());
+        assert_eq!(machine.bus.read_immutable(0x6000), 0);
+        assert!(machine.flush_save_ram().is_ok());
+        std::fs::remove_dir(&sav_path).unwrap();
+
+        // A save of the wrong size fails the open, and is left alone.
+        std::fs::write(&sav_path, [0; 16]).unwrap();
+        assert!(matches!(
+            machine.open_path(&rom_path),
+            Err(NesMachineError::SaveRamUnexpectedLen(16))
+        ));
+        assert!(machine.header().is_none());
+        assert!(machine.bus.cart.save_ram().is_none());
+        machine.flush_save_ram().unwrap();
+        assert_eq!(std::fs::read(&sav_path).unwrap(), [0; 16]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_four_screen() {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x4000 + 0x2000]);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        assert_eq!(
+            machine.bus.cart.nt_arrangement(),
+            Some(bus::NametableArrangement::FourScreen)
+        );
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2c00].into_iter().enumerate() {
+            machine.bus.write_ppu(addr, i as u8 + 1);
+        }
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2c00].into_iter().enumerate() {
+            assert_eq!(machine.bus.read_ppu(addr), i as u8 + 1);
+            assert!(!machine.bus.cart.is_ppu_addr_mirror(addr));
+        }
+    }
+
+    #[test]
+    fn test_jam_halts_cpu() {
+        // NOP; JAM; NOP
+        let mut machine = machine_with_iram_code(&[0xea, 0x02, 0xea]);
+        machine.step_instruction();
+        assert!(!machine.cpu_halted());
+        machine.step_instruction();
+        assert!(machine.cpu_halted());
+        assert_eq!(machine.cpu.pc, 0x0001);
+
+        // Stays put, time keeps going, and NMI is ignored.
+        machine.bus.ppu_regs.vblank = false;
+        machine.bus.write(0x2000, 0x80);
+        let cycles = machine.cycle_count;
+        machine.step_frame();
+        machine.step_frame();
+        assert_eq!(machine.cpu.pc, 0x0001);
+        assert!(machine.cycle_count > cycles + 29000);
+
+        machine.reset();
+        assert!(!machine.cpu_halted());
+    }
+
+    #[test]
+    fn test_oam_dma_even_cycle() {
+        // LDA $02; STA $4014; NOP
+        let mut machine = machine_with_iram_code(&[0xa5, 0x02, 0x8d, 0x14, 0x40, 0xea]);
+        machine.step_instruction();
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 14);
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 14 + 513 + 2);
+    }
+
+    #[test]
+    fn test_dmc_fetch_stalls_cpu() {
+        // NOP; NOP
+        let mut machine = machine_with_iram_code(&[0xea, 0xea]);
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 9);
+
+        // 1 byte sample is fetched as soon as the channel is enabled.
+        machine.bus.write(0x4012, 0x00);
+        machine.bus.write(0x4013, 0x00);
+        machine.bus.write(0x4015, 0x10);
+        machine.step_instruction();
+        assert_eq!(machine.cycle_count, 9 + 4 + 2);
+        assert_eq!(machine.bus.read(0x4015) & 0x10, 0);
+    }
+
+    #[test]
+    fn test_audio_samples_per_frame() {
+        // Pulse 1 at ~440 Hz, constant volume 15
+        let mut machine = machine_with_iram_code(&[0x4c, 0x00, 0x00]); // JMP $0000
+        machine.set_sample_rate(Some(48000));
+        machine.bus.write(0x4015, 0x01);
+        machine.bus.write(0x4000, 0xbf);
+        machine.bus.write(0x4001, 0x08);
+        machine.bus.write(0x4002, 0xfd);
+        machine.bus.write(0x4003, 0x00);
+
+        let mut total = 0;
+        for _ in 0..60 {
+            machine.step_frame();
+            let samples = machine.take_audio_i16();
+            assert!((790..=810).contains(&samples.len()));
+            total += samples.len();
+            if total > 48000 / 2 {
+                assert!(samples.iter().any(|s| *s > 1000));
+                assert!(samples.iter().any(|s| *s < -1000));
+            }
+        }
+        assert!(machine.take_audio_f32().is_empty());
+    }
+
+    /// Runs `steps` instructions and records the CPU after each one.
+    fn trace(machine: &mut NesMachine, steps: usize) -> Vec<(String, usize)> {
+        let mut trace = vec![];
+        for _ in 0..steps {
+            machine.step_instruction();
+            trace.push((machine.cpu.to_string(), machine.cycle_count));
+        }
+        trace
+    }
+
+    #[test]
+    fn test_save_state_determinism() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/ntsc_torture.nes").unwrap();
+        for _ in 0..10 {
+            machine.step_frame();
+        }
+        for _ in 0..1234 {
+            machine.step_instruction();
+        }
+
+        let state = machine.save_state(true);
+        let expected_trace = trace(&mut machine, 30_000);
+        let expected_state = machine.save_state(true);
+
+        machine.load_state(&state).unwrap();
+        assert_eq!(machine.save_state(true), state);
+        assert_eq!(trace(&mut machine, 30_000), expected_trace);
+        assert_eq!(machine.save_state(true), expected_state);
+    }
+
+    #[test]
+    fn test_save_state_without_framebuffer() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/ntsc_torture.nes").unwrap();
+        machine.step_frame();
+        machine.step_frame();
+
+        let state = machine.save_state(false);
+        machine.step_frame();
+        let expected_state = machine.save_state(true);
+
+        // The framebuffer is left as is, and gets redrawn by the next frame.
+        let framebuffer = *machine.ppu.framebuffer();
+        machine.load_state(&state).unwrap();
+        assert_eq!(*machine.ppu.framebuffer(), framebuffer);
+        machine.step_frame();
+        assert_eq!(machine.save_state(true), expected_state);
+    }
+
+    #[test]
+    fn test_load_bad_state() {
+        let mut machine = NesMachine::default();
+        machine.open_path("../../tests/nestest.nes").unwrap();
+        machine.step_frame();
+        let state = machine.save_state(true);
+
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            machine.load_state(&bad_magic),
+            Err(NesMachineError::SaveStateInvalid)
+        ));
+
+        let mut bad_version = state.clone();
+        bad_version[4..8].copy_from_slice(&99_u32.to_le_bytes());
+        assert!(matches!(
+            machine.load_state(&bad_version),
+            Err(NesMachineError::SaveStateUnsupportedVersion(99))
+        ));
+
+        // Truncated. The machine must be left as it was.
+        machine.step_instruction();
+        let before = machine.save_state(true);
+        assert!(matches!(
+            machine.load_state(&state[..state.len() - 1]),
+            Err(NesMachineError::SaveStateInvalid)
+        ));
+        assert_eq!(machine.save_state(true), before);
+
+        // Different mapper
+        let mut other = NesMachine::default();
+        assert!(matches!(
+            other.load_state(&state),
+            Err(NesMachineError::SaveStateMismatch)
+        ));
+    }
+
     /*
     #[test]
     fn run_nestest_c000_auto_legal() {
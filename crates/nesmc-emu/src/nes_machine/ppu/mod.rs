@@ -1,6 +1,30 @@
-use super::bus::Bus;
+mod palette;
 
-#[derive(Debug)]
+pub use palette::Palette;
+
+use super::bus::{Bus, PpuDevice};
+use super::save_state::{Snapshot, StateReader, StateWriter};
+use crate::NesMachineError;
+
+/// Sprite loaded for the scanline being drawn
+#[derive(Debug, Clone, Copy, Default)]
+struct SpriteSlot {
+    x: u8,
+    attr: u8,
+    pattern_lo: u8,
+    pattern_hi: u8,
+}
+
+/// Opaque sprite pixel that won the sprite priority
+#[derive(Debug, Clone, Copy)]
+struct SpritePixel {
+    palette: u8,
+    pixel: u8,
+    behind_bg: bool,
+    is_sprite_zero: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct Ppu {
     framebuffer: [u8; 256 * 240 * 3],
 
@@ -8,19 +32,36 @@ pub struct Ppu {
     /// Within scanline
     cycle: usize,
 
-    /// VRAM addr
-    _v: u16,
-    /// Temp VRAM addr
-    _t: u16,
-    /// Fine x scroll (3 bits)
-    _x: u8,
-    /// Write
-    _w: bool,
-
     frame_even: bool,
 
-    pattern_tile_idx: u16,
-    pub nmi_fired: bool,
+    // Background tile fetched ahead of time. Loaded into the shifters every 8 cycles.
+    bg_next_tile_id: u8,
+    bg_next_tile_attr: u8,
+    bg_next_tile_lo: u8,
+    bg_next_tile_hi: u8,
+
+    // Background shift registers. High byte is the tile being drawn, low byte is the next one.
+    bg_shifter_pattern_lo: u16,
+    bg_shifter_pattern_hi: u16,
+    bg_shifter_attr_lo: u16,
+    bg_shifter_attr_hi: u16,
+
+    /// Sprites found for the next scanline. 8 sprites, 4 bytes each.
+    secondary_oam: [u8; 32],
+    /// No. of sprites in secondary OAM
+    sprite_count: usize,
+    /// Sprite 0 made it to secondary OAM
+    sprite_zero_found: bool,
+    /// Dot at which the evaluation raises the overflow flag on this scanline
+    sprite_overflow_dot: Option<usize>,
+
+    /// Sprites being drawn on the current scanline
+    sprite_slots: [SpriteSlot; 8],
+    sprite_slot_count: usize,
+    /// Slot 0 holds sprite 0
+    sprite_zero_in_slots: bool,
+
+    pub palette: Palette,
 }
 
 impl Default for Ppu {
@@ -30,13 +71,28 @@ impl Default for Ppu {
 
             scanline: 0,
             cycle: 0,
-            _v: 0,
-            _t: 0,
-            _x: 0,
-            _w: false,
             frame_even: true,
-            pattern_tile_idx: 0,
-            nmi_fired: false,
+
+            bg_next_tile_id: 0,
+            bg_next_tile_attr: 0,
+            bg_next_tile_lo: 0,
+            bg_next_tile_hi: 0,
+
+            bg_shifter_pattern_lo: 0,
+            bg_shifter_pattern_hi: 0,
+            bg_shifter_attr_lo: 0,
+            bg_shifter_attr_hi: 0,
+
+            secondary_oam: [0xff; 32],
+            sprite_count: 0,
+            sprite_zero_found: false,
+            sprite_overflow_dot: None,
+
+            sprite_slots: [SpriteSlot::default(); 8],
+            sprite_slot_count: 0,
+            sprite_zero_in_slots: false,
+
+            palette: Palette::default(),
         }
     }
 }
@@ -67,114 +123,370 @@ impl Ppu {
         }
     }
 
-    fn process_pre_render_scanline(&mut self, _bus: &Bus) {}
+    fn process_pre_render_scanline(&mut self, bus: &mut Bus) {
+        if self.cycle == 1 {
+            bus.ppu_regs.vblank = false;
+            bus.ppu_regs.sprite_0_hit = false;
+            bus.ppu_regs.sprite_overflow = false;
+            // Sprites are never drawn on the first scanline.
+            self.sprite_count = 0;
+            self.sprite_zero_found = false;
+        }
+
+        self.process_bg_fetches(bus);
+        self.process_sprite_fetches(bus);
+
+        // Vertical scroll is reloaded over and over during these cycles.
+        if let 280..=304 = self.cycle
+            && bus.ppu_regs.rendering_enabled()
+        {
+            bus.ppu_regs.transfer_y();
+        }
+    }
 
     fn process_render_scanline(&mut self, bus: &mut Bus) {
-        // PPU skips first idle cycle on even frames
-        if self.cycle == 0 && self.frame_even {
+        // PPU skips first idle cycle on odd frames when rendering
+        if self.scanline == 0
+            && self.cycle == 0
+            && !self.frame_even
+            && bus.ppu_regs.rendering_enabled()
+        {
             self.cycle += 1;
         }
 
+        self.process_bg_fetches(bus);
+        self.process_sprite_evaluation(bus);
+
+        if let 1..=256 = self.cycle {
+            self.render_pixel(bus);
+        }
+
+        self.process_sprite_fetches(bus);
+    }
+
+    fn process_vblank_set_scanline(&mut self, bus: &mut Bus) {
+        if self.cycle == 1 {
+            bus.ppu_regs.vblank = true;
+        }
+    }
+
+    /// Background memory fetches and scroll updates, shared by visible and pre-render scanlines.
+    fn process_bg_fetches(&mut self, bus: &mut Bus) {
+        if !bus.ppu_regs.rendering_enabled() {
+            return;
+        }
+
         match self.cycle {
-            0 => (),
-            1..=256 => {
-                // BG
+            2..=257 | 321..=337 => {
+                self.shift_bg();
+
                 match (self.cycle - 1) % 8 {
-                    1 => self.fetch_nametable(bus),
-                    3 => self.fetch_attribute(bus),
-                    5 => self.fetch_pattern_lo(bus),
-                    7 => self.fetch_pattern_hi(bus),
+                    0 => {
+                        self.load_bg_shifters();
+                        self.fetch_nametable(bus);
+                    }
+                    2 => self.fetch_attribute(bus),
+                    4 => self.fetch_pattern_lo(bus),
+                    6 => self.fetch_pattern_hi(bus),
+                    7 => bus.ppu_regs.increment_coarse_x(),
                     _ => (),
                 }
-
-                self.render_pixel(bus);
             }
-            257..=320 => {
-                //
+            // Unused nametable fetches
+            338 | 340 => self.fetch_nametable(bus),
+            _ => (),
+        }
+
+        match self.cycle {
+            256 => bus.ppu_regs.increment_y(),
+            257 => {
+                self.load_bg_shifters();
+                bus.ppu_regs.transfer_x();
             }
-            321..=336 => {
-                //
+            _ => (),
+        }
+    }
+
+    /// Find the sprites of the next scanline.
+    fn process_sprite_evaluation(&mut self, bus: &mut Bus) {
+        if !bus.ppu_regs.rendering_enabled() {
+            return;
+        }
+
+        match self.cycle {
+            // Hardware clears secondary OAM over dots 1-64.
+            1 => self.secondary_oam = [0xff; 32],
+            // Evaluation takes dots 65-256. It's done in one go, only the overflow flag is timed.
+            65 => self.evaluate_sprites(bus),
+            _ => (),
+        }
+
+        if self.sprite_overflow_dot == Some(self.cycle) {
+            bus.ppu_regs.sprite_overflow = true;
+        }
+    }
+
+    fn evaluate_sprites(&mut self, bus: &Bus) {
+        let height = self.sprite_height(bus) as isize;
+        let line = self.scanline as isize;
+        let oam = &bus.ppu_regs.oam;
+        let in_range = |y: u8| (0..height).contains(&(line - y as isize));
+
+        self.sprite_count = 0;
+        self.sprite_zero_found = false;
+        self.sprite_overflow_dot = None;
+
+        // Reading a Y coord takes 2 dots, copying the rest of an in-range sprite 6 more.
+        let mut dot = 65;
+        let mut n = 0;
+        while n < 64 && self.sprite_count < 8 {
+            let entry = &oam[n * 4..n * 4 + 4];
+            dot += 2;
+            if in_range(entry[0]) {
+                let slot = self.sprite_count * 4;
+                self.secondary_oam[slot..slot + 4].copy_from_slice(entry);
+                self.sprite_count += 1;
+                if n == 0 {
+                    self.sprite_zero_found = true;
+                }
+                dot += 6;
             }
-            337..=340 => {
-                //
+            n += 1;
+        }
+
+        // Overflow search. Hardware bug: the byte index is incremented along with the sprite
+        // index, so it reads tile nos., attributes and X coords as if they were Y coords.
+        let mut m = 0;
+        while n < 64 {
+            dot += 2;
+            if in_range(oam[n * 4 + m]) {
+                self.sprite_overflow_dot = Some(dot);
+                break;
             }
-            _ => unreachable!(),
+            n += 1;
+            m = (m + 1) % 4;
         }
     }
 
-    fn process_vblank_set_scanline(&mut self, bus: &mut Bus) {
-        if self.cycle == 1 {
-            bus.ppu_regs.vblank = true;
-            if bus.ppu_regs.ctrl.nmi_enable {
-                self.nmi_fired = true;
-            }
+    /// Load the sprite patterns for the next scanline. Missing sprites still fetch tile $ff.
+    fn process_sprite_fetches(&mut self, bus: &mut Bus) {
+        if !bus.ppu_regs.rendering_enabled() {
+            return;
+        }
+        let 257..=320 = self.cycle else {
+            return;
+        };
+
+        bus.ppu_regs.oam_addr = 0;
+
+        let slot = (self.cycle - 257) / 8;
+        match (self.cycle - 257) % 8 {
+            4 => self.fetch_sprite_pattern(bus, slot, false),
+            6 => self.fetch_sprite_pattern(bus, slot, true),
+            _ => (),
+        }
+
+        if self.cycle == 320 {
+            self.sprite_slot_count = self.sprite_count;
+            self.sprite_zero_in_slots = self.sprite_zero_found;
         }
     }
 
-    // TODO: Cycle-accurate rendering
-    fn fetch_nametable(&mut self, _bus: &mut Bus) {
-        // let current_px_idx = self.cycle - 1 + self.scanline * 256;
-        // let fetch_px_idx = (current_px_idx + 16) % self.framebuffer.len();
+    fn sprite_height(&self, bus: &Bus) -> u16 {
+        if bus.ppu_regs.ctrl.tall_sprites {
+            16
+        } else {
+            8
+        }
     }
 
-    // TODO: Cycle-accurate rendering
-    fn fetch_attribute(&mut self, _bus: &mut Bus) {
-        //
+    fn fetch_sprite_pattern(&mut self, bus: &mut Bus, slot: usize, hi: bool) {
+        let entry = &self.secondary_oam[slot * 4..slot * 4 + 4];
+        let (y, tile, attr, x) = (entry[0], entry[1] as u16, entry[2], entry[3]);
+
+        let height = self.sprite_height(bus);
+        let mut row = (self.scanline as u8).wrapping_sub(y) as u16 % height;
+        if attr & 0x80 != 0 {
+            row = height - 1 - row;
+        }
+
+        let mut addr = if bus.ppu_regs.ctrl.tall_sprites {
+            // 8x16 sprites pick their pattern table with bit 0 of the tile no.
+            let table = (tile & 0x01) * 0x1000;
+            let tile = (tile & 0xfe) + row / 8;
+            table + tile * 0x10 + row % 8
+        } else {
+            bus.ppu_regs.ctrl.base_sprite_pattern_addr + tile * 0x10 + row
+        };
+        if hi {
+            addr += 8;
+        }
+
+        let mut pattern = bus.fetch_ppu(addr);
+        if attr & 0x40 != 0 {
+            pattern = pattern.reverse_bits();
+        }
+        if slot >= self.sprite_count {
+            pattern = 0;
+        }
+
+        let sprite = &mut self.sprite_slots[slot];
+        sprite.x = x;
+        sprite.attr = attr;
+        if hi {
+            sprite.pattern_hi = pattern;
+        } else {
+            sprite.pattern_lo = pattern;
+        }
     }
 
-    // TODO: Cycle-accurate rendering
-    fn fetch_pattern_lo(&mut self, _bus: &mut Bus) {
-        self.pattern_tile_idx &= 0xff00;
-        //let value = bus.read_ppu(address) as u16;
-        //self.pattern_tile_idx |= value;
+    fn fetch_nametable(&mut self, bus: &mut Bus) {
+        let addr = 0x2000 | (bus.ppu_regs.v & 0x0fff);
+        self.bg_next_tile_id = bus.fetch_ppu(addr);
     }
 
-    // TODO: Cycle-accurate rendering
-    fn fetch_pattern_hi(&mut self, _bus: &mut Bus) {
-        self.pattern_tile_idx &= 0x00ff;
-        //let value = bus.read_ppu(address + 1) as u16;
-        //self.pattern_tile_idx |= value << 8;
+    fn fetch_attribute(&mut self, bus: &mut Bus) {
+        let v = bus.ppu_regs.v;
+        let addr = 0x23c0 | (v & 0x0c00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+        let mut attr = bus.fetch_ppu(addr);
+
+        // Each attribute byte covers 4x4 tiles, 2 bits per 2x2 quadrant.
+        let coarse_x = v & 0x001f;
+        let coarse_y = (v >> 5) & 0x001f;
+        if coarse_y & 0x02 != 0 {
+            attr >>= 4;
+        }
+        if coarse_x & 0x02 != 0 {
+            attr >>= 2;
+        }
+        self.bg_next_tile_attr = attr & 0x03;
     }
 
-    // TODO: Cycle-accurate rendering
-    fn render_pixel(&mut self, bus: &mut Bus) {
-        let name_idx = (self.cycle as u16 - 1) / 8 + (self.scanline as u16 / 8) * 32;
-        let name_off = bus.ppu_regs.ctrl.base_nametable_addr + name_idx;
-        let tile_idx = bus.read_ppu(name_off) as u16;
-        let tile_addr = tile_idx * 0x10 + bus.ppu_regs.ctrl.base_bg_pattern_addr;
+    fn bg_pattern_addr(&self, bus: &Bus) -> u16 {
+        let fine_y = (bus.ppu_regs.v >> 12) & 0x07;
+        bus.ppu_regs.ctrl.base_bg_pattern_addr + self.bg_next_tile_id as u16 * 0x10 + fine_y
+    }
 
-        let x = (self.cycle as u16 - 1) % 8;
-        let y = self.scanline as u16 % 8;
+    fn fetch_pattern_lo(&mut self, bus: &mut Bus) {
+        let addr = self.bg_pattern_addr(bus);
+        self.bg_next_tile_lo = bus.fetch_ppu(addr);
+    }
 
-        let plane_0 = bus.read_ppu(tile_addr + y);
-        let plane_1 = bus.read_ppu(tile_addr + y + 8);
+    fn fetch_pattern_hi(&mut self, bus: &mut Bus) {
+        let addr = self.bg_pattern_addr(bus) + 8;
+        self.bg_next_tile_hi = bus.fetch_ppu(addr);
+    }
 
-        let val_lo = plane_0 << x & 0x80;
-        let val_hi = plane_1 << x & 0x80;
+    fn load_bg_shifters(&mut self) {
+        self.bg_shifter_pattern_lo =
+            (self.bg_shifter_pattern_lo & 0xff00) | self.bg_next_tile_lo as u16;
+        self.bg_shifter_pattern_hi =
+            (self.bg_shifter_pattern_hi & 0xff00) | self.bg_next_tile_hi as u16;
+
+        // Attribute applies to the whole tile, so it's spread over all 8 bits.
+        let attr_lo = if self.bg_next_tile_attr & 0x01 != 0 {
+            0xff
+        } else {
+            0
+        };
+        let attr_hi = if self.bg_next_tile_attr & 0x02 != 0 {
+            0xff
+        } else {
+            0
+        };
+        self.bg_shifter_attr_lo = (self.bg_shifter_attr_lo & 0xff00) | attr_lo;
+        self.bg_shifter_attr_hi = (self.bg_shifter_attr_hi & 0xff00) | attr_hi;
+    }
 
-        let color_idx = (val_lo >> 7) + (val_hi >> 6);
+    fn shift_bg(&mut self) {
+        self.bg_shifter_pattern_lo <<= 1;
+        self.bg_shifter_pattern_hi <<= 1;
+        self.bg_shifter_attr_lo <<= 1;
+        self.bg_shifter_attr_hi <<= 1;
+    }
 
-        // Placeholder palette
-        let color = match color_idx {
-            0 => (0, 0, 0),
-            1 => (0x40, 0x40, 0x40),
-            2 => (0x80, 0x80, 0x80),
-            3 => (0xff, 0xff, 0xff),
-            _ => unreachable!(),
+    /// Background pixel at the current position.
+    /// Returns (palette, pixel). Pixel 0 is transparent.
+    fn bg_pixel(&self, bus: &Bus, x: usize) -> (u8, u8) {
+        let regs = &bus.ppu_regs;
+        if !regs.mask.bg || (x < 8 && !regs.mask.bg_mask) {
+            return (0, 0);
+        }
+
+        let mux = 0x8000 >> regs.x;
+        let bit = |shifter: u16| u8::from(shifter & mux != 0);
+
+        let pixel = bit(self.bg_shifter_pattern_lo) | (bit(self.bg_shifter_pattern_hi) << 1);
+        let palette = bit(self.bg_shifter_attr_lo) | (bit(self.bg_shifter_attr_hi) << 1);
+        (palette, pixel)
+    }
+
+    /// Frontmost opaque sprite pixel at the current position.
+    fn sprite_pixel(&self, bus: &Bus, x: usize) -> Option<SpritePixel> {
+        let regs = &bus.ppu_regs;
+        if !regs.mask.sprite || (x < 8 && !regs.mask.sprite_mask) {
+            return None;
+        }
+
+        let slots = &self.sprite_slots[..self.sprite_slot_count];
+        slots.iter().enumerate().find_map(|(i, sprite)| {
+            let offset = x.checked_sub(sprite.x as usize).filter(|off| *off < 8)?;
+            let bit = 7 - offset;
+            let pixel = ((sprite.pattern_lo >> bit) & 1) | (((sprite.pattern_hi >> bit) & 1) << 1);
+            if pixel == 0 {
+                return None;
+            }
+            Some(SpritePixel {
+                palette: sprite.attr & 0x03,
+                pixel,
+                behind_bg: sprite.attr & 0x20 != 0,
+                is_sprite_zero: i == 0 && self.sprite_zero_in_slots,
+            })
+        })
+    }
+
+    fn render_pixel(&mut self, bus: &mut Bus) {
+        let x = self.cycle - 1;
+        let (bg_palette, bg_pixel) = self.bg_pixel(bus, x);
+        let sprite = self.sprite_pixel(bus, x);
+
+        if let Some(sprite) = sprite
+            && sprite.is_sprite_zero
+            && bg_pixel != 0
+            && x != 255
+        {
+            bus.ppu_regs.sprite_0_hit = true;
+        }
+
+        // Priority mux
+        let color_idx = match sprite {
+            Some(sprite) if bg_pixel == 0 || !sprite.behind_bg => {
+                0x10 | (sprite.palette << 2) | sprite.pixel
+            }
+            _ if bg_pixel != 0 => (bg_palette << 2) | bg_pixel,
+            _ => 0,
         };
 
-        let pixel_idx = self.cycle - 1 + self.scanline * 256;
+        let mut value = bus.pram.read_ppu(color_idx as u16);
+        if bus.ppu_regs.mask.grayscale {
+            value &= 0x30;
+        }
+        let color = self.palette.color(value, bus.ppu_regs.mask.emphasis());
+
+        let pixel_idx = x + self.scanline * 256;
         let pixel_off = pixel_idx * 3;
 
-        self.framebuffer[pixel_off] = color.0;
-        self.framebuffer[pixel_off + 1] = color.1;
-        self.framebuffer[pixel_off + 2] = color.2;
+        self.framebuffer[pixel_off..pixel_off + 3].copy_from_slice(&color);
     }
 
     pub fn framebuffer(&self) -> &[u8; 256 * 240 * 3] {
         &self.framebuffer
     }
 
+    pub(crate) fn framebuffer_mut(&mut self) -> &mut [u8; 256 * 240 * 3] {
+        &mut self.framebuffer
+    }
+
     pub fn scanline(&self) -> usize {
         self.scanline
     }
@@ -190,17 +502,322 @@ impl Ppu {
 }
 
 fn cpu_ppu_access(bus: &mut Bus) {
-    let addr = bus.ppu_regs.ppu_addr;
-    let value = bus.ppu_regs.ppu_read_buf;
+    let addr = bus.ppu_regs.ppu_access_addr & 0x3fff;
 
     if bus.ppu_regs.ppu_read_refresh {
-        bus.ppu_regs.ppu_read_buf = bus.read_ppu(addr);
+        // Palette reads bypass the buffer, which gets the nametable byte "under" the palette.
+        let buf_addr = if addr >= 0x3f00 { addr - 0x1000 } else { addr };
+        bus.ppu_regs.ppu_read_buf = bus.fetch_ppu(buf_addr);
     }
     if bus.ppu_regs.ppu_written {
-        bus.write_ppu(addr, value);
-        println!("w {addr:04x} {value:02x}");
+        bus.cart.ppu_bus_addr(addr);
+        bus.write_ppu(addr, bus.ppu_regs.ppu_write_buf);
     }
 
     bus.ppu_regs.ppu_read_refresh = false;
     bus.ppu_regs.ppu_written = false;
 }
+
+impl Snapshot for Ppu {
+    /// Framebuffer and palette aren't included.
+    fn snapshot(&self, w: &mut StateWriter) {
+        w.usize(self.scanline);
+        w.usize(self.cycle);
+        w.bool(self.frame_even);
+
+        w.u8(self.bg_next_tile_id);
+        w.u8(self.bg_next_tile_attr);
+        w.u8(self.bg_next_tile_lo);
+        w.u8(self.bg_next_tile_hi);
+        w.u16(self.bg_shifter_pattern_lo);
+        w.u16(self.bg_shifter_pattern_hi);
+        w.u16(self.bg_shifter_attr_lo);
+        w.u16(self.bg_shifter_attr_hi);
+
+        w.bytes(&self.secondary_oam);
+        w.usize(self.sprite_count);
+        w.bool(self.sprite_zero_found);
+        w.option_usize(self.sprite_overflow_dot);
+
+        for sprite in &self.sprite_slots {
+            w.bytes(&[sprite.x, sprite.attr, sprite.pattern_lo, sprite.pattern_hi]);
+        }
+        w.usize(self.sprite_slot_count);
+        w.bool(self.sprite_zero_in_slots);
+    }
+
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError> {
+        self.scanline = r.usize()?;
+        self.cycle = r.usize()?;
+        self.frame_even = r.bool()?;
+        if self.scanline > 261 || self.cycle > 340 {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+
+        self.bg_next_tile_id = r.u8()?;
+        self.bg_next_tile_attr = r.u8()?;
+        self.bg_next_tile_lo = r.u8()?;
+        self.bg_next_tile_hi = r.u8()?;
+        self.bg_shifter_pattern_lo = r.u16()?;
+        self.bg_shifter_pattern_hi = r.u16()?;
+        self.bg_shifter_attr_lo = r.u16()?;
+        self.bg_shifter_attr_hi = r.u16()?;
+
+        r.bytes(&mut self.secondary_oam)?;
+        self.sprite_count = r.usize()?;
+        self.sprite_zero_found = r.bool()?;
+        self.sprite_overflow_dot = r.option_usize()?;
+
+        for sprite in &mut self.sprite_slots {
+            let mut bytes = [0; 4];
+            r.bytes(&mut bytes)?;
+            let [x, attr, pattern_lo, pattern_hi] = bytes;
+            *sprite = SpriteSlot {
+                x,
+                attr,
+                pattern_lo,
+                pattern_hi,
+            };
+        }
+        self.sprite_slot_count = r.usize()?;
+        self.sprite_zero_in_slots = r.bool()?;
+        if self.sprite_count > 8 || self.sprite_slot_count > 8 {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::bus::Mapper;
+
+    /// NROM bus where CHR tile 1 is solid pixel value 1 and tile 2 is solid pixel value 3.
+    fn test_bus() -> Bus {
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(vec![0; 0x4000]);
+        let mut chr = vec![0; 0x2000];
+        chr[0x10..0x18].fill(0xff);
+        chr[0x20..0x30].fill(0xff);
+        rom.extend(chr);
+
+        let mut bus = Bus {
+            cart: Mapper::from_reader(&mut BufReader::new(&rom[..])).unwrap(),
+            ..Default::default()
+        };
+        bus.write_ppu(0x3f00, 0x0f);
+        bus.write_ppu(0x3f01, 0x00);
+        bus.write_ppu(0x3f03, 0x30);
+        bus.write_ppu(0x3f11, 0x00);
+        bus.write_ppu(0x3f13, 0x30);
+        bus
+    }
+
+    /// Red channel of color 1 and 3 of the test bus palettes.
+    const SHADE_1: u8 = 84;
+    const SHADE_3: u8 = 236;
+
+    /// Run until the end of the next fully rendered frame.
+    fn run_frame(ppu: &mut Ppu, bus: &mut Bus) {
+        while ppu.scanline() != 261 {
+            ppu.step(bus);
+        }
+        while ppu.scanline() != 240 {
+            ppu.step(bus);
+        }
+    }
+
+    fn shade(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.framebuffer()[(x + y * 256) * 3]
+    }
+
+    #[test]
+    fn test_fine_x_scroll() {
+        let mut bus = test_bus();
+        for col in 0..32 {
+            bus.write_ppu(0x2000 + col, if col % 2 == 0 { 1 } else { 2 });
+        }
+        bus.write(0x2005, 4);
+        bus.write(0x2005, 0);
+        bus.write(0x2001, 0x0a);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+
+        assert_eq!(shade(&ppu, 0, 0), SHADE_1);
+        assert_eq!(shade(&ppu, 3, 0), SHADE_1);
+        assert_eq!(shade(&ppu, 4, 0), SHADE_3);
+        assert_eq!(shade(&ppu, 11, 0), SHADE_3);
+        assert_eq!(shade(&ppu, 12, 0), SHADE_1);
+    }
+
+    #[test]
+    fn test_y_scroll_wraps_to_next_nametable() {
+        let mut bus = test_bus();
+        // Horizontal arrangement: $2800 is the nametable below $2000
+        bus.write_ppu(0x2000 + 29 * 32, 1);
+        bus.write_ppu(0x2800, 2);
+        bus.write(0x2005, 0);
+        bus.write(0x2005, 29 * 8);
+        bus.write(0x2001, 0x0a);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+
+        assert_eq!(shade(&ppu, 0, 0), SHADE_1);
+        assert_eq!(shade(&ppu, 0, 7), SHADE_1);
+        assert_eq!(shade(&ppu, 0, 8), SHADE_3);
+        assert_eq!(shade(&ppu, 8, 8), 0);
+    }
+
+    #[test]
+    fn test_split_scroll_via_2006() {
+        let mut bus = test_bus();
+        bus.write_ppu(0x2000, 1);
+        bus.write_ppu(0x2001, 2);
+        bus.write(0x2001, 0x0a);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+        assert_eq!(shade(&ppu, 0, 120), 0);
+
+        // Mid-frame $2006 writes point v at the top row, one tile to the right.
+        while ppu.scanline() != 119 || ppu.cycle() != 300 {
+            ppu.step(&mut bus);
+        }
+        bus.read(0x2002);
+        bus.write(0x2006, 0x00);
+        bus.write(0x2006, 0x01);
+        while ppu.scanline() != 240 {
+            ppu.step(&mut bus);
+        }
+        assert_eq!(shade(&ppu, 0, 119), 0);
+        assert_eq!(shade(&ppu, 0, 120), SHADE_3);
+        assert_eq!(shade(&ppu, 8, 120), 0);
+    }
+
+    /// Test bus with every sprite hidden below the screen.
+    fn sprite_test_bus() -> Bus {
+        let mut bus = test_bus();
+        bus.ppu_regs.oam = [0xff; 256];
+        bus.write(0x2001, 0x1e);
+        bus
+    }
+
+    fn set_sprite(bus: &mut Bus, n: usize, y: u8, tile: u8, attr: u8, x: u8) {
+        bus.ppu_regs.oam[n * 4..n * 4 + 4].copy_from_slice(&[y, tile, attr, x]);
+    }
+
+    #[test]
+    fn test_sprite_drawn_one_line_below_y() {
+        let mut bus = sprite_test_bus();
+        set_sprite(&mut bus, 0, 9, 2, 0, 20);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+
+        assert_eq!(shade(&ppu, 20, 9), 0);
+        assert_eq!(shade(&ppu, 20, 10), SHADE_3);
+        assert_eq!(shade(&ppu, 27, 17), SHADE_3);
+        assert_eq!(shade(&ppu, 28, 17), 0);
+        assert_eq!(shade(&ppu, 20, 18), 0);
+    }
+
+    #[test]
+    fn test_sprite_priority() {
+        let mut bus = sprite_test_bus();
+        bus.write_ppu(0x2000, 2);
+        bus.write_ppu(0x2001, 2);
+        // Front sprite over tile 0, back sprite over tile 1
+        set_sprite(&mut bus, 0, 0, 1, 0x00, 0);
+        set_sprite(&mut bus, 1, 0, 1, 0x20, 8);
+        // Back sprite over the transparent background
+        set_sprite(&mut bus, 2, 0, 1, 0x20, 16);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+
+        assert_eq!(shade(&ppu, 0, 1), SHADE_1);
+        assert_eq!(shade(&ppu, 8, 1), SHADE_3);
+        assert_eq!(shade(&ppu, 16, 1), SHADE_1);
+    }
+
+    #[test]
+    fn test_sprite_0_hit() {
+        let mut bus = sprite_test_bus();
+        bus.write_ppu(0x2002, 1);
+
+        let mut ppu = Ppu::default();
+        set_sprite(&mut bus, 0, 40, 1, 0, 16);
+        run_frame(&mut ppu, &mut bus);
+        assert!(!bus.ppu_regs.sprite_0_hit);
+
+        set_sprite(&mut bus, 0, 3, 1, 0, 16);
+        run_frame(&mut ppu, &mut bus);
+        assert!(bus.ppu_regs.sprite_0_hit);
+    }
+
+    #[test]
+    fn test_palette_and_grayscale() {
+        let mut bus = test_bus();
+        bus.write_ppu(0x2000, 1);
+        bus.write_ppu(0x2001, 2);
+        bus.write_ppu(0x3f00, 0x21);
+        bus.write_ppu(0x3f01, 0x16);
+        bus.write_ppu(0x3f03, 0x2a);
+        bus.write(0x2001, 0x0a);
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+        let rgb = |ppu: &Ppu, x: usize| {
+            let off = x * 3;
+            ppu.framebuffer()[off..off + 3].to_vec()
+        };
+        assert_eq!(rgb(&ppu, 0), ppu.palette.color(0x16, 0));
+        assert_eq!(rgb(&ppu, 8), ppu.palette.color(0x2a, 0));
+        assert_eq!(rgb(&ppu, 16), ppu.palette.color(0x21, 0));
+
+        bus.write(0x2001, 0x2b);
+        run_frame(&mut ppu, &mut bus);
+        assert_eq!(rgb(&ppu, 0), ppu.palette.color(0x10, 0b001));
+        assert_eq!(rgb(&ppu, 8), ppu.palette.color(0x20, 0b001));
+    }
+
+    #[test]
+    fn test_palette_read_skips_buffer() {
+        let mut bus = test_bus();
+        let mut ppu = Ppu::default();
+        bus.write_ppu(0x2f01, 0x55);
+        bus.write_ppu(0x3f01, 0x16);
+
+        bus.write(0x2006, 0x3f);
+        bus.write(0x2006, 0x01);
+        assert_eq!(bus.read(0x2007), 0x16);
+        ppu.step(&mut bus);
+        // The buffer gets the nametable byte from $2F01.
+        bus.write(0x2006, 0x20);
+        bus.write(0x2006, 0x00);
+        assert_eq!(bus.read(0x2007), 0x55);
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let mut bus = sprite_test_bus();
+        for n in 0..8 {
+            set_sprite(&mut bus, n, 50, 1, 0, n as u8 * 10);
+        }
+
+        let mut ppu = Ppu::default();
+        run_frame(&mut ppu, &mut bus);
+        assert!(!bus.ppu_regs.sprite_overflow);
+
+        set_sprite(&mut bus, 8, 50, 1, 0, 80);
+        run_frame(&mut ppu, &mut bus);
+        assert!(bus.ppu_regs.sprite_overflow);
+    }
+}
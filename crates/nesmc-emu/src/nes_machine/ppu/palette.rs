@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use crate::NesMachineError;
+
+/// 2C02 master palette, 64 RGB colors
+#[rustfmt::skip]
+const DEFAULT_COLORS: [[u8; 3]; 64] = [
+    [84, 84, 84], [0, 30, 116], [8, 16, 144], [48, 0, 136],
+    [68, 0, 100], [92, 0, 48], [84, 4, 0], [60, 24, 0],
+    [32, 42, 0], [8, 58, 0], [0, 64, 0], [0, 60, 0],
+    [0, 50, 60], [0, 0, 0], [0, 0, 0], [0, 0, 0],
+
+    [152, 150, 152], [8, 76, 196], [48, 50, 236], [92, 30, 228],
+    [136, 20, 176], [160, 20, 100], [152, 34, 32], [120, 60, 0],
+    [84, 90, 0], [40, 114, 0], [8, 124, 0], [0, 118, 40],
+    [0, 102, 120], [0, 0, 0], [0, 0, 0], [0, 0, 0],
+
+    [236, 238, 236], [76, 154, 236], [120, 124, 236], [176, 98, 236],
+    [228, 84, 236], [236, 88, 180], [236, 106, 100], [212, 136, 32],
+    [160, 170, 0], [116, 196, 0], [76, 208, 32], [56, 204, 108],
+    [56, 180, 204], [60, 60, 60], [0, 0, 0], [0, 0, 0],
+
+    [236, 238, 236], [168, 204, 236], [188, 188, 236], [212, 178, 236],
+    [236, 174, 236], [236, 174, 212], [236, 180, 176], [228, 196, 144],
+    [204, 210, 120], [180, 222, 120], [168, 226, 144], [152, 226, 180],
+    [160, 214, 228], [160, 162, 160], [0, 0, 0], [0, 0, 0],
+];
+
+/// Attenuation of a color channel per emphasis bit that doesn't belong to it
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+/// Master palette: 64 colors for each of the 8 color emphasis combinations.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: [[u8; 3]; Self::LEN_FULL],
+}
+
+impl Palette {
+    /// No. of colors in a palette without emphasis
+    pub const LEN: usize = 64;
+    /// No. of colors in a palette with emphasis
+    pub const LEN_FULL: usize = Self::LEN * 8;
+
+    /// Parse a .pal file. 192 byte files get their emphasis colors generated.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, NesMachineError> {
+        let colors: Vec<[u8; 3]> = data
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+
+        match data.len() {
+            len if len == Self::LEN_FULL * 3 => Ok(Self {
+                colors: colors.try_into().unwrap(),
+            }),
+            len if len == Self::LEN * 3 => {
+                Ok(Self::with_generated_emphasis(colors.try_into().unwrap()))
+            }
+            len => Err(NesMachineError::PaletteUnexpectedLen(len)),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, NesMachineError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Emphasis bits dim the other two color channels.
+    fn with_generated_emphasis(base: [[u8; 3]; Self::LEN]) -> Self {
+        let mut colors = [[0; 3]; Self::LEN_FULL];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let emphasis = i / Self::LEN;
+            let [r, g, b] = base[i % Self::LEN];
+            // Emphasis bits are R, G, B from lsb. Each channel is dimmed by the other two.
+            let dim = |value: u8, channel_bit: usize| {
+                let n = (emphasis & !channel_bit).count_ones() as i32;
+                (value as f32 * EMPHASIS_ATTENUATION.powi(n)).round() as u8
+            };
+            *color = [dim(r, 0b001), dim(g, 0b010), dim(b, 0b100)];
+        }
+        Self { colors }
+    }
+
+    /// RGB of a palette RAM value (6 bits) with the emphasis bits of PPUMASK (R, G, B from lsb).
+    pub fn color(&self, value: u8, emphasis: u8) -> [u8; 3] {
+        let emphasis = (emphasis & 0x07) as usize;
+        self.colors[emphasis * Self::LEN + (value & 0x3f) as usize]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::with_generated_emphasis(DEFAULT_COLORS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes_192() {
+        let mut data = vec![0; 192];
+        data[0x16 * 3..0x16 * 3 + 3].copy_from_slice(&[200, 100, 50]);
+
+        let palette = Palette::from_bytes(&data).unwrap();
+        assert_eq!(palette.color(0x16, 0), [200, 100, 50]);
+        assert_eq!(palette.color(0x56, 0), [200, 100, 50]);
+
+        // Red emphasis keeps red and dims green and blue.
+        let [r, g, b] = palette.color(0x16, 0b001);
+        assert_eq!(r, 200);
+        assert!(g < 100 && b < 50);
+
+        // All bits dim everything
+        let [r, g, b] = palette.color(0x16, 0b111);
+        assert!(r < 200 && g < 100 && b < 50);
+    }
+
+    #[test]
+    fn test_from_bytes_1536() {
+        let data: Vec<u8> = (0..Palette::LEN_FULL)
+            .flat_map(|i| [(i / Palette::LEN) as u8, (i % Palette::LEN) as u8, 0])
+            .collect();
+
+        let palette = Palette::from_bytes(&data).unwrap();
+        assert_eq!(palette.color(0x00, 0), [0, 0x00, 0]);
+        assert_eq!(palette.color(0x2a, 0b101), [5, 0x2a, 0]);
+        assert_eq!(palette.color(0x3f, 0b111), [7, 0x3f, 0]);
+    }
+
+    #[test]
+    fn test_from_bytes_bad_len() {
+        let err = Palette::from_bytes(&[0; 191]).unwrap_err();
+        assert!(matches!(err, NesMachineError::PaletteUnexpectedLen(191)));
+        assert_eq!(err.to_string(), "Unexpected palette file length: 191 bytes");
+    }
+}
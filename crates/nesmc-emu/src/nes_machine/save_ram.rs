@@ -0,0 +1,155 @@
+//! Battery-backed RAM kept between sessions
+//!
+//! Native builds keep it in a `.sav` file next to the ROM. The wasm build has no file system, so
+//! it goes to browser local storage instead, keyed by the ROM file name.
+
+use std::path::Path;
+
+use super::NesMachineError;
+
+/// Save RAM storage of the open ROM
+#[derive(Debug)]
+pub(super) struct SaveRamStore {
+    #[cfg(not(target_arch = "wasm32"))]
+    path: std::path::PathBuf,
+    #[cfg(target_arch = "wasm32")]
+    key: String,
+    /// Contents as of the last load or store. Unchanged RAM isn't written again.
+    stored: Vec<u8>,
+}
+
+impl SaveRamStore {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn for_rom(rom_path: &Path) -> Self {
+        Self {
+            path: rom_path.with_extension("sav"),
+            stored: vec![],
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn for_rom(rom_path: &Path) -> Self {
+        let name = rom_path.file_name().unwrap_or(rom_path.as_os_str());
+        Self {
+            key: format!("nesmachine.sav.{}", name.to_string_lossy()),
+            stored: vec![],
+        }
+    }
+
+    /// Saved contents, or None if nothing has been saved yet
+    pub fn load(&mut self) -> Result<Option<Vec<u8>>, NesMachineError> {
+        let data = self.read()?;
+        if let Some(data) = &data {
+            self.stored = data.clone();
+        }
+        Ok(data)
+    }
+
+    /// Write `data` out, unless it's the same as last time.
+    pub fn store(&mut self, data: &[u8]) -> Result<(), NesMachineError> {
+        if data == self.stored {
+            return Ok(());
+        }
+        self.write(data)?;
+        self.stored = data.to_vec();
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read(&self) -> Result<Option<Vec<u8>>, NesMachineError> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write(&self, data: &[u8]) -> Result<(), NesMachineError> {
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read(&self) -> Result<Option<Vec<u8>>, NesMachineError> {
+        local_storage()?
+            .get_item(&self.key)
+            .map_err(|_| NesMachineError::SaveRamStorage)?
+            .map(|hex| decode_hex(&hex))
+            .transpose()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn write(&self, data: &[u8]) -> Result<(), NesMachineError> {
+        local_storage()?
+            .set_item(&self.key, &encode_hex(data))
+            .map_err(|_| NesMachineError::SaveRamStorage)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, NesMachineError> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or(NesMachineError::SaveRamStorage)
+}
+
+/// Local storage only holds strings.
+#[cfg(any(target_arch = "wasm32", test))]
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(any(target_arch = "wasm32", test))]
+fn decode_hex(hex: &str) -> Result<Vec<u8>, NesMachineError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(NesMachineError::SaveRamStorage);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or(NesMachineError::SaveRamStorage)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        let data = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(encode_hex(&data), "007f80ff");
+        assert_eq!(decode_hex("007f80ff").unwrap(), data);
+        assert!(decode_hex("007").is_err());
+        assert!(decode_hex("0g").is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_store() {
+        let dir = std::env::temp_dir().join("nesmachine_test_save_ram_store");
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.nes");
+        let sav_path = dir.join("game.sav");
+        let _ = std::fs::remove_file(&sav_path);
+
+        let mut store = SaveRamStore::for_rom(&rom_path);
+        assert_eq!(store.load().unwrap(), None);
+        store.store(&[1, 2, 3]).unwrap();
+        assert_eq!(std::fs::read(&sav_path).unwrap(), [1, 2, 3]);
+
+        // Unchanged RAM is not written again.
+        std::fs::remove_file(&sav_path).unwrap();
+        store.store(&[1, 2, 3]).unwrap();
+        assert!(!sav_path.exists());
+
+        store.store(&[4, 5, 6]).unwrap();
+        let mut store = SaveRamStore::for_rom(&rom_path);
+        assert_eq!(store.load().unwrap(), Some(vec![4, 5, 6]));
+        std::fs::remove_file(&sav_path).unwrap();
+    }
+}
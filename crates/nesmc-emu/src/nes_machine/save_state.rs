@@ -0,0 +1,193 @@
+//! Save state serialization
+//!
+//! States are a flat little-endian byte stream. Each component writes its own fields in a
+//! fixed order through [Snapshot]. ROM data is never written, a state can only be loaded
+//! into a machine that has the same ROM open.
+
+use super::NesMachineError;
+
+pub const MAGIC: &[u8; 4] = b"NMST";
+/// Bump on any change to the layout.
+pub const VERSION: u32 = 9;
+
+/// Mutable state that goes into a save state
+pub(crate) trait Snapshot {
+    fn snapshot(&self, w: &mut StateWriter);
+    fn restore(&mut self, r: &mut StateReader) -> Result<(), NesMachineError>;
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.data.extend((value as u64).to_le_bytes());
+    }
+
+    pub fn option_u8(&mut self, value: Option<u8>) {
+        self.bool(value.is_some());
+        self.u8(value.unwrap_or_default());
+    }
+
+    pub fn option_usize(&mut self, value: Option<usize>) {
+        self.bool(value.is_some());
+        self.usize(value.unwrap_or_default());
+    }
+
+    /// Fixed length data. The reader has to know the length.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.data.extend(value);
+    }
+
+    /// Length-prefixed data
+    pub fn vec(&mut self, value: &[u8]) {
+        self.usize(value.len());
+        self.bytes(value);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], NesMachineError> {
+        if self.data.len() < len {
+            return Err(NesMachineError::SaveStateInvalid);
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, NesMachineError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, NesMachineError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(NesMachineError::SaveStateInvalid),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, NesMachineError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, NesMachineError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, NesMachineError> {
+        let value = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(value).map_err(|_| NesMachineError::SaveStateInvalid)
+    }
+
+    pub fn option_u8(&mut self) -> Result<Option<u8>, NesMachineError> {
+        let is_some = self.bool()?;
+        let value = self.u8()?;
+        Ok(is_some.then_some(value))
+    }
+
+    pub fn option_usize(&mut self) -> Result<Option<usize>, NesMachineError> {
+        let is_some = self.bool()?;
+        let value = self.usize()?;
+        Ok(is_some.then_some(value))
+    }
+
+    /// Fill `buf` with fixed length data.
+    pub fn bytes(&mut self, buf: &mut [u8]) -> Result<(), NesMachineError> {
+        buf.copy_from_slice(self.take(buf.len())?);
+        Ok(())
+    }
+
+    /// Read length-prefixed data into `buf`, which must be of the same length.
+    pub fn vec_into(&mut self, buf: &mut [u8]) -> Result<(), NesMachineError> {
+        if self.usize()? != buf.len() {
+            return Err(NesMachineError::SaveStateMismatch);
+        }
+        self.bytes(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut w = StateWriter::default();
+        w.u8(0x12);
+        w.bool(true);
+        w.u16(0x3456);
+        w.u32(0x789a_bcde);
+        w.usize(123_456);
+        w.bytes(&[1, 2, 3]);
+        w.vec(&[4, 5]);
+        w.option_u8(Some(6));
+        w.option_usize(None);
+        let data = w.into_bytes();
+
+        let mut r = StateReader::new(&data);
+        assert_eq!(r.u8().unwrap(), 0x12);
+        assert!(r.bool().unwrap());
+        assert_eq!(r.u16().unwrap(), 0x3456);
+        assert_eq!(r.u32().unwrap(), 0x789a_bcde);
+        assert_eq!(r.usize().unwrap(), 123_456);
+        let mut buf = [0; 3];
+        r.bytes(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3]);
+        let mut buf = [0; 2];
+        r.vec_into(&mut buf).unwrap();
+        assert_eq!(buf, [4, 5]);
+        assert_eq!(r.option_u8().unwrap(), Some(6));
+        assert_eq!(r.option_usize().unwrap(), None);
+        assert!(r.is_empty());
+
+        assert!(matches!(r.u8(), Err(NesMachineError::SaveStateInvalid)));
+    }
+
+    #[test]
+    fn test_length_mismatch() {
+        let mut w = StateWriter::default();
+        w.vec(&[1, 2, 3]);
+        let data = w.into_bytes();
+
+        let mut buf = [0; 4];
+        let result = StateReader::new(&data).vec_into(&mut buf);
+        assert!(matches!(result, Err(NesMachineError::SaveStateMismatch)));
+    }
+}
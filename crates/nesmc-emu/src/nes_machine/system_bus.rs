@@ -0,0 +1,78 @@
+use super::{AudioOutput, bus::Bus, cpu::CpuBus, ppu::Ppu};
+
+/// The machine as the CPU sees it. Every CPU cycle runs the PPU for 3 dots and the APU and
+/// cart for one cycle, so they see the CPU's reads and writes at the right time.
+pub(super) struct SystemBus<'a> {
+    pub bus: &'a mut Bus,
+    pub ppu: &'a mut Ppu,
+    pub audio: &'a mut Option<AudioOutput>,
+    pub cycle_count: &'a mut usize,
+}
+
+impl SystemBus<'_> {
+    /// Run everything but the CPU for one CPU cycle, and any cycles a DMC sample fetch halts
+    /// the CPU for.
+    fn run_cycle(&mut self) {
+        let mut cycles = 1;
+        while cycles > 0 {
+            cycles -= 1;
+            for _ in 0..3 {
+                self.ppu.step(self.bus);
+            }
+            cycles += self.bus.step_apu();
+            self.bus.cart.clock_cpu();
+            if let Some(audio) = self.audio {
+                audio.push(self.bus.apu.mix());
+            }
+            *self.cycle_count += 1;
+        }
+    }
+
+    /// Copy CPU page $XX00-$XXFF to OAM, starting at OAMADDR. The CPU is halted for a cycle,
+    /// one more to align if that was an odd cycle, then 256 read/write pairs.
+    fn run_oam_dma(&mut self, page: u8) {
+        let odd_cycle = *self.cycle_count % 2 == 1;
+        self.run_cycle();
+        if odd_cycle {
+            self.run_cycle();
+        }
+
+        let base = (page as u16) << 8;
+        for i in 0..0x100 {
+            self.run_cycle();
+            let value = self.bus.read(base + i);
+            self.run_cycle();
+            self.bus.write(0x2004, value);
+        }
+    }
+}
+
+impl CpuBus for SystemBus<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.bus.write(addr, value)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.bus.read_immutable(addr)
+    }
+
+    fn tick(&mut self) {
+        // A write to $4014 halts the CPU on the next cycle.
+        if let Some(page) = self.bus.oam_dma_page.take() {
+            self.run_oam_dma(page);
+        }
+        self.run_cycle();
+    }
+
+    fn nmi_line(&self) -> bool {
+        self.bus.ppu_regs.nmi_line()
+    }
+
+    fn irq_line(&self) -> bool {
+        self.bus.irq()
+    }
+}
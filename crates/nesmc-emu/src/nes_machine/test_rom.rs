@@ -0,0 +1,242 @@
+//! Runner for test ROMs that report through PRG-RAM
+//!
+//! blargg's newer test suites (instr_test-v5, cpu_interrupts, ppu_vbl_nmi, apu_test,
+//! mmc3_test, ...) write their status to $6000 and the signature DE B0 61 to $6001-$6003.
+//! The text they print on screen is also written to $6004 onwards, zero-terminated.
+//!
+//! Status values:
+//! - $80: Test is running
+//! - $81: Reset button should be pressed, no sooner than 100 ms from now
+//! - $00-$7f: Done, with a result code. 0 means passed.
+
+use super::NesMachine;
+
+const STATUS_ADDR: u16 = 0x6000;
+const SIGNATURE_ADDR: u16 = 0x6001;
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const TEXT_ADDR: u16 = 0x6004;
+const TEXT_END: u16 = 0x7fff;
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET: u8 = 0x81;
+/// A bit over the 100 ms the ROM asks to wait before reset
+const RESET_DELAY_FRAMES: usize = 8;
+
+/// Status byte at $6000
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Running,
+    ResetRequested,
+    /// Result code. 0 means passed.
+    Done(u8),
+}
+
+/// How a test run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    /// Result code from $6000
+    Failed(u8),
+    /// Frame limit was hit before the ROM reported a result
+    TimedOut,
+    /// CPU hit a JAM opcode before the ROM reported a result
+    Halted,
+}
+
+impl std::fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestOutcome::Passed => write!(f, "Passed"),
+            TestOutcome::Failed(code) => write!(f, "Failed with code {code}"),
+            TestOutcome::TimedOut => write!(f, "No result"),
+            TestOutcome::Halted => write!(f, "CPU halted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub outcome: TestOutcome,
+    /// Text from $6004. Empty if the ROM never wrote the signature.
+    pub message: String,
+    /// Frames run
+    pub frames: usize,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.outcome == TestOutcome::Passed
+    }
+}
+
+/// Current status, or None if the signature isn't there (yet).
+pub fn status(machine: &NesMachine) -> Option<TestStatus> {
+    let bus = &machine.bus;
+    let signature = [0, 1, 2].map(|i| bus.read_immutable(SIGNATURE_ADDR + i));
+    if signature != SIGNATURE {
+        return None;
+    }
+
+    Some(match bus.read_immutable(STATUS_ADDR) {
+        STATUS_RUNNING => TestStatus::Running,
+        STATUS_RESET => TestStatus::ResetRequested,
+        code => TestStatus::Done(code),
+    })
+}
+
+/// Text written so far, or an empty string if the signature isn't there.
+pub fn message(machine: &NesMachine) -> String {
+    if status(machine).is_none() {
+        return String::new();
+    }
+
+    let bytes: Vec<u8> = (TEXT_ADDR..=TEXT_END)
+        .map(|addr| machine.bus.read_immutable(addr))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Run the open ROM until it reports a result, pressing reset when asked to.
+pub fn run(machine: &mut NesMachine, max_frames: usize) -> TestResult {
+    let mut reset_frame = None;
+
+    for frame in 1..=max_frames {
+        machine.step_frame();
+
+        let outcome = match status(machine) {
+            _ if machine.cpu_halted() => TestOutcome::Halted,
+            Some(TestStatus::Done(0)) => TestOutcome::Passed,
+            Some(TestStatus::Done(code)) => TestOutcome::Failed(code),
+            Some(TestStatus::ResetRequested) => {
+                // The status stays at $81 until the ROM starts over.
+                if frame >= *reset_frame.get_or_insert(frame + RESET_DELAY_FRAMES) {
+                    machine.reset();
+                    reset_frame = None;
+                }
+                continue;
+            }
+            Some(TestStatus::Running) | None => continue,
+        };
+
+        return TestResult {
+            outcome,
+            message: message(machine),
+            frames: frame,
+        };
+    }
+
+    TestResult {
+        outcome: TestOutcome::TimedOut,
+        message: message(machine),
+        frames: max_frames,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// LDA #value; STA addr
+    fn store(addr: u16, value: u8) -> [u8; 5] {
+        let [lo, hi] = addr.to_le_bytes();
+        [0xa9, value, 0x8d, lo, hi]
+    }
+
+    /// Code that reports `status` with `text`, then loops. Placed at `origin`.
+    fn report(origin: u16, status: u8, text: &str) -> Vec<u8> {
+        let mut code = vec![];
+        code.extend(store(STATUS_ADDR, STATUS_RUNNING));
+        for (i, byte) in SIGNATURE.into_iter().enumerate() {
+            code.extend(store(SIGNATURE_ADDR + i as u16, byte));
+        }
+        for (i, byte) in text.bytes().chain([0]).enumerate() {
+            code.extend(store(TEXT_ADDR + i as u16, byte));
+        }
+        code.extend(store(STATUS_ADDR, status));
+
+        let [lo, hi] = (origin + code.len() as u16).to_le_bytes();
+        code.extend([0x4c, lo, hi]); // JMP self
+        code
+    }
+
+    /// NROM machine running `code` from $8000
+    fn machine_with_code(code: &[u8]) -> NesMachine {
+        let mut prg = vec![0xea; 0x4000];
+        prg[..code.len()].copy_from_slice(code);
+        prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+
+        let mut rom = b"NES\x1a".to_vec();
+        rom.extend([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rom.extend(prg);
+        rom.extend(vec![0; 0x2000]);
+
+        let mut machine = NesMachine::default();
+        machine.open_data(&rom).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_passed() {
+        let mut machine = machine_with_code(&report(0x8000, 0, "\nPassed\n"));
+        let result = run(&mut machine, 10);
+        assert!(result.passed());
+        assert_eq!(result.message, "\nPassed\n");
+        assert_eq!(result.frames, 1);
+    }
+
+    #[test]
+    fn test_failed() {
+        let mut machine = machine_with_code(&report(0x8000, 3, "Failed #3"));
+        let result = run(&mut machine, 10);
+        assert_eq!(result.outcome, TestOutcome::Failed(3));
+        assert_eq!(result.message, "Failed #3");
+    }
+
+    #[test]
+    fn test_no_signature() {
+        let mut machine = machine_with_code(&[0x4c, 0x00, 0x80]);
+        machine.step_frame();
+        assert_eq!(status(&machine), None);
+        assert_eq!(message(&machine), "");
+
+        let result = run(&mut machine, 5);
+        assert_eq!(result.outcome, TestOutcome::TimedOut);
+        assert_eq!(result.message, "");
+        assert_eq!(result.frames, 5);
+    }
+
+    #[test]
+    fn test_halted() {
+        let mut machine = machine_with_code(&[0x02]);
+        assert_eq!(run(&mut machine, 5).outcome, TestOutcome::Halted);
+    }
+
+    #[test]
+    fn test_reset_requested() {
+        // First run asks for reset. PRG-RAM survives it, so the second run sees $81 and passes.
+        #[rustfmt::skip]
+        let mut code = vec![
+            0xad, 0x00, 0x60, // LDA $6000
+            0xc9, 0x81,       // CMP #$81
+            0xf0, 0x00,       // BEQ second; patched below
+        ];
+        code.extend(report(
+            0x8000 + code.len() as u16,
+            STATUS_RESET,
+            "Press reset",
+        ));
+        code[6] = (code.len() - 7) as u8;
+        code.extend(report(0x8000 + code.len() as u16, 0, "Passed"));
+
+        let mut machine = machine_with_code(&code);
+        machine.step_frame();
+        assert_eq!(status(&machine), Some(TestStatus::ResetRequested));
+        assert_eq!(message(&machine), "Press reset");
+
+        let result = run(&mut machine, 30);
+        assert!(result.passed());
+        assert_eq!(result.message, "Passed");
+        assert!(result.frames > RESET_DELAY_FRAMES);
+    }
+}
@@ -0,0 +1,113 @@
+//! blargg's PPU tests from 2005 (tests/blargg_ppu_tests_2005.09.15b)
+//!
+//! The ROMs print a result code like `$01` on screen, and also leave it at $00f0.
+//! 1 always means passed. See the readme next to the ROMs for the other codes.
+
+use nesmc_emu::NesMachine;
+
+const ROM_DIR: &str = "../../tests/blargg_ppu_tests_2005.09.15b";
+/// The tests take a few seconds at most.
+const MAX_FRAMES: usize = 60 * 20;
+/// Result code in RAM
+const RESULT_ADDR: u16 = 0x00f0;
+
+/// Nametable 0 as text. The ROMs use an ASCII font.
+fn screen_text(machine: &NesMachine) -> Vec<String> {
+    (0..30)
+        .map(|row| {
+            let line: String = (0..32)
+                .map(|col| match machine.bus.read_ppu(0x2000 + row * 32 + col) {
+                    byte @ 0x20..=0x7e => byte as char,
+                    _ => ' ',
+                })
+                .collect();
+            line.trim_end().to_owned()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// The `$XX` result code on screen, once the ROM has printed it.
+fn screen_result(lines: &[String]) -> Option<u8> {
+    lines.iter().find_map(|line| {
+        let hex = line.trim().strip_prefix('$')?;
+        u8::from_str_radix(hex.get(..2)?, 16).ok()
+    })
+}
+
+fn run_rom(name: &str) {
+    let mut machine = NesMachine::default();
+    machine
+        .open_path(format!("{ROM_DIR}/{name}.nes"))
+        .unwrap_or_else(|e| panic!("{name}: {e}"));
+
+    for _ in 0..MAX_FRAMES {
+        machine.step_frame();
+
+        let lines = screen_text(&machine);
+        let Some(result) = screen_result(&lines) else {
+            continue;
+        };
+        let screen = lines.join("\n");
+        assert_eq!(
+            machine.bus.read_immutable(RESULT_ADDR),
+            result,
+            "{name}: result in RAM doesn't match the screen:\n{screen}"
+        );
+        assert_eq!(result, 1, "{name} failed with code {result}:\n{screen}");
+        return;
+    }
+
+    let screen = screen_text(&machine).join("\n");
+    panic!("{name}: no result in {MAX_FRAMES} frames. Screen:\n{screen}");
+}
+
+#[test]
+fn palette_ram() {
+    run_rom("palette_ram");
+}
+
+#[test]
+fn power_up_palette() {
+    run_rom("power_up_palette");
+}
+
+#[test]
+fn sprite_ram() {
+    run_rom("sprite_ram");
+}
+
+#[test]
+fn vbl_clear_time() {
+    run_rom("vbl_clear_time");
+}
+
+#[test]
+fn vram_access() {
+    run_rom("vram_access");
+}
+
+/// The ROMs have no CHR ROM and upload their font to 8KB of CHR RAM, which has to survive a
+/// save state.
+#[test]
+fn chr_ram_in_save_state() {
+    let mut machine = NesMachine::default();
+    machine
+        .open_path(format!("{ROM_DIR}/palette_ram.nes"))
+        .unwrap();
+    assert_eq!(machine.header().unwrap().len_chr_rom, 0);
+    for _ in 0..60 {
+        machine.step_frame();
+    }
+
+    let patterns: Vec<u8> = (0..0x2000).map(|addr| machine.bus.read_ppu(addr)).collect();
+    assert!(patterns.iter().any(|&byte| byte != 0), "No font in CHR RAM");
+
+    let state = machine.save_state(false);
+    for addr in 0..0x2000 {
+        machine.bus.write_ppu(addr, 0);
+    }
+    machine.load_state(&state).unwrap();
+    let restored: Vec<u8> = (0..0x2000).map(|addr| machine.bus.read_ppu(addr)).collect();
+    assert_eq!(restored, patterns);
+}
@@ -0,0 +1,117 @@
+//! MMC1 ROM images run through [test_rom::run]
+//!
+//! No third-party MMC1 test ROMs ship with the repo. [bank_switch] boots a self-checking image
+//! built here that reports through $6000 like blargg's ROMs do. [external_roms] runs real ones
+//! from `MMC1_TEST_ROMS_DIR`, like holy mapperel or blargg's mmc1 tests, when asked to:
+//!
+//! `MMC1_TEST_ROMS_DIR=path/to/roms cargo test --test mmc1_tests -- --ignored`
+
+use nesmc_emu::{NesMachine, test_rom};
+
+const KIB: usize = 1024;
+const MAX_FRAMES: usize = 60 * 30;
+
+/// PRG ROM where every byte holds its own 16KB bank no.
+fn numbered_prg(banks: usize) -> Vec<u8> {
+    (0..banks).flat_map(|i| vec![i as u8; 16 * KIB]).collect()
+}
+
+/// LDA #value; STA addr
+fn store(addr: u16, value: u8) -> [u8; 5] {
+    let [lo, hi] = addr.to_le_bytes();
+    [0xa9, value, 0x8d, lo, hi]
+}
+
+/// Write 5-bit `value` to the MMC1 register at `addr`, one bit at a time.
+fn write_reg(addr: u16, value: u8) -> Vec<u8> {
+    let [lo, hi] = addr.to_le_bytes();
+    let mut code = vec![0xa9, value]; // LDA #value
+    for i in 0..5 {
+        code.extend([0x8d, lo, hi]); // STA addr
+        if i < 4 {
+            code.push(0x4a); // LSR A
+        }
+    }
+    code
+}
+
+/// 128KB MMC1 image. Code in the fixed last bank switches $8000 through a few banks and
+/// checks each one, then reports 0 on success, 1 on a wrong bank.
+fn bank_switch_rom() -> Vec<u8> {
+    const FAIL: u16 = 0xc200;
+
+    let mut code = vec![];
+    code.extend(store(0x6000, 0x80));
+    code.extend(store(0x6001, 0xde));
+    code.extend(store(0x6002, 0xb0));
+    code.extend(store(0x6003, 0x61));
+    code.extend(store(0x6004, 0x00));
+    for bank in [5, 2, 6, 0] {
+        code.extend(write_reg(0xe000, bank));
+        code.extend([0xad, 0x00, 0x80]); // LDA $8000
+        code.extend([0xc9, bank]); // CMP #bank
+        code.extend([0xf0, 0x03]); // BEQ +3
+        code.extend([0x4c, FAIL as u8, (FAIL >> 8) as u8]); // JMP fail
+    }
+    code.extend(store(0x6000, 0x00));
+    let pass_loop = 0xc000 + code.len() as u16;
+    code.extend([0x4c, pass_loop as u8, (pass_loop >> 8) as u8]);
+
+    let mut fail = store(0x6000, 0x01).to_vec();
+    let fail_loop = FAIL + fail.len() as u16;
+    fail.extend([0x4c, fail_loop as u8, (fail_loop >> 8) as u8]);
+
+    let mut prg = numbered_prg(8);
+    let last = 7 * 16 * KIB;
+    prg[last..last + code.len()].copy_from_slice(&code);
+    let fail_off = last + (FAIL - 0xc000) as usize;
+    prg[fail_off..fail_off + fail.len()].copy_from_slice(&fail);
+    prg[last + 0x3ffc] = 0x00;
+    prg[last + 0x3ffd] = 0xc0;
+
+    let mut rom = b"NES\x1a".to_vec();
+    rom.extend([8, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    rom.extend(prg);
+    rom.extend(vec![0; 8 * KIB]);
+    rom
+}
+
+#[test]
+fn bank_switch() {
+    let mut machine = NesMachine::default();
+    machine.open_data(&bank_switch_rom()).unwrap();
+
+    let result = test_rom::run(&mut machine, MAX_FRAMES);
+    assert!(result.passed(), "{}: {}", result.outcome, result.message);
+}
+
+#[test]
+#[ignore = "needs MMC1_TEST_ROMS_DIR"]
+fn external_roms() {
+    let dir = std::env::var("MMC1_TEST_ROMS_DIR").expect("MMC1_TEST_ROMS_DIR is not set");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{dir}: {e}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "nes"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No .nes files in {dir}");
+
+    let mut failures = vec![];
+    for path in paths {
+        let mut machine = NesMachine::default();
+        machine
+            .open_path(&path)
+            .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        let result = test_rom::run(&mut machine, MAX_FRAMES);
+        if !result.passed() {
+            failures.push(format!(
+                "{}: {}\n{}",
+                path.display(),
+                result.outcome,
+                result.message
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,239 @@
+//! Per-opcode CPU tests in the SingleStepTests format (github.com/SingleStepTests/65x02)
+//!
+//! Each `XX.json` file holds cases for opcode XX: the initial state, the final state, and the
+//! bus activity of every cycle. The `nes6502` set is ~2 GB, so it isn't in the repo.
+//! Put it in [DEFAULT_DIR] or point `SINGLE_STEP_TESTS_DIR` at it, and run the ignored test:
+//!
+//! `cargo test --test single_step_tests -- --ignored`
+
+use std::path::{Path, PathBuf};
+
+use nesmc_emu::{Cpu, CpuBus, CpuStatus};
+use nesmc_types::instruction::OpCode;
+use serde::Deserialize;
+
+const DEFAULT_DIR: &str = "../../tests/SingleStepTests/nes6502/v1";
+/// Unused and B bits, which don't exist in the CPU
+const P_IGNORED: u8 = 0x30;
+
+#[derive(Debug, Deserialize)]
+struct Case {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    /// Address, value and "read"/"write" of each cycle
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+/// 64 KB of RAM, no devices. Logs every access in the format of [Case::cycles].
+struct FlatBus {
+    ram: Vec<u8>,
+    cycles: usize,
+    log: Vec<(u16, u8, String)>,
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        Self {
+            ram: vec![0; 0x10000],
+            cycles: 0,
+            log: vec![],
+        }
+    }
+}
+
+impl CpuBus for FlatBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.ram[addr as usize];
+        self.log.push((addr, value, "read".into()));
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.log.push((addr, value, "write".into()));
+        self.ram[addr as usize] = value;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+/// Run one case. Returns the first thing that differs from the expected final state.
+fn run_case(bus: &mut FlatBus, case: &Case) -> Result<(), String> {
+    let initial = &case.initial;
+    for &(addr, value) in &initial.ram {
+        bus.ram[addr as usize] = value;
+    }
+
+    let mut cpu = Cpu::new(bus);
+    cpu.pc = initial.pc;
+    cpu.sp = initial.s;
+    cpu.a = initial.a;
+    cpu.x = initial.x;
+    cpu.y = initial.y;
+    cpu.status = CpuStatus::from(initial.p);
+
+    bus.cycles = 0;
+    bus.log.clear();
+    cpu.step(bus);
+
+    let expected = &case.expected;
+    let registers = [
+        ("PC", expected.pc, cpu.pc),
+        ("S", expected.s as u16, cpu.sp as u16),
+        ("A", expected.a as u16, cpu.a as u16),
+        ("X", expected.x as u16, cpu.x as u16),
+        ("Y", expected.y as u16, cpu.y as u16),
+        (
+            "P",
+            (expected.p | P_IGNORED) as u16,
+            (u8::from(cpu.status) | P_IGNORED) as u16,
+        ),
+    ];
+    for (name, expected, actual) in registers {
+        if expected != actual {
+            return Err(format!(
+                "{name}: expected ${expected:02x}, got ${actual:02x}"
+            ));
+        }
+    }
+    if bus.cycles != case.cycles.len() {
+        return Err(format!(
+            "cycles: expected {}, got {}",
+            case.cycles.len(),
+            bus.cycles
+        ));
+    }
+    for (i, (expected, actual)) in case.cycles.iter().zip(&bus.log).enumerate() {
+        if expected != actual {
+            return Err(format!("cycle {i}: expected {expected:?}, got {actual:?}"));
+        }
+    }
+    if bus.log.len() != bus.cycles {
+        return Err(format!(
+            "{} accesses in {} cycles",
+            bus.log.len(),
+            bus.cycles
+        ));
+    }
+    for &(addr, expected) in &expected.ram {
+        let actual = bus.ram[addr as usize];
+        if expected != actual {
+            return Err(format!(
+                "${addr:04x}: expected ${expected:02x}, got ${actual:02x}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run cases until one fails. The error names the case and what differed.
+fn run_cases(cases: &[Case]) -> Result<(), String> {
+    let mut bus = FlatBus::default();
+    for case in cases {
+        run_case(&mut bus, case).map_err(|e| format!("[{}] {e}", case.name))?;
+        // Only the bytes a case touches are listed, clear them for the next one.
+        for &(addr, _) in case.initial.ram.iter().chain(&case.expected.ram) {
+            bus.ram[addr as usize] = 0;
+        }
+    }
+    Ok(())
+}
+
+fn test_dir() -> PathBuf {
+    std::env::var_os("SINGLE_STEP_TESTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIR))
+}
+
+fn load_cases(path: &Path) -> Vec<Case> {
+    let data = std::fs::read(path).unwrap_or_else(|e| panic!("{path:?}: {e}"));
+    serde_json::from_slice(&data).unwrap_or_else(|e| panic!("{path:?}: {e}"))
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests vectors"]
+fn single_step_tests() {
+    let dir = test_dir();
+    assert!(dir.is_dir(), "{dir:?} not found");
+
+    let mut opcodes_run = 0;
+    let mut failures = vec![];
+    for opcode in 0..=0xffu8 {
+        // JAM halts the CPU, the vectors expect it to keep reading.
+        if OpCode::from(opcode) == OpCode::Jam {
+            continue;
+        }
+        let path = dir.join(format!("{opcode:02x}.json"));
+        if !path.exists() {
+            continue;
+        }
+        opcodes_run += 1;
+        if let Err(e) = run_cases(&load_cases(&path)) {
+            failures.push(format!("{opcode:02x} {:?}: {e}", OpCode::from(opcode)));
+        }
+    }
+
+    assert!(opcodes_run > 0, "No XX.json files in {dir:?}");
+
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+/// Check the harness itself on a handwritten case
+#[test]
+fn single_step_harness() {
+    // LDA ($10),Y with a page cross
+    let json = r#"[{
+        "name": "b1 10 ff",
+        "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 255, "p": 36,
+            "ram": [[512, 177], [513, 16], [16, 2], [17, 3], [1025, 128]] },
+        "final": { "pc": 514, "s": 253, "a": 128, "x": 0, "y": 255, "p": 164,
+            "ram": [[512, 177], [513, 16], [16, 2], [17, 3], [1025, 128]] },
+        "cycles": [[512, 177, "read"], [513, 16, "read"], [16, 2, "read"],
+            [17, 3, "read"], [769, 0, "read"], [1025, 128, "read"]]
+    }]"#;
+    let mut cases: Vec<Case> = serde_json::from_str(json).unwrap();
+    assert_eq!(run_cases(&cases), Ok(()));
+
+    cases[0].expected.a = 0x7f;
+    assert_eq!(
+        run_cases(&cases),
+        Err("[b1 10 ff] A: expected $7f, got $80".into())
+    );
+
+    cases[0].expected.a = 0x80;
+    cases[0].cycles[4].0 = 0x0401;
+    assert_eq!(
+        run_cases(&cases),
+        Err(r#"[b1 10 ff] cycle 4: expected (1025, 0, "read"), got (769, 0, "read")"#.into())
+    );
+
+    cases[0].cycles[4].0 = 0x0301;
+    cases[0].expected.ram[4] = (0x0401, 0x42);
+    assert_eq!(
+        run_cases(&cases),
+        Err("[b1 10 ff] $0401: expected $42, got $80".into())
+    );
+}
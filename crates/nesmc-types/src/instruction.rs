@@ -3,8 +3,6 @@ use OpCode::*;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 
 pub enum OpCode {
-    /// Illegal, not yet built
-    Illegal(u8),
     Jam,
 
     AdcAbs,
@@ -15,6 +13,8 @@ pub enum OpCode {
     AdcXInd,
     AdcZpg,
     AdcZpgX,
+    AlrImm,
+    AncImm,
     AndAbs,
     AndAbsX,
     AndAbsY,
@@ -23,6 +23,8 @@ pub enum OpCode {
     AndXInd,
     AndZpg,
     AndZpgX,
+    AneImm,
+    ArrImm,
     AslA,
     AslAbs,
     AslAbsX,
@@ -100,6 +102,7 @@ pub enum OpCode {
     LaxIndY,
     LaxZpg,
     LaxZpgY,
+    LasAbsY,
     LdaAbs,
     LdaAbsX,
     LdaAbsY,
@@ -123,6 +126,7 @@ pub enum OpCode {
     LsrAbsX,
     LsrZpg,
     LsrZpgX,
+    LxaImm,
     NopAbs,
     NopAbsX,
     NopImm,
@@ -179,9 +183,14 @@ pub enum OpCode {
     SbcXInd,
     SbcZpg,
     SbcZpgX,
+    SbxImm,
     SecImpl,
     SedImpl,
     SeiImpl,
+    ShaAbsY,
+    ShaIndY,
+    ShxAbsY,
+    ShyAbsX,
     SloAbs,
     SloAbsX,
     SloAbsY,
@@ -209,6 +218,7 @@ pub enum OpCode {
     StyAbs,
     StyZpg,
     StyZpgX,
+    TasAbsY,
     TaxImpl,
     TayImpl,
     TsxImpl,
@@ -231,7 +241,7 @@ impl From<u8> for OpCode {
             0x08 => PhpImpl,
             0x09 => OraImm,
             0x0a => AslA,
-            0x0b => Illegal(value),
+            0x0b => AncImm,
             0x0c => NopAbs,
             0x0d => OraAbs,
             0x0e => AslAbs,
@@ -265,7 +275,7 @@ impl From<u8> for OpCode {
             0x28 => PlpImpl,
             0x29 => AndImm,
             0x2a => RolA,
-            0x2b => Illegal(value),
+            0x2b => AncImm,
             0x2c => BitAbs,
             0x2d => AndAbs,
             0x2e => RolAbs,
@@ -299,7 +309,7 @@ impl From<u8> for OpCode {
             0x48 => PhaImpl,
             0x49 => EorImm,
             0x4a => LsrA,
-            0x4b => Illegal(value),
+            0x4b => AlrImm,
             0x4c => JmpAbs,
             0x4d => EorAbs,
             0x4e => LsrAbs,
@@ -333,7 +343,7 @@ impl From<u8> for OpCode {
             0x68 => PlaImpl,
             0x69 => AdcImm,
             0x6a => RorA,
-            0x6b => Illegal(value),
+            0x6b => ArrImm,
             0x6c => JmpInd,
             0x6d => AdcAbs,
             0x6e => RorAbs,
@@ -367,7 +377,7 @@ impl From<u8> for OpCode {
             0x88 => DeyImpl,
             0x89 => NopImm,
             0x8a => TxaImpl,
-            0x8b => Illegal(value),
+            0x8b => AneImm,
             0x8c => StyAbs,
             0x8d => StaAbs,
             0x8e => StxAbs,
@@ -376,7 +386,7 @@ impl From<u8> for OpCode {
             0x90 => BccRel,
             0x91 => StaIndY,
             0x92 => Jam,
-            0x93 => Illegal(value),
+            0x93 => ShaIndY,
             0x94 => StyZpgX,
             0x95 => StaZpgX,
             0x96 => StxZpgY,
@@ -384,11 +394,11 @@ impl From<u8> for OpCode {
             0x98 => TyaImpl,
             0x99 => StaAbsY,
             0x9a => TxsImpl,
-            0x9b => Illegal(value),
-            0x9c => Illegal(value),
+            0x9b => TasAbsY,
+            0x9c => ShyAbsX,
             0x9d => StaAbsX,
-            0x9e => Illegal(value),
-            0x9f => Illegal(value),
+            0x9e => ShxAbsY,
+            0x9f => ShaAbsY,
 
             0xa0 => LdyImm,
             0xa1 => LdaXInd,
@@ -401,7 +411,7 @@ impl From<u8> for OpCode {
             0xa8 => TayImpl,
             0xa9 => LdaImm,
             0xaa => TaxImpl,
-            0xab => Illegal(value),
+            0xab => LxaImm,
             0xac => LdyAbs,
             0xad => LdaAbs,
             0xae => LdxAbs,
@@ -418,7 +428,7 @@ impl From<u8> for OpCode {
             0xb8 => ClvImpl,
             0xb9 => LdaAbsY,
             0xba => TsxImpl,
-            0xbb => Illegal(value),
+            0xbb => LasAbsY,
             0xbc => LdyAbsX,
             0xbd => LdaAbsX,
             0xbe => LdxAbsY,
@@ -435,7 +445,7 @@ impl From<u8> for OpCode {
             0xc8 => InyImpl,
             0xc9 => CmpImm,
             0xca => DexImpl,
-            0xcb => Illegal(value),
+            0xcb => SbxImm,
             0xcc => CpyAbs,
             0xcd => CmpAbs,
             0xce => DecAbs,
@@ -497,7 +507,7 @@ impl From<u8> for OpCode {
 
 impl OpCode {
     pub const fn is_illegal(&self) -> bool {
-        matches!(self, Illegal(_) | Jam)
+        matches!(self, Jam)
     }
 }
 
@@ -552,8 +562,8 @@ mod tests {
         assert_eq!(SbcImm, OpCode::from(0xe9));
         assert_eq!(SbcZpg, OpCode::from(0xe5));
         assert_eq!(SbcZpgX, OpCode::from(0xf5));
-        assert_eq!(SbcAbs, OpCode::from(0xeD));
-        assert_eq!(SbcAbsX, OpCode::from(0xfD));
+        assert_eq!(SbcAbs, OpCode::from(0xed));
+        assert_eq!(SbcAbsX, OpCode::from(0xfd));
         assert_eq!(SbcAbsY, OpCode::from(0xf9));
         assert_eq!(SbcXInd, OpCode::from(0xe1));
         assert_eq!(SbcIndY, OpCode::from(0xf1));
@@ -637,4 +647,22 @@ mod tests {
         assert_eq!(NopZpgX, OpCode::from(0xd4));
         assert_eq!(NopZpgX, OpCode::from(0xf4));
     }
+
+    #[test]
+    fn test_match_ill_unstable() {
+        assert_eq!(AncImm, OpCode::from(0x0b));
+        assert_eq!(AncImm, OpCode::from(0x2b));
+        assert_eq!(AlrImm, OpCode::from(0x4b));
+        assert_eq!(ArrImm, OpCode::from(0x6b));
+        assert_eq!(AneImm, OpCode::from(0x8b));
+        assert_eq!(LxaImm, OpCode::from(0xab));
+        assert_eq!(SbxImm, OpCode::from(0xcb));
+
+        assert_eq!(ShaIndY, OpCode::from(0x93));
+        assert_eq!(ShaAbsY, OpCode::from(0x9f));
+        assert_eq!(TasAbsY, OpCode::from(0x9b));
+        assert_eq!(ShyAbsX, OpCode::from(0x9c));
+        assert_eq!(ShxAbsY, OpCode::from(0x9e));
+        assert_eq!(LasAbsY, OpCode::from(0xbb));
+    }
 }